@@ -1,17 +1,19 @@
-#![feature(async_await)]
-#![feature(async_closure)]
 #![feature(test)]
 #![deny(nonstandard_style)]
 #![deny(future_incompatible)]
 #![deny(rust_2018_idioms)]
 #![warn(unused)]
+// failure_derive predates the non_local_definitions lint and trips it on every `#[derive(Fail)]`
+#![allow(non_local_definitions)]
 
 pub mod debugging;
 pub mod loading;
 pub mod logging;
+pub mod render_graph;
 pub mod rhi;
 pub mod settings;
 pub mod shaderpack;
+pub mod shaders;
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,167 @@
+//! Support for the different kinds of archives that resource packs can be shipped in
+
+use crate::loading::*;
+use std::path::Path;
+
+/// A kind of archive that a resource pack can be stored in
+///
+/// Nova only knows about zip archives out of the box, but other formats can be added by implementing this trait and
+/// registering the implementation with an `ArchiveFormatRegistry`
+pub trait ArchiveFormat: Send + Sync {
+    /// The file extensions that archives of this format use, without the leading dot
+    fn extensions(&self) -> &[&str];
+
+    /// Opens an archive of this format as a resource pack
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - The path to the archive
+    fn open(&self, path: &Path) -> Result<Box<dyn ResourcePack>, ResourcePackError>;
+}
+
+/// Zip archives, the format that nearly every shaderpack and resourcepack is distributed in
+#[derive(Debug, Clone, Default)]
+pub struct ZipArchiveFormat;
+
+impl ArchiveFormat for ZipArchiveFormat {
+    fn extensions(&self) -> &[&str] {
+        &["zip"]
+    }
+
+    fn open(&self, path: &Path) -> Result<Box<dyn ResourcePack>, ResourcePackError> {
+        Ok(Box::new(ZipResourcePack::open(path)?))
+    }
+}
+
+/// All the archive formats that Nova can open resource packs from
+///
+/// Callers should open resource packs through `open_pack` instead of picking a backend themselves, so that supporting a
+/// new archive format only means registering it here
+pub struct ArchiveFormatRegistry {
+    formats: Vec<Box<dyn ArchiveFormat>>,
+}
+
+impl ArchiveFormatRegistry {
+    /// Creates a registry that doesn't know about any archive formats
+    ///
+    /// Such a registry can still open resource packs that are folders on disk
+    pub fn new() -> ArchiveFormatRegistry {
+        ArchiveFormatRegistry { formats: Vec::new() }
+    }
+
+    /// Adds an archive format to this registry
+    ///
+    /// If multiple formats claim the same extension, the one that was registered last is used
+    ///
+    /// # Parameters
+    ///
+    /// * `format` - The format to add
+    pub fn register<F: ArchiveFormat + 'static>(&mut self, format: F) {
+        self.formats.push(Box::new(format));
+    }
+
+    /// Finds the format that can open the archive at the provided path, based on its extension
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - The path to the archive
+    pub fn format_for(&self, path: &Path) -> Option<&dyn ArchiveFormat> {
+        let extension = path.extension()?.to_string_lossy();
+        self.formats
+            .iter()
+            .rev()
+            .find(|format| format.extensions().iter().any(|e| e.eq_ignore_ascii_case(&extension)))
+            .map(|format| format.as_ref())
+    }
+
+    /// Opens the resource pack at the provided path
+    ///
+    /// Folders are opened as `FolderResourcePack`s. Files are opened with the archive format that matches their
+    /// extension
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - The path to the folder or archive that holds the resource pack
+    pub fn open_pack(&self, path: &Path) -> Result<Box<dyn ResourcePack>, ResourcePackError> {
+        if path.is_dir() {
+            return Ok(Box::new(FolderResourcePack::new(path)?));
+        }
+
+        match self.format_for(path) {
+            Some(format) => format.open(path),
+            None => Err(ResourcePackError::UnsupportedArchiveFormat(path.to_path_buf())),
+        }
+    }
+}
+
+impl Default for ArchiveFormatRegistry {
+    /// Creates a registry that knows about every archive format Nova supports out of the box
+    fn default() -> ArchiveFormatRegistry {
+        let mut registry = ArchiveFormatRegistry::new();
+        registry.register(ZipArchiveFormat);
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::loading::*;
+    use std::fs;
+    use std::fs::File;
+    use std::io::Write;
+    use std::path::Path;
+    use zip::ZipWriter;
+    use zip::write::FileOptions;
+
+    /// Pretends to be a Java archive, which is really just a zip file with a different extension
+    struct JarArchiveFormat;
+
+    impl ArchiveFormat for JarArchiveFormat {
+        fn extensions(&self) -> &[&str] {
+            &["jar"]
+        }
+
+        fn open(&self, path: &Path) -> Result<Box<dyn ResourcePack>, ResourcePackError> {
+            Ok(Box::new(ZipResourcePack::open(path)?))
+        }
+    }
+
+    fn write_zip(path: &Path) {
+        let mut writer = ZipWriter::new(File::create(path).unwrap());
+        writer.start_file("pack.mcmeta", FileOptions::default()).unwrap();
+        writer.write_all(b"{}").unwrap();
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn opens_folders_and_zips() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("folder")).unwrap();
+        fs::write(dir.path().join("folder/pack.mcmeta"), "{}").unwrap();
+        write_zip(&dir.path().join("archive.ZIP"));
+
+        let registry = ArchiveFormatRegistry::default();
+        for name in &["folder", "archive.ZIP"] {
+            let pack = registry.open_pack(&dir.path().join(name)).unwrap();
+            assert_eq!(pack.read_string(Path::new("pack.mcmeta")).unwrap(), "{}");
+        }
+    }
+
+    #[test]
+    fn new_formats_can_be_plugged_in() {
+        let dir = tempfile::tempdir().unwrap();
+        let jar_path = dir.path().join("mod.jar");
+        write_zip(&jar_path);
+
+        let mut registry = ArchiveFormatRegistry::default();
+        match registry.open_pack(&jar_path) {
+            Err(ResourcePackError::UnsupportedArchiveFormat(_)) => {}
+            Err(other) => panic!("Expected UnsupportedArchiveFormat, got {:?}", other),
+            Ok(_) => panic!("Jars shouldn't be supported by default"),
+        }
+
+        registry.register(JarArchiveFormat);
+        let pack = registry.open_pack(&jar_path).unwrap();
+        assert!(pack.exists(Path::new("pack.mcmeta")));
+    }
+}
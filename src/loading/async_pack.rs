@@ -0,0 +1,153 @@
+//! Asynchronous access to resource packs
+
+use crate::loading::*;
+use futures::executor::ThreadPool;
+use futures::future;
+use futures::prelude::*;
+use futures::task::SpawnExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Reads files from a resource pack on a thread pool
+///
+/// Loading a shaderpack or a resourcepack means reading hundreds of files. Doing that on the render thread would stall
+/// rendering, so this wraps a `ResourcePack` and runs every read as a task on a thread pool instead. Each method starts
+/// its task as soon as it's called and returns a future that resolves once the task finishes, so many reads can be in
+/// flight at once
+///
+/// Cloning an `AsyncResourcePack` is cheap, and the clones share the same pack and thread pool
+#[derive(Clone)]
+pub struct AsyncResourcePack {
+    pack: Arc<dyn ResourcePack>,
+    pool: ThreadPool,
+}
+
+impl AsyncResourcePack {
+    /// Wraps a resource pack so that it can be read from asynchronously
+    ///
+    /// # Parameters
+    ///
+    /// * `pack` - The pack to read from
+    /// * `pool` - The thread pool to read files on
+    pub fn new(pack: Arc<dyn ResourcePack>, pool: ThreadPool) -> AsyncResourcePack {
+        AsyncResourcePack { pack, pool }
+    }
+
+    /// The pack that this reads from
+    pub fn pack(&self) -> &Arc<dyn ResourcePack> {
+        &self.pack
+    }
+
+    /// Asynchronously checks if a file or directory exists in the pack
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - The path to check, relative to the root of the pack
+    pub fn exists<P: Into<PathBuf>>(&self, path: P) -> impl Future<Output = Result<bool, ResourcePackError>> {
+        self.spawn(path.into(), |pack, path| Ok(pack.exists(path)))
+    }
+
+    /// Asynchronously reads an entire file from the pack
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - The path of the file to read, relative to the root of the pack
+    pub fn read_bytes<P: Into<PathBuf>>(&self, path: P) -> impl Future<Output = Result<Vec<u8>, ResourcePackError>> {
+        self.spawn(path.into(), |pack, path| pack.read_bytes(path))
+    }
+
+    /// Asynchronously reads an entire file from the pack as a UTF-8 string
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - The path of the file to read, relative to the root of the pack
+    pub fn read_string<P: Into<PathBuf>>(&self, path: P) -> impl Future<Output = Result<String, ResourcePackError>> {
+        self.spawn(path.into(), |pack, path| pack.read_string(path))
+    }
+
+    /// Asynchronously lists the files and directories directly inside a directory of the pack
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - The directory to list, relative to the root of the pack
+    pub fn list_directory<P: Into<PathBuf>>(
+        &self,
+        path: P,
+    ) -> impl Future<Output = Result<Vec<PathBuf>, ResourcePackError>> {
+        self.spawn(path.into(), |pack, path| pack.list_directory(path))
+    }
+
+    /// Reads many files from the pack at once
+    ///
+    /// All the reads are started before this method returns. The results are in the same order as `paths`
+    ///
+    /// # Parameters
+    ///
+    /// * `paths` - The paths of the files to read, relative to the root of the pack
+    pub fn read_many<I>(&self, paths: I) -> impl Future<Output = Vec<Result<Vec<u8>, ResourcePackError>>>
+    where
+        I: IntoIterator,
+        I::Item: Into<PathBuf>,
+    {
+        future::join_all(paths.into_iter().map(|path| self.read_bytes(path)).collect::<Vec<_>>())
+    }
+
+    fn spawn<T, F>(&self, path: PathBuf, read: F) -> impl Future<Output = Result<T, ResourcePackError>>
+    where
+        T: Send + 'static,
+        F: FnOnce(&dyn ResourcePack, &Path) -> Result<T, ResourcePackError> + Send + 'static,
+    {
+        let pack = self.pack.clone();
+        let task_path = path.clone();
+        let handle = self
+            .pool
+            .clone()
+            .spawn_with_handle(async move { read(pack.as_ref(), &task_path) });
+
+        async move {
+            match handle {
+                Ok(handle) => handle.await,
+                Err(_) => Err(ResourcePackError::CouldNotStartTask(path)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::loading::*;
+    use futures::executor;
+    use futures::executor::ThreadPool;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    #[test]
+    fn reads_files_on_the_thread_pool() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("shaders")).unwrap();
+        for i in 0..16 {
+            fs::write(dir.path().join(format!("shaders/{}.glsl", i)), i.to_string()).unwrap();
+        }
+
+        let pack = Arc::new(FolderResourcePack::new(dir.path()).unwrap());
+        let pack = AsyncResourcePack::new(pack, ThreadPool::new().unwrap());
+
+        executor::block_on(async {
+            assert!(pack.exists("shaders/3.glsl").await.unwrap());
+            assert_eq!(pack.read_string("shaders/3.glsl").await.unwrap(), "3");
+            assert_eq!(pack.list_directory("shaders").await.unwrap().len(), 16);
+
+            let paths: Vec<PathBuf> = (0..17).map(|i| PathBuf::from(format!("shaders/{}.glsl", i))).collect();
+            let results = pack.read_many(paths).await;
+            for (i, result) in results.iter().take(16).enumerate() {
+                assert_eq!(result.as_ref().unwrap(), i.to_string().as_bytes());
+            }
+            match &results[16] {
+                Err(ResourcePackError::NotFound(_)) => {}
+                other => panic!("Expected NotFound, got {:?}", other),
+            }
+        });
+    }
+}
@@ -0,0 +1,221 @@
+//! Resource packs that live in a folder on the filesystem
+
+use crate::loading::*;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// A resource pack that's been unpacked into a folder on disk
+///
+/// Shader authors usually work on an unpacked shaderpack, and Minecraft will happily load resourcepacks from folders
+/// too
+///
+/// Paths are normalized with `normalize_pack_path` before they're looked up. Lookups can optionally fall back to
+/// ignoring case, for packs that were written on case-insensitive filesystems
+#[derive(Debug, Clone)]
+pub struct FolderResourcePack {
+    /// The folder that holds the pack's files
+    root: PathBuf,
+
+    /// Whether to look for a file with different capitalization when a file can't be found
+    case_insensitive_fallback: bool,
+}
+
+impl FolderResourcePack {
+    /// Opens the resource pack in the provided folder
+    ///
+    /// # Parameters
+    ///
+    /// * `root` - The folder that holds the pack's files
+    pub fn new<P: Into<PathBuf>>(root: P) -> Result<FolderResourcePack, ResourcePackError> {
+        let root = root.into();
+        let metadata = fs::metadata(&root).map_err(|e| ResourcePackError::io(&root, e))?;
+        if !metadata.is_dir() {
+            return Err(ResourcePackError::NotADirectory(root));
+        }
+
+        Ok(FolderResourcePack {
+            root,
+            case_insensitive_fallback: false,
+        })
+    }
+
+    /// Sets whether files that can't be found should be looked for again while ignoring case
+    ///
+    /// When a file is found this way, a warning is logged so that the pack's author can fix the path
+    ///
+    /// # Parameters
+    ///
+    /// * `enabled` - True to look for files while ignoring case, false to only find files with exactly matching paths
+    pub fn with_case_insensitive_fallback(mut self, enabled: bool) -> FolderResourcePack {
+        self.case_insensitive_fallback = enabled;
+        self
+    }
+
+    /// The folder that holds the pack's files
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Finds the pack-relative path of the file that `path` refers to
+    fn resolve(&self, path: &Path) -> Result<PathBuf, ResourcePackError> {
+        let normalized = normalize_pack_path(path)?;
+        if !self.case_insensitive_fallback || self.root.join(&normalized).exists() {
+            return Ok(normalized);
+        }
+
+        let mut resolved = PathBuf::new();
+        for component in normalized.iter() {
+            let exact = resolved.join(component);
+            if self.root.join(&exact).exists() {
+                resolved = exact;
+                continue;
+            }
+
+            let lowercase_component = component.to_string_lossy().to_lowercase();
+            let matching_entry = fs::read_dir(self.root.join(&resolved)).ok().and_then(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .find(|entry| entry.file_name().to_string_lossy().to_lowercase() == lowercase_component)
+            });
+
+            match matching_entry {
+                Some(entry) => resolved.push(entry.file_name()),
+                None => return Ok(normalized),
+            }
+        }
+
+        warn_case_mismatch(path, &resolved);
+        Ok(resolved)
+    }
+}
+
+impl ResourcePack for FolderResourcePack {
+    fn exists(&self, path: &Path) -> bool {
+        match self.resolve(path) {
+            Ok(resolved) => self.root.join(resolved).exists(),
+            Err(_) => false,
+        }
+    }
+
+    fn read_bytes(&self, path: &Path) -> Result<Vec<u8>, ResourcePackError> {
+        let resolved = self.resolve(path)?;
+        fs::read(self.root.join(resolved)).map_err(|e| ResourcePackError::io(path, e))
+    }
+
+    fn list_directory(&self, path: &Path) -> Result<Vec<PathBuf>, ResourcePackError> {
+        let resolved = self.resolve(path)?;
+        let full_path = self.root.join(&resolved);
+        if full_path.is_file() {
+            return Err(ResourcePackError::NotADirectory(path.to_path_buf()));
+        }
+
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&full_path).map_err(|e| ResourcePackError::io(path, e))? {
+            let entry = entry.map_err(|e| ResourcePackError::io(path, e))?;
+            entries.push(resolved.join(entry.file_name()));
+        }
+        entries.sort();
+
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::loading::*;
+    use std::fs;
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    fn make_pack() -> (tempfile::TempDir, FolderResourcePack) {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("shaders/lib")).unwrap();
+        fs::write(dir.path().join("passes.json"), "[]").unwrap();
+        fs::write(dir.path().join("shaders/gbuffers.vert"), "void main() {}").unwrap();
+        fs::write(dir.path().join("shaders/lib/common.glsl"), [0xFF, 0xFE]).unwrap();
+
+        let pack = FolderResourcePack::new(dir.path()).unwrap();
+        (dir, pack)
+    }
+
+    #[test]
+    fn reads_files_relative_to_the_root() {
+        let (_dir, pack) = make_pack();
+
+        assert!(pack.exists(Path::new("shaders/gbuffers.vert")));
+        assert!(!pack.exists(Path::new("shaders/gbuffers.frag")));
+        assert_eq!(pack.read_bytes(Path::new("passes.json")).unwrap(), b"[]");
+        assert_eq!(
+            pack.read_string(Path::new("shaders/gbuffers.vert")).unwrap(),
+            "void main() {}"
+        );
+    }
+
+    #[test]
+    fn reports_missing_files_and_bad_text() {
+        let (_dir, pack) = make_pack();
+
+        match pack.read_bytes(Path::new("resources.json")) {
+            Err(ResourcePackError::NotFound(path)) => assert_eq!(path, Path::new("resources.json")),
+            other => panic!("Expected NotFound, got {:?}", other),
+        }
+        match pack.read_string(Path::new("shaders/lib/common.glsl")) {
+            Err(ResourcePackError::InvalidUtf8(_)) => {}
+            other => panic!("Expected InvalidUtf8, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn lists_directories_in_order() {
+        let (_dir, pack) = make_pack();
+
+        assert_eq!(
+            pack.list_directory(Path::new("")).unwrap(),
+            vec![PathBuf::from("passes.json"), PathBuf::from("shaders")]
+        );
+        assert_eq!(
+            pack.list_directory(Path::new("shaders")).unwrap(),
+            vec![PathBuf::from("shaders/gbuffers.vert"), PathBuf::from("shaders/lib")]
+        );
+        assert!(pack.list_directory(Path::new("passes.json")).is_err());
+    }
+
+    #[test]
+    fn normalizes_paths_and_stays_inside_the_pack() {
+        let (_dir, pack) = make_pack();
+
+        assert_eq!(
+            pack.read_string(Path::new("shaders\\gbuffers.vert")).unwrap(),
+            "void main() {}"
+        );
+        assert_eq!(
+            pack.read_string(Path::new("/shaders/lib/../gbuffers.vert")).unwrap(),
+            "void main() {}"
+        );
+        match pack.read_bytes(Path::new("shaders/../../passes.json")) {
+            Err(ResourcePackError::PathEscapesRoot(_)) => {}
+            other => panic!("Expected PathEscapesRoot, got {:?}", other),
+        }
+        assert!(!pack.exists(Path::new("../passes.json")));
+    }
+
+    #[test]
+    fn can_ignore_case_when_asked_to() {
+        let (dir, pack) = make_pack();
+        assert!(!pack.exists(Path::new("Shaders/GBuffers.vert")));
+
+        let pack = FolderResourcePack::new(dir.path())
+            .unwrap()
+            .with_case_insensitive_fallback(true);
+        assert_eq!(
+            pack.read_string(Path::new("Shaders/GBuffers.vert")).unwrap(),
+            "void main() {}"
+        );
+        assert_eq!(
+            pack.list_directory(Path::new("SHADERS/LIB")).unwrap(),
+            vec![PathBuf::from("shaders/lib/common.glsl")]
+        );
+        assert!(!pack.exists(Path::new("Shaders/GBuffers.frag")));
+    }
+}
@@ -5,3 +5,25 @@
 //! of those and will instead only take in file paths and will return either streams of bytes or strings. The resource
 //! pack loader will also be able to read resource packs in either filesystem folders or a zip folder. It should be
 //! constructed in a way that will allow support for other zip formats
+
+mod archive_format;
+mod async_pack;
+mod folder_pack;
+mod pack_path;
+mod pack_stack;
+mod pack_watcher;
+mod resource_pack;
+#[cfg(test)]
+mod test_packs;
+mod zip_pack;
+
+pub use archive_format::*;
+pub use async_pack::*;
+pub use folder_pack::*;
+pub use pack_path::*;
+pub use pack_stack::*;
+pub use pack_watcher::*;
+pub use resource_pack::*;
+#[cfg(test)]
+pub use test_packs::*;
+pub use zip_pack::*;
@@ -0,0 +1,92 @@
+//! Utilities for the paths that are used to look up files in resource packs
+
+use crate::loading::*;
+use log::warn;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Turns a path that was given to a resource pack into a canonical pack-relative path
+///
+/// Packs are often authored on Windows, and reference their files in whatever way worked there. This makes those paths
+/// work everywhere:
+/// - Backslashes are treated as path separators
+/// - A leading separator refers to the root of the pack, so it's ignored
+/// - `.` segments are removed, and `..` segments remove the segment before them
+///
+/// # Errors
+///
+/// Returns `ResourcePackError::PathEscapesRoot` if a `..` segment would go above the root of the pack. Packs may never
+/// read files that aren't in the pack
+///
+/// # Parameters
+///
+/// * `path` - The path to normalize
+pub fn normalize_pack_path(path: &Path) -> Result<PathBuf, ResourcePackError> {
+    let path_string = path.to_string_lossy().replace('\\', "/");
+
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in path_string.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                if segments.pop().is_none() {
+                    return Err(ResourcePackError::PathEscapesRoot(path.to_path_buf()));
+                }
+            }
+            segment => segments.push(segment),
+        }
+    }
+
+    Ok(segments.iter().collect())
+}
+
+/// Logs that a file was found by ignoring case, so that pack authors can fix their paths
+pub(crate) fn warn_case_mismatch(requested: &Path, found: &Path) {
+    warn!(
+        "{:?} isn't in the resource pack, using {:?} instead. Paths in resource packs are case-sensitive on most \
+         platforms, so this pack won't work everywhere",
+        requested, found
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::loading::*;
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    #[test]
+    fn normalizes_windows_style_paths() {
+        let normalize = |path: &str| normalize_pack_path(Path::new(path)).unwrap();
+
+        assert_eq!(
+            normalize("shaders/composite.fsh"),
+            PathBuf::from("shaders/composite.fsh")
+        );
+        assert_eq!(
+            normalize("Shaders\\Composite.fsh"),
+            PathBuf::from("Shaders/Composite.fsh")
+        );
+        assert_eq!(normalize("/lib/./common.glsl"), PathBuf::from("lib/common.glsl"));
+        assert_eq!(
+            normalize("shaders/lib/../composite.fsh"),
+            PathBuf::from("shaders/composite.fsh")
+        );
+        assert_eq!(normalize(""), PathBuf::new());
+    }
+
+    #[test]
+    fn rejects_paths_outside_the_pack() {
+        for path in &[
+            "..",
+            "../other_pack/shaders",
+            "shaders/../../secret.txt",
+            "a\\..\\..\\b",
+        ] {
+            match normalize_pack_path(Path::new(path)) {
+                Err(ResourcePackError::PathEscapesRoot(_)) => {}
+                other => panic!("Expected PathEscapesRoot for {}, got {:?}", path, other),
+            }
+        }
+    }
+}
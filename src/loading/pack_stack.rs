@@ -0,0 +1,209 @@
+//! Several resource packs layered on top of each other
+
+use crate::loading::*;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// A file in a `ResourcePackStack`, along with the pack that supplies it
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PackEntry {
+    /// The path of the file, relative to the root of the packs
+    pub path: PathBuf,
+
+    /// The name of the highest-priority pack that has this file
+    pub pack: String,
+}
+
+struct PackLayer {
+    name: String,
+    pack: Box<dyn ResourcePack>,
+}
+
+/// A stack of resource packs that act like a single resource pack
+///
+/// Minecraft lets players enable several resourcepacks at once. When more than one pack has a file, the pack that's
+/// highest in the stack wins. Directories are merged, so listing a directory lists the files from every pack
+///
+/// The stack is itself a `ResourcePack`, so code that reads files doesn't need to know that it's reading from a stack
+#[derive(Default)]
+pub struct ResourcePackStack {
+    /// The packs in this stack, from highest priority to lowest
+    layers: Vec<PackLayer>,
+}
+
+impl ResourcePackStack {
+    /// Creates a stack with no packs in it
+    pub fn new() -> ResourcePackStack {
+        ResourcePackStack { layers: Vec::new() }
+    }
+
+    /// Adds a pack to the top of this stack, giving it priority over all the packs already in the stack
+    ///
+    /// # Parameters
+    ///
+    /// * `name` - The name of the pack, used to report where files come from
+    /// * `pack` - The pack to add
+    pub fn push<S: Into<String>>(&mut self, name: S, pack: Box<dyn ResourcePack>) {
+        self.layers.insert(
+            0,
+            PackLayer {
+                name: name.into(),
+                pack,
+            },
+        );
+    }
+
+    /// The names of the packs in this stack, from highest priority to lowest
+    pub fn pack_names(&self) -> impl Iterator<Item = &str> {
+        self.layers.iter().map(|layer| layer.name.as_str())
+    }
+
+    /// Finds the pack that supplies a file or directory
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - The path to look for, relative to the root of the packs
+    pub fn provider_of(&self, path: &Path) -> Option<&str> {
+        self.find_layer(path).map(|layer| layer.name.as_str())
+    }
+
+    /// Lists every file in a directory and all of its subdirectories, along with the pack that supplies each file
+    ///
+    /// This is the effective contents of the directory, such as the textures that go into Nova's virtual texture
+    /// atlases. Files are sorted by path
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - The directory to list, relative to the root of the packs
+    pub fn effective_files(&self, path: &Path) -> Result<Vec<PackEntry>, ResourcePackError> {
+        let mut files = BTreeMap::new();
+        let mut directories = vec![path.to_path_buf()];
+
+        while let Some(directory) = directories.pop() {
+            for entry in self.list_directory(&directory)? {
+                match self.list_directory(&entry) {
+                    Ok(_) => directories.push(entry),
+                    Err(ResourcePackError::NotADirectory(_)) => {
+                        let pack = self.provider_of(&entry).unwrap_or_default().to_owned();
+                        files.insert(entry, pack);
+                    }
+                    Err(error) => return Err(error),
+                }
+            }
+        }
+
+        Ok(files.into_iter().map(|(path, pack)| PackEntry { path, pack }).collect())
+    }
+
+    fn find_layer(&self, path: &Path) -> Option<&PackLayer> {
+        self.layers.iter().find(|layer| layer.pack.exists(path))
+    }
+}
+
+impl ResourcePack for ResourcePackStack {
+    fn exists(&self, path: &Path) -> bool {
+        self.find_layer(path).is_some()
+    }
+
+    fn read_bytes(&self, path: &Path) -> Result<Vec<u8>, ResourcePackError> {
+        match self.find_layer(path) {
+            Some(layer) => layer.pack.read_bytes(path),
+            None => Err(ResourcePackError::NotFound(path.to_path_buf())),
+        }
+    }
+
+    fn list_directory(&self, path: &Path) -> Result<Vec<PathBuf>, ResourcePackError> {
+        let mut entries = Vec::new();
+        let mut error = None;
+        let mut found_directory = false;
+
+        for layer in &self.layers {
+            match layer.pack.list_directory(path) {
+                Ok(layer_entries) => {
+                    found_directory = true;
+                    entries.extend(layer_entries);
+                }
+                // A pack that doesn't have the directory at all says less about the path than one that has something
+                // else there, so it shouldn't hide that pack's error
+                Err(ResourcePackError::NotFound(_)) => {}
+                Err(layer_error) => {
+                    if error.is_none() {
+                        error = Some(layer_error);
+                    }
+                }
+            }
+        }
+
+        if !found_directory {
+            return Err(error.unwrap_or_else(|| ResourcePackError::NotFound(path.to_path_buf())));
+        }
+
+        entries.sort();
+        entries.dedup();
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::loading::*;
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    fn make_stack(vanilla: &tempfile::TempDir, faithful: &tempfile::TempDir) -> ResourcePackStack {
+        let mut stack = ResourcePackStack::new();
+        stack.push("vanilla", Box::new(FolderResourcePack::new(vanilla.path()).unwrap()));
+        stack.push("faithful", Box::new(FolderResourcePack::new(faithful.path()).unwrap()));
+        stack
+    }
+
+    #[test]
+    fn highest_pack_wins() {
+        let vanilla = make_folder(&[
+            ("textures/block/stone.png", "vanilla stone"),
+            ("textures/block/dirt.png", "dirt"),
+        ]);
+        let faithful = make_folder(&[("textures/block/stone.png", "faithful stone")]);
+        let stack = make_stack(&vanilla, &faithful);
+
+        assert_eq!(stack.pack_names().collect::<Vec<_>>(), vec!["faithful", "vanilla"]);
+        assert_eq!(
+            stack.read_string(Path::new("textures/block/stone.png")).unwrap(),
+            "faithful stone"
+        );
+        assert_eq!(stack.read_string(Path::new("textures/block/dirt.png")).unwrap(), "dirt");
+        assert_eq!(stack.provider_of(Path::new("textures/block/dirt.png")), Some("vanilla"));
+        assert_eq!(stack.provider_of(Path::new("textures/block/gravel.png")), None);
+        assert!(stack.read_bytes(Path::new("textures/block/gravel.png")).is_err());
+    }
+
+    #[test]
+    fn directories_are_merged() {
+        let vanilla = make_folder(&[("textures/block/stone.png", ""), ("textures/item/stick.png", "")]);
+        let faithful = make_folder(&[("textures/block/stone.png", ""), ("textures/block/stone_n.png", "")]);
+        let stack = make_stack(&vanilla, &faithful);
+
+        assert_eq!(
+            stack.list_directory(Path::new("textures/block")).unwrap(),
+            vec![
+                PathBuf::from("textures/block/stone.png"),
+                PathBuf::from("textures/block/stone_n.png")
+            ]
+        );
+
+        let files = stack.effective_files(Path::new("textures")).unwrap();
+        let files: Vec<_> = files
+            .iter()
+            .map(|entry| (entry.path.to_str().unwrap(), entry.pack.as_str()))
+            .collect();
+        assert_eq!(
+            files,
+            vec![
+                ("textures/block/stone.png", "faithful"),
+                ("textures/block/stone_n.png", "faithful"),
+                ("textures/item/stick.png", "vanilla"),
+            ]
+        );
+    }
+}
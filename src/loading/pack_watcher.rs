@@ -0,0 +1,194 @@
+//! Watching folder resource packs for changes
+
+use crate::loading::*;
+use log::warn;
+use notify::DebouncedEvent;
+use notify::RecommendedWatcher;
+use notify::RecursiveMode;
+use notify::Watcher;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+use std::time::Instant;
+
+/// How a file in a resource pack changed
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum PackChangeKind {
+    Added,
+    Modified,
+    Removed,
+}
+
+/// A change to a file or directory in a resource pack
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PackChange {
+    /// How the file changed
+    pub kind: PackChangeKind,
+
+    /// The path of the file that changed, relative to the root of the pack
+    ///
+    /// If the watcher lost track of what changed, it reports that the root of the pack itself was modified. The path
+    /// is empty in that case
+    pub path: PathBuf,
+}
+
+/// Watches a `FolderResourcePack` for changes to its files
+///
+/// Shader authors expect their changes to show up as soon as they save a file. The watcher tells Nova which files
+/// changed so that it can reload the shaderpack
+///
+/// Editors often touch a file several times when saving it. The watcher debounces those touches, so each save is
+/// reported as a single change
+pub struct PackWatcher {
+    /// The folder being watched. Kept around so the paths of changes can be made relative to it
+    root: PathBuf,
+
+    /// The thing doing the actual watching. It stops watching when it's dropped, so it has to live as long as this
+    /// struct
+    _watcher: RecommendedWatcher,
+
+    events: Receiver<DebouncedEvent>,
+}
+
+impl PackWatcher {
+    fn new(root: &Path, debounce: Duration) -> Result<PackWatcher, ResourcePackError> {
+        let root = root.canonicalize().map_err(|e| ResourcePackError::io(root, e))?;
+
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::watcher(sender, debounce).map_err(|e| watch_error(&root, e))?;
+        watcher
+            .watch(&root, RecursiveMode::Recursive)
+            .map_err(|e| watch_error(&root, e))?;
+
+        Ok(PackWatcher {
+            root,
+            _watcher: watcher,
+            events,
+        })
+    }
+
+    /// Gets all the changes that have happened since the last time changes were requested, without blocking
+    pub fn poll(&self) -> Vec<PackChange> {
+        let mut changes = Vec::new();
+        while let Ok(event) = self.events.try_recv() {
+            self.translate_event(event, &mut changes);
+        }
+
+        changes
+    }
+
+    /// Waits until at least one change happens or the timeout expires, then returns all the changes that are ready
+    ///
+    /// # Parameters
+    ///
+    /// * `timeout` - The longest time to wait for a change
+    pub fn wait(&self, timeout: Duration) -> Vec<PackChange> {
+        let deadline = Instant::now() + timeout;
+        let mut changes = Vec::new();
+
+        while changes.is_empty() {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+
+            match self.events.recv_timeout(deadline - now) {
+                Ok(event) => self.translate_event(event, &mut changes),
+                Err(_) => break,
+            }
+        }
+
+        changes.extend(self.poll());
+        changes
+    }
+
+    fn translate_event(&self, event: DebouncedEvent, changes: &mut Vec<PackChange>) {
+        let mut push = |kind, path: &Path| {
+            if let Ok(path) = path.strip_prefix(&self.root) {
+                changes.push(PackChange {
+                    kind,
+                    path: path.to_path_buf(),
+                });
+            }
+        };
+
+        match event {
+            DebouncedEvent::Create(path) => push(PackChangeKind::Added, &path),
+            DebouncedEvent::Write(path) => push(PackChangeKind::Modified, &path),
+            DebouncedEvent::Remove(path) => push(PackChangeKind::Removed, &path),
+            DebouncedEvent::Rename(from, to) => {
+                push(PackChangeKind::Removed, &from);
+                push(PackChangeKind::Added, &to);
+            }
+            DebouncedEvent::Rescan => push(PackChangeKind::Modified, &self.root),
+            DebouncedEvent::Error(error, path) => warn!("Error while watching {:?}: {}", path, error),
+            // Notices come before the debounced event and permission changes don't change what Nova loads
+            DebouncedEvent::NoticeWrite(_) | DebouncedEvent::NoticeRemove(_) | DebouncedEvent::Chmod(_) => {}
+        }
+    }
+}
+
+impl FolderResourcePack {
+    /// Starts watching this pack's folder for changes
+    ///
+    /// # Parameters
+    ///
+    /// * `debounce` - How long a file has to go without being touched before its change is reported
+    pub fn watch(&self, debounce: Duration) -> Result<PackWatcher, ResourcePackError> {
+        PackWatcher::new(self.root(), debounce)
+    }
+}
+
+fn watch_error(root: &Path, error: notify::Error) -> ResourcePackError {
+    ResourcePackError::CouldNotWatch {
+        path: root.to_path_buf(),
+        reason: error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::loading::*;
+    use std::fs;
+    use std::path::Path;
+    use std::time::Duration;
+    use std::time::Instant;
+
+    /// Waits until the watcher reports a specific change, ignoring any others
+    fn wait_for(watcher: &PackWatcher, kind: PackChangeKind, path: &str) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while Instant::now() < deadline {
+            let changes = watcher.wait(Duration::from_millis(100));
+            if changes
+                .iter()
+                .any(|change| change.kind == kind && change.path == Path::new(path))
+            {
+                return;
+            }
+        }
+
+        panic!("Never saw {:?} for {}", kind, path);
+    }
+
+    #[test]
+    fn reports_changes_to_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("shaders")).unwrap();
+
+        let pack = FolderResourcePack::new(dir.path()).unwrap();
+        let watcher = pack.watch(Duration::from_millis(50)).unwrap();
+        assert!(watcher.poll().is_empty());
+
+        let file = dir.path().join("shaders/composite.frag");
+        fs::write(&file, "void main() {}").unwrap();
+        wait_for(&watcher, PackChangeKind::Added, "shaders/composite.frag");
+
+        fs::write(&file, "void main() { discard; }").unwrap();
+        wait_for(&watcher, PackChangeKind::Modified, "shaders/composite.frag");
+
+        fs::remove_file(&file).unwrap();
+        wait_for(&watcher, PackChangeKind::Removed, "shaders/composite.frag");
+    }
+}
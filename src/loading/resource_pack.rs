@@ -0,0 +1,94 @@
+//! The trait that all resource pack backends implement
+
+use failure::Fail;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Errors that can happen when reading from a resource pack
+#[derive(Fail, Debug)]
+pub enum ResourcePackError {
+    #[fail(display = "{:?} does not exist in the resource pack", _0)]
+    NotFound(PathBuf),
+
+    #[fail(display = "{:?} refers to a file outside of the resource pack", _0)]
+    PathEscapesRoot(PathBuf),
+
+    #[fail(display = "{:?} is not a directory", _0)]
+    NotADirectory(PathBuf),
+
+    #[fail(display = "{:?} is not valid UTF-8", _0)]
+    InvalidUtf8(PathBuf),
+
+    #[fail(display = "Could not read {:?}: {}", path, error)]
+    Io {
+        path: PathBuf,
+        #[cause]
+        error: io::Error,
+    },
+
+    #[fail(display = "{:?} is not a valid archive: {}", path, reason)]
+    InvalidArchive { path: PathBuf, reason: String },
+
+    #[fail(display = "{:?} is not in an archive format that Nova knows how to read", _0)]
+    UnsupportedArchiveFormat(PathBuf),
+
+    #[fail(display = "Could not watch {:?} for changes: {}", path, reason)]
+    CouldNotWatch { path: PathBuf, reason: String },
+
+    #[fail(display = "Could not start a task to read {:?}", _0)]
+    CouldNotStartTask(PathBuf),
+}
+
+impl ResourcePackError {
+    pub(crate) fn io(path: &Path, error: io::Error) -> ResourcePackError {
+        if error.kind() == io::ErrorKind::NotFound {
+            ResourcePackError::NotFound(path.to_path_buf())
+        } else {
+            ResourcePackError::Io {
+                path: path.to_path_buf(),
+                error,
+            }
+        }
+    }
+}
+
+/// A source of files, such as a folder on disk or a zip archive
+///
+/// A resource pack doesn't know what kind of pack it holds. Callers ask for files by their path relative to the root of
+/// the pack, and get back bytes or strings
+pub trait ResourcePack: Send + Sync {
+    /// Checks if a file or directory exists in this pack
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - The path to check, relative to the root of the pack
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Reads an entire file from this pack
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - The path of the file to read, relative to the root of the pack
+    fn read_bytes(&self, path: &Path) -> Result<Vec<u8>, ResourcePackError>;
+
+    /// Reads an entire file from this pack as a UTF-8 string
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - The path of the file to read, relative to the root of the pack
+    fn read_string(&self, path: &Path) -> Result<String, ResourcePackError> {
+        let bytes = self.read_bytes(path)?;
+        String::from_utf8(bytes).map_err(|_| ResourcePackError::InvalidUtf8(path.to_path_buf()))
+    }
+
+    /// Lists the files and directories directly inside a directory of this pack
+    ///
+    /// The returned paths are relative to the root of the pack, so they can be passed straight back into the other
+    /// methods of this trait. They're sorted so that iterating a pack is deterministic
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - The directory to list, relative to the root of the pack. An empty path lists the root of the pack
+    fn list_directory(&self, path: &Path) -> Result<Vec<PathBuf>, ResourcePackError>;
+}
@@ -0,0 +1,32 @@
+//! Temporary resource packs for tests
+
+use crate::loading::*;
+use std::fs;
+
+/// Makes a temporary folder with some files in it. The folder is deleted when the returned `TempDir` is dropped
+///
+/// # Parameters
+///
+/// * `files` - The path of each file relative to the folder, and its contents. Parent folders are created as needed
+pub fn make_folder(files: &[(&str, &str)]) -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    for (name, contents) in files {
+        let path = dir.path().join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    dir
+}
+
+/// Makes a resource pack from a temporary folder with some files in it. The `TempDir` has to be kept alive for as long
+/// as the pack is used
+///
+/// # Parameters
+///
+/// * `files` - The path of each file relative to the root of the pack, and its contents
+pub fn make_pack(files: &[(&str, &str)]) -> (tempfile::TempDir, FolderResourcePack) {
+    let dir = make_folder(files);
+    let pack = FolderResourcePack::new(dir.path()).unwrap();
+    (dir, pack)
+}
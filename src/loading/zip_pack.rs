@@ -0,0 +1,306 @@
+//! Resource packs that are stored in a zip archive
+
+use crate::loading::*;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::io::Seek;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use zip::ZipArchive;
+use zip::result::ZipError;
+
+/// A resource pack that's stored in a zip archive
+///
+/// Most shaderpacks and resourcepacks are distributed as zip files. Nova reads them without extracting them anywhere
+///
+/// Zip archives don't have to contain entries for their directories, so the directory structure is worked out from the
+/// names of the files when the archive is opened
+///
+/// Paths are normalized with `normalize_pack_path` before they're looked up. Lookups can optionally fall back to
+/// ignoring case, for packs that were written on case-insensitive filesystems
+pub struct ZipResourcePack<R: Read + Seek + Send = File> {
+    /// The path to the archive, used for error messages
+    archive_path: PathBuf,
+
+    /// The archive itself. Reading from a zip archive requires mutable access, hence the mutex
+    archive: Mutex<ZipArchive<R>>,
+
+    /// The names of all the files in the archive
+    files: BTreeSet<String>,
+
+    /// The names of all the directories in the archive, including the root directory and any directories that the
+    /// archive doesn't have entries for
+    directories: BTreeSet<String>,
+
+    /// The names of all the files and directories in the archive, keyed by their lowercase names
+    lowercase_entries: HashMap<String, String>,
+
+    /// Whether to look for a file with different capitalization when a file can't be found
+    case_insensitive_fallback: bool,
+}
+
+impl ZipResourcePack<File> {
+    /// Opens the zip archive at the provided path
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - The path to the zip archive
+    pub fn open<P: Into<PathBuf>>(path: P) -> Result<ZipResourcePack<File>, ResourcePackError> {
+        let path = path.into();
+        let file = File::open(&path).map_err(|e| ResourcePackError::io(&path, e))?;
+        ZipResourcePack::new(path, file)
+    }
+}
+
+impl<R: Read + Seek + Send> ZipResourcePack<R> {
+    /// Reads a zip archive from any seekable reader
+    ///
+    /// # Parameters
+    ///
+    /// * `archive_path` - Where the archive came from. Only used for error messages
+    /// * `reader` - The reader to read the archive from
+    pub fn new<P: Into<PathBuf>>(archive_path: P, reader: R) -> Result<ZipResourcePack<R>, ResourcePackError> {
+        let archive_path = archive_path.into();
+        let archive = ZipArchive::new(reader).map_err(|e| zip_error(&archive_path, e))?;
+
+        let mut files = BTreeSet::new();
+        let mut directories = BTreeSet::new();
+        directories.insert(String::new());
+
+        for name in archive.file_names() {
+            let is_dir = name.ends_with('/');
+            let name = name.trim_end_matches('/');
+            if name.is_empty() {
+                continue;
+            }
+
+            let mut parent = name;
+            while let Some(separator) = parent.rfind('/') {
+                parent = &parent[..separator];
+                directories.insert(parent.to_owned());
+            }
+
+            if is_dir {
+                directories.insert(name.to_owned());
+            } else {
+                files.insert(name.to_owned());
+            }
+        }
+
+        let lowercase_entries = files
+            .iter()
+            .chain(directories.iter())
+            .map(|entry| (entry.to_lowercase(), entry.clone()))
+            .collect();
+
+        Ok(ZipResourcePack {
+            archive_path,
+            archive: Mutex::new(archive),
+            files,
+            directories,
+            lowercase_entries,
+            case_insensitive_fallback: false,
+        })
+    }
+
+    /// Sets whether files that can't be found should be looked for again while ignoring case
+    ///
+    /// When a file is found this way, a warning is logged so that the pack's author can fix the path
+    ///
+    /// # Parameters
+    ///
+    /// * `enabled` - True to look for files while ignoring case, false to only find files with exactly matching paths
+    pub fn with_case_insensitive_fallback(mut self, enabled: bool) -> ZipResourcePack<R> {
+        self.case_insensitive_fallback = enabled;
+        self
+    }
+
+    /// Finds the name of the zip entry that `path` refers to
+    fn resolve(&self, path: &Path) -> Result<String, ResourcePackError> {
+        let name = entry_name(&normalize_pack_path(path)?);
+        if !self.case_insensitive_fallback || self.files.contains(&name) || self.directories.contains(&name) {
+            return Ok(name);
+        }
+
+        match self.lowercase_entries.get(&name.to_lowercase()) {
+            Some(entry) => {
+                warn_case_mismatch(path, Path::new(entry));
+                Ok(entry.clone())
+            }
+            None => Ok(name),
+        }
+    }
+}
+
+impl<R: Read + Seek + Send> ResourcePack for ZipResourcePack<R> {
+    fn exists(&self, path: &Path) -> bool {
+        match self.resolve(path) {
+            Ok(name) => self.files.contains(&name) || self.directories.contains(&name),
+            Err(_) => false,
+        }
+    }
+
+    fn read_bytes(&self, path: &Path) -> Result<Vec<u8>, ResourcePackError> {
+        let name = self.resolve(path)?;
+        if !self.files.contains(&name) {
+            return Err(ResourcePackError::NotFound(path.to_path_buf()));
+        }
+
+        // Every read seeks to the start of its file, so a read that panicked doesn't leave the archive in a state that
+        // breaks later reads
+        let mut archive = self.archive.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut file = archive.by_name(&name).map_err(|e| zip_error(&self.archive_path, e))?;
+
+        // The size in the archive's header can't be trusted, so the buffer grows as the file is actually read
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)
+            .map_err(|e| ResourcePackError::io(path, e))?;
+
+        Ok(bytes)
+    }
+
+    fn list_directory(&self, path: &Path) -> Result<Vec<PathBuf>, ResourcePackError> {
+        let name = self.resolve(path)?;
+        if !self.directories.contains(&name) {
+            return if self.files.contains(&name) {
+                Err(ResourcePackError::NotADirectory(path.to_path_buf()))
+            } else {
+                Err(ResourcePackError::NotFound(path.to_path_buf()))
+            };
+        }
+
+        let mut entries: Vec<PathBuf> = self
+            .files
+            .iter()
+            .chain(self.directories.iter())
+            .filter(|entry| !entry.is_empty() && parent_of(entry) == name)
+            .map(PathBuf::from)
+            .collect();
+        entries.sort();
+
+        Ok(entries)
+    }
+}
+
+/// Turns a normalized pack-relative path into the name of a zip entry, which always uses `/` as a separator
+fn entry_name(path: &Path) -> String {
+    path.iter()
+        .map(|segment| segment.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn parent_of(entry: &str) -> &str {
+    match entry.rfind('/') {
+        Some(separator) => &entry[..separator],
+        None => "",
+    }
+}
+
+fn zip_error(archive_path: &Path, error: ZipError) -> ResourcePackError {
+    match error {
+        ZipError::Io(error) => ResourcePackError::io(archive_path, error),
+        ZipError::InvalidArchive(reason) | ZipError::UnsupportedArchive(reason) => ResourcePackError::InvalidArchive {
+            path: archive_path.to_path_buf(),
+            reason: reason.to_owned(),
+        },
+        ZipError::FileNotFound => ResourcePackError::NotFound(archive_path.to_path_buf()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::loading::*;
+    use std::io::Cursor;
+    use std::io::Write;
+    use std::path::Path;
+    use std::path::PathBuf;
+    use zip::ZipWriter;
+    use zip::write::FileOptions;
+
+    fn make_zip(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in files {
+            writer.start_file(*name, FileOptions::default()).unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn make_pack() -> ZipResourcePack<Cursor<Vec<u8>>> {
+        let bytes = make_zip(&[
+            ("passes.json", "[]"),
+            ("shaders/gbuffers.vert", "void main() {}"),
+            ("shaders/lib/common.glsl", "// common"),
+        ]);
+        ZipResourcePack::new("test.zip", Cursor::new(bytes)).unwrap()
+    }
+
+    #[test]
+    fn reads_files_by_pack_relative_path() {
+        let pack = make_pack();
+
+        assert!(pack.exists(Path::new("shaders/gbuffers.vert")));
+        assert!(pack.exists(Path::new("shaders/lib")));
+        assert!(!pack.exists(Path::new("resources.json")));
+        assert_eq!(
+            pack.read_string(Path::new("shaders/lib/common.glsl")).unwrap(),
+            "// common"
+        );
+
+        match pack.read_bytes(Path::new("shaders/lib")) {
+            Err(ResourcePackError::NotFound(_)) => {}
+            other => panic!("Expected NotFound, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn lists_directories_without_directory_entries() {
+        let pack = make_pack();
+
+        assert_eq!(
+            pack.list_directory(Path::new("")).unwrap(),
+            vec![PathBuf::from("passes.json"), PathBuf::from("shaders")]
+        );
+        assert_eq!(
+            pack.list_directory(Path::new("shaders")).unwrap(),
+            vec![PathBuf::from("shaders/gbuffers.vert"), PathBuf::from("shaders/lib")]
+        );
+        assert!(pack.list_directory(Path::new("passes.json")).is_err());
+    }
+
+    #[test]
+    fn rejects_files_that_are_not_zips() {
+        match ZipResourcePack::new("broken.zip", Cursor::new(b"not a zip".to_vec())) {
+            Err(ResourcePackError::InvalidArchive { .. }) => {}
+            Err(other) => panic!("Expected InvalidArchive, got {:?}", other),
+            Ok(_) => panic!("Expected InvalidArchive"),
+        }
+    }
+
+    #[test]
+    fn normalizes_paths_and_can_ignore_case() {
+        let pack = make_pack();
+        assert!(pack.exists(Path::new("\\shaders\\lib\\..\\gbuffers.vert")));
+        assert!(!pack.exists(Path::new("Shaders/GBuffers.vert")));
+        match pack.read_bytes(Path::new("../passes.json")) {
+            Err(ResourcePackError::PathEscapesRoot(_)) => {}
+            other => panic!("Expected PathEscapesRoot, got {:?}", other),
+        }
+
+        let pack = pack.with_case_insensitive_fallback(true);
+        assert_eq!(
+            pack.read_string(Path::new("Shaders/GBuffers.vert")).unwrap(),
+            "void main() {}"
+        );
+        assert_eq!(
+            pack.list_directory(Path::new("SHADERS/Lib")).unwrap(),
+            vec![PathBuf::from("shaders/lib/common.glsl")]
+        );
+    }
+}
@@ -0,0 +1,410 @@
+//! Working out the barriers that render passes need between them
+
+use crate::render_graph::*;
+use crate::rhi::*;
+use crate::shaderpack::*;
+use std::collections::HashMap;
+
+/// A barrier that transitions a resource from how one pass uses it to how another pass uses it
+///
+/// This is plain data, so that it can be worked out before any GPU resources exist. It's turned into a
+/// `ResourceBarrier` once the resource has been created
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PlannedBarrier {
+    /// The name of the resource
+    pub resource: String,
+
+    pub initial_state: ResourceState,
+
+    pub final_state: ResourceState,
+
+    /// How the resource was accessed before the barrier. Those accesses have to finish before the barrier
+    pub access_before_barrier: ResourceAccessFlags,
+
+    /// How the resource is accessed after the barrier
+    pub access_after_barrier: ResourceAccessFlags,
+
+    /// The pipeline stages that accessed the resource before the barrier
+    pub stages_before_barrier: PipelineStageFlags,
+
+    /// The pipeline stages that access the resource after the barrier
+    pub stages_after_barrier: PipelineStageFlags,
+}
+
+/// The barriers to record right before a pass
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PassBarriers {
+    /// The name of the pass
+    pub pass: String,
+
+    pub barriers: Vec<PlannedBarrier>,
+}
+
+/// Every barrier that a frame needs
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BarrierPlan {
+    /// The barriers before each pass, in the order that the passes run in
+    pub passes: Vec<PassBarriers>,
+
+    /// The barriers to record after the last pass, such as the one that gets the backbuffer ready to be presented
+    pub final_barriers: Vec<PlannedBarrier>,
+}
+
+/// How one pass uses a resource
+#[derive(Debug, Clone)]
+struct Access {
+    state: ResourceState,
+    access: ResourceAccessFlags,
+    stages: PipelineStageFlags,
+}
+
+impl Access {
+    fn is_write(&self) -> bool {
+        self.access.intersects(
+            ResourceAccessFlags::SHADER_WRITE_BIT
+                | ResourceAccessFlags::COLOR_ATTACHMENT_WRITE_BIT
+                | ResourceAccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE_BIT,
+        )
+    }
+
+    /// Combines two ways that the same pass uses a resource
+    fn merge(&mut self, other: Access) {
+        if self.state != other.state {
+            self.state = ResourceState::General;
+        }
+        self.access |= other.access;
+        self.stages |= other.stages;
+    }
+}
+
+/// Works out the barriers that a frame needs
+///
+/// A pass needs a barrier for a resource when it needs the resource in a different state than the last pass to use it
+/// left it in, when the last pass to use the resource wrote to it, or when it writes to the resource. Passes that only
+/// read a resource in the same state don't need barriers between them
+///
+/// At the start of the frame, a resource whose first use is a cleared attachment is in `ResourceState::Undefined`,
+/// since its old contents don't matter. Every other resource is in whatever state the end of the last frame left it
+/// in, including attachments that are loaded instead of cleared. The
+/// backbuffer is transitioned to `ResourceState::PresentSource` after the last pass, so that it can be shown on screen
+///
+/// # Parameters
+///
+/// * `graph` - The order that the passes run in
+/// * `passes` - The passes that `graph` was built from
+pub fn plan_barriers(graph: &RenderGraph, passes: &[RenderPassCreationInfo]) -> BarrierPlan {
+    let accesses: Vec<Vec<(&str, Access)>> = graph
+        .passes()
+        .iter()
+        .map(|pass| pass_accesses(&passes[pass.index]))
+        .collect();
+
+    // How each resource is left at the end of the frame, which is how the next frame finds it
+    let mut end_of_frame: HashMap<&str, Access> = HashMap::new();
+    for pass_accesses in &accesses {
+        for (resource, access) in pass_accesses {
+            end_of_frame.insert(resource, access.clone());
+        }
+    }
+    if let Some(backbuffer) = end_of_frame.get_mut(BACKBUFFER_NAME) {
+        *backbuffer = present_access();
+    }
+
+    let mut current: HashMap<&str, Access> = HashMap::new();
+    let mut plan = BarrierPlan {
+        passes: Vec::new(),
+        final_barriers: Vec::new(),
+    };
+    for (pass, pass_accesses) in graph.passes().iter().zip(&accesses) {
+        let mut barriers = Vec::new();
+        for (resource, access) in pass_accesses {
+            let previous = current.get(resource).cloned().unwrap_or_else(|| {
+                if clears(&passes[pass.index], resource) {
+                    Access {
+                        state: ResourceState::Undefined,
+                        access: ResourceAccessFlags::NO_FLAGS,
+                        stages: PipelineStageFlags::TOP_OF_PIPE,
+                    }
+                } else {
+                    end_of_frame[resource].clone()
+                }
+            });
+
+            if previous.state != access.state || previous.is_write() || access.is_write() {
+                barriers.push(barrier(resource, &previous, access));
+            }
+            current.insert(resource, access.clone());
+        }
+
+        plan.passes.push(PassBarriers {
+            pass: pass.name.clone(),
+            barriers,
+        });
+    }
+
+    if let Some(backbuffer) = current.get(BACKBUFFER_NAME) {
+        plan.final_barriers
+            .push(barrier(BACKBUFFER_NAME, backbuffer, &present_access()));
+    }
+
+    plan
+}
+
+fn barrier(resource: &str, before: &Access, after: &Access) -> PlannedBarrier {
+    PlannedBarrier {
+        resource: resource.to_owned(),
+        initial_state: before.state.clone(),
+        final_state: after.state.clone(),
+        access_before_barrier: before.access,
+        access_after_barrier: after.access,
+        stages_before_barrier: before.stages,
+        stages_after_barrier: after.stages,
+    }
+}
+
+fn present_access() -> Access {
+    Access {
+        state: ResourceState::PresentSource,
+        access: ResourceAccessFlags::NO_FLAGS,
+        stages: PipelineStageFlags::BOTTOM_OF_PIPE,
+    }
+}
+
+/// Works out how a pass accesses each of its resources, in the order that the resources are declared
+fn pass_accesses(pass: &RenderPassCreationInfo) -> Vec<(&str, Access)> {
+    let mut accesses: Vec<(&str, Access)> = Vec::new();
+    for resource_use in resource_uses(pass) {
+        let access = usage_access(pass, &resource_use);
+        match accesses
+            .iter_mut()
+            .find(|(resource, _)| *resource == resource_use.resource)
+        {
+            Some((_, existing)) => existing.merge(access),
+            None => accesses.push((resource_use.resource, access)),
+        }
+    }
+
+    accesses
+}
+
+fn usage_access(pass: &RenderPassCreationInfo, resource_use: &ResourceUse<'_>) -> Access {
+    let shader_stages = PipelineStageFlags::VERTEX_SHADER | PipelineStageFlags::FRAGMENT_SHADER;
+    match resource_use.usage {
+        ResourceUsage::SampledTexture => Access {
+            state: ResourceState::FragmentShaderReadOnly,
+            access: ResourceAccessFlags::SHADER_READ_BIT,
+            stages: PipelineStageFlags::FRAGMENT_SHADER,
+        },
+        ResourceUsage::ColorAttachment => {
+            // Attachments that aren't cleared are loaded, so that passes can blend with what's already there
+            let mut access = ResourceAccessFlags::COLOR_ATTACHMENT_WRITE_BIT;
+            if !clears(pass, resource_use.resource) {
+                access |= ResourceAccessFlags::COLOR_ATTACHMENT_READ_BIT;
+            }
+
+            Access {
+                state: ResourceState::ColorAttachment,
+                access,
+                stages: PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            }
+        }
+        ResourceUsage::DepthAttachment => Access {
+            state: ResourceState::DepthStencilAttachment,
+            access: ResourceAccessFlags::DEPTH_STENCIL_ATTACHMENT_READ_BIT
+                | ResourceAccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE_BIT,
+            stages: PipelineStageFlags::EARLY_FRAGMENT_TESTS | PipelineStageFlags::LATE_FRAGMENT_TESTS,
+        },
+        ResourceUsage::ReadBuffer => Access {
+            state: ResourceState::General,
+            access: ResourceAccessFlags::SHADER_READ_BIT,
+            stages: shader_stages,
+        },
+        ResourceUsage::WriteBuffer => Access {
+            state: ResourceState::General,
+            access: ResourceAccessFlags::SHADER_WRITE_BIT,
+            stages: shader_stages,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render_graph::*;
+    use crate::rhi::*;
+    use crate::shaderpack::*;
+
+    fn pass(name: &str, inputs: &[&str], outputs: &[&str]) -> RenderPassCreationInfo {
+        let outputs: Vec<_> = outputs
+            .iter()
+            .map(|name| serde_json::json!({ "name": name, "clear": true }))
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "textureInputs": inputs,
+            "textureOutputs": outputs,
+        }))
+        .unwrap()
+    }
+
+    fn transitions(barriers: &[PlannedBarrier]) -> Vec<(&str, ResourceState, ResourceState)> {
+        barriers
+            .iter()
+            .map(|barrier| {
+                (
+                    barrier.resource.as_str(),
+                    barrier.initial_state.clone(),
+                    barrier.final_state.clone(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn plans_minimal_barriers() {
+        let passes = vec![
+            pass("GBuffer", &[], &["Albedo"]),
+            pass("Lighting", &["Albedo"], &["Backbuffer"]),
+            pass("Bloom", &["Albedo"], &["Bloom"]),
+            pass("Overlay", &["Bloom"], &["Backbuffer"]),
+        ];
+        let graph = RenderGraph::build(&passes).unwrap();
+
+        let plan = plan_barriers(&graph, &passes);
+
+        assert_eq!(
+            transitions(&plan.passes[0].barriers),
+            vec![("Albedo", ResourceState::Undefined, ResourceState::ColorAttachment)]
+        );
+        let gbuffer_barrier = &plan.passes[0].barriers[0];
+        assert_eq!(gbuffer_barrier.access_before_barrier, ResourceAccessFlags::NO_FLAGS);
+        assert_eq!(gbuffer_barrier.stages_before_barrier, PipelineStageFlags::TOP_OF_PIPE);
+        assert_eq!(
+            gbuffer_barrier.access_after_barrier,
+            ResourceAccessFlags::COLOR_ATTACHMENT_WRITE_BIT
+        );
+
+        assert_eq!(
+            transitions(&plan.passes[1].barriers),
+            vec![
+                (
+                    "Albedo",
+                    ResourceState::ColorAttachment,
+                    ResourceState::FragmentShaderReadOnly
+                ),
+                ("Backbuffer", ResourceState::Undefined, ResourceState::ColorAttachment),
+            ]
+        );
+
+        // Albedo is already readable, so only Bloom needs a barrier
+        assert_eq!(
+            transitions(&plan.passes[2].barriers),
+            vec![("Bloom", ResourceState::Undefined, ResourceState::ColorAttachment)]
+        );
+
+        // The backbuffer stays a color attachment, but Lighting's writes have to finish before Overlay's
+        let overlay = &plan.passes[3].barriers;
+        assert_eq!(
+            transitions(overlay),
+            vec![
+                (
+                    "Bloom",
+                    ResourceState::ColorAttachment,
+                    ResourceState::FragmentShaderReadOnly
+                ),
+                (
+                    "Backbuffer",
+                    ResourceState::ColorAttachment,
+                    ResourceState::ColorAttachment
+                ),
+            ]
+        );
+        assert_eq!(
+            overlay[1].stages_before_barrier,
+            PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+        );
+
+        assert_eq!(
+            transitions(&plan.final_barriers),
+            vec![(
+                "Backbuffer",
+                ResourceState::ColorAttachment,
+                ResourceState::PresentSource
+            )]
+        );
+    }
+
+    #[test]
+    fn resources_read_first_keep_their_state_between_frames() {
+        let passes = vec![
+            pass("Reproject", &["History"], &["Reprojected"]),
+            pass("Resolve", &["Reprojected"], &["History"]),
+            pass("Final", &["History"], &["Backbuffer"]),
+        ];
+        let graph = RenderGraph::build(&passes).unwrap();
+
+        let plan = plan_barriers(&graph, &passes);
+
+        // The last frame left History readable, so the first pass can read it as-is
+        assert_eq!(
+            transitions(&plan.passes[0].barriers),
+            vec![("Reprojected", ResourceState::Undefined, ResourceState::ColorAttachment)]
+        );
+
+        let passes = vec![
+            pass("Reproject", &["History"], &["Reprojected"]),
+            pass("Resolve", &["Reprojected"], &["History"]),
+        ];
+        let graph = RenderGraph::build(&passes).unwrap();
+
+        let plan = plan_barriers(&graph, &passes);
+
+        // Here the last frame left History as a color attachment, so it has to be transitioned first
+        assert_eq!(
+            transitions(&plan.passes[0].barriers),
+            vec![
+                (
+                    "History",
+                    ResourceState::ColorAttachment,
+                    ResourceState::FragmentShaderReadOnly
+                ),
+                ("Reprojected", ResourceState::Undefined, ResourceState::ColorAttachment),
+            ]
+        );
+        assert!(plan.final_barriers.is_empty());
+    }
+
+    #[test]
+    fn writes_wait_for_earlier_reads() {
+        let passes: Vec<RenderPassCreationInfo> = serde_json::from_value(serde_json::json!([
+            { "name": "Cull", "inputBuffers": ["Lights"], "outputBuffers": ["Visible"] },
+            { "name": "Update", "outputBuffers": ["Lights"] },
+            { "name": "Decals", "inputBuffers": ["Visible"], "textureOutputs": [{ "name": "Backbuffer" }] },
+        ]))
+        .unwrap();
+        let graph = RenderGraph::build(&passes).unwrap();
+
+        let plan = plan_barriers(&graph, &passes);
+
+        // Update reads and writes Lights in the same state, but it has to wait for Cull to finish reading
+        let update = &plan.passes[1].barriers;
+        assert_eq!(
+            transitions(update),
+            vec![("Lights", ResourceState::General, ResourceState::General)]
+        );
+        assert_eq!(update[0].access_before_barrier, ResourceAccessFlags::SHADER_READ_BIT);
+        assert_eq!(update[0].access_after_barrier, ResourceAccessFlags::SHADER_WRITE_BIT);
+
+        // The backbuffer is loaded rather than cleared, so it starts in the state that the last frame left it in
+        assert_eq!(
+            transitions(&plan.passes[2].barriers),
+            vec![
+                ("Visible", ResourceState::General, ResourceState::General),
+                (
+                    "Backbuffer",
+                    ResourceState::PresentSource,
+                    ResourceState::ColorAttachment
+                ),
+            ]
+        );
+    }
+}
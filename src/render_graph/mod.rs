@@ -0,0 +1,18 @@
+//! Nova's render graph
+//!
+//! A shaderpack's render passes declare the resources that they read from and write to. The render graph uses those
+//! declarations to work out the order that the passes run in, so that shaderpack authors don't have to
+
+mod barrier_planning;
+mod pass_culling;
+mod pass_graph;
+mod resource_usage;
+mod texture_aliasing;
+mod texture_extents;
+
+pub use barrier_planning::*;
+pub use pass_culling::*;
+pub use pass_graph::*;
+pub use resource_usage::*;
+pub use texture_aliasing::*;
+pub use texture_extents::*;
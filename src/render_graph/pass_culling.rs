@@ -0,0 +1,253 @@
+//! Removing render passes that don't contribute to the final image
+
+use crate::render_graph::*;
+use crate::shaderpack::*;
+use log::info;
+use log::warn;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+/// Everything that was removed from a shaderpack because it doesn't contribute to the final image
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct CullingReport {
+    /// The names of the passes that were removed
+    pub passes: Vec<String>,
+
+    /// The names of the pipelines that were removed, because they belong to a removed pass
+    pub pipelines: Vec<String>,
+
+    /// The material passes that were removed because they use a removed pipeline, as (material name, pass name)
+    pub material_passes: Vec<(String, String)>,
+
+    /// The names of the materials that were removed, because all their passes were removed
+    pub materials: Vec<String>,
+}
+
+impl CullingReport {
+    /// Whether nothing was removed
+    pub fn is_empty(&self) -> bool {
+        self.passes.is_empty()
+    }
+}
+
+/// Removes the passes that don't contribute to the backbuffer, along with their pipelines and materials
+///
+/// Shaderpacks often have passes left over from debugging, whose outputs are never used. Running those passes would
+/// waste GPU time
+///
+/// A pass contributes to the backbuffer if it writes to the backbuffer, or if a pass that contributes to the backbuffer
+/// depends on it. Passes that are explicitly listed as dependencies always count. When a pass writes to a resource that
+/// another pass already wrote to, the earlier pass only counts if the later pass doesn't clear the resource
+///
+/// Logs each pass that's removed. If no pass writes to the backbuffer, nothing can contribute to it. That's almost
+/// certainly a mistake, so this logs a warning and leaves the shaderpack alone instead
+///
+/// # Parameters
+///
+/// * `data` - The shaderpack to remove passes from
+pub fn cull_unused_passes(data: &mut ShaderpackData) -> Result<CullingReport, RenderGraphError> {
+    let graph = RenderGraph::build(&data.passes)?;
+
+    let mut live: Vec<&str> = data
+        .passes
+        .iter()
+        .filter(|pass| writes_to(pass, BACKBUFFER_NAME))
+        .map(|pass| pass.name.as_str())
+        .collect();
+    if live.is_empty() {
+        warn!(
+            "No pass writes to {}, so nothing will be shown on screen. Keeping every pass",
+            BACKBUFFER_NAME
+        );
+        return Ok(CullingReport::default());
+    }
+
+    let ordered_passes: HashMap<&str, &OrderedPass> =
+        graph.passes().iter().map(|pass| (pass.name.as_str(), pass)).collect();
+    let mut live_passes: HashSet<&str> = live.iter().cloned().collect();
+    while let Some(name) = live.pop() {
+        let pass = &data.passes[ordered_passes[name].index];
+        for dependency in &ordered_passes[name].dependencies {
+            let contributes = match &dependency.reason {
+                DependencyReason::Explicit | DependencyReason::ReadAfterWrite(_) => true,
+                DependencyReason::WriteAfterWrite(resource) => !clears(pass, resource),
+                DependencyReason::WriteAfterRead(_) => false,
+            };
+            if contributes && live_passes.insert(dependency.pass.as_str()) {
+                live.push(dependency.pass.as_str());
+            }
+        }
+    }
+
+    let mut report = CullingReport::default();
+    for pass in &data.passes {
+        if !live_passes.contains(pass.name.as_str()) {
+            info!(
+                "Culling pass {} because it doesn't contribute to {}",
+                pass.name, BACKBUFFER_NAME
+            );
+            report.passes.push(pass.name.clone());
+        }
+    }
+    if report.is_empty() {
+        return Ok(report);
+    }
+
+    let culled_passes: HashSet<String> = report.passes.iter().cloned().collect();
+    data.passes.retain(|pass| !culled_passes.contains(&pass.name));
+
+    let (pipelines, culled_pipelines): (Vec<_>, Vec<_>) = data
+        .pipelines
+        .drain(..)
+        .partition(|pipeline| !culled_passes.contains(&pipeline.pass));
+    data.pipelines = pipelines;
+    report.pipelines = culled_pipelines.into_iter().map(|pipeline| pipeline.name).collect();
+
+    let culled_pipelines: HashSet<&String> = report.pipelines.iter().collect();
+    for material in &mut data.materials {
+        let material_name = &material.name;
+        let material_passes = &mut report.material_passes;
+        material.passes.retain(|pass| {
+            let culled = culled_pipelines.contains(&pass.pipeline);
+            if culled {
+                material_passes.push((material_name.clone(), pass.name.clone()));
+            }
+            !culled
+        });
+        if material.passes.is_empty() {
+            info!("Culling material {} because all its passes were culled", material.name);
+            report.materials.push(material.name.clone());
+        }
+    }
+    data.materials.retain(|material| !material.passes.is_empty());
+
+    Ok(report)
+}
+
+fn writes_to(pass: &RenderPassCreationInfo, resource: &str) -> bool {
+    resource_uses(pass)
+        .iter()
+        .any(|resource_use| resource_use.resource == resource && resource_use.usage.is_write())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render_graph::*;
+    use crate::shaderpack::*;
+
+    fn shaderpack() -> ShaderpackData {
+        serde_json::from_str::<TestShaderpack>(
+            r#"{
+                "passes": [
+                    { "name": "Shadow", "textureOutputs": [{ "name": "ShadowMap" }] },
+                    { "name": "Debug", "textureInputs": ["ShadowMap"], "textureOutputs": [{ "name": "DebugView" }] },
+                    { "name": "Sky", "textureOutputs": [{ "name": "Scene" }] },
+                    { "name": "Forward", "textureInputs": ["ShadowMap"], "textureOutputs": [{ "name": "Scene" }] },
+                    { "name": "Final", "textureInputs": ["Scene"], "textureOutputs": [{ "name": "Backbuffer" }] }
+                ],
+                "pipelines": [
+                    { "name": "shadow", "pass": "Shadow", "vertexShader": "shadow.vert" },
+                    { "name": "debug", "pass": "Debug", "vertexShader": "debug.vert" },
+                    { "name": "forward", "pass": "Forward", "vertexShader": "forward.vert" }
+                ],
+                "materials": [
+                    {
+                        "name": "terrain",
+                        "passes": [
+                            { "name": "shadow", "pipeline": "shadow" },
+                            { "name": "debug", "pipeline": "debug" },
+                            { "name": "main", "pipeline": "forward" }
+                        ]
+                    },
+                    { "name": "debug_overlay", "passes": [{ "name": "main", "pipeline": "debug" }] }
+                ]
+            }"#,
+        )
+        .unwrap()
+        .into()
+    }
+
+    #[derive(serde::Deserialize)]
+    struct TestShaderpack {
+        passes: Vec<RenderPassCreationInfo>,
+        pipelines: Vec<PipelineCreationInfo>,
+        materials: Vec<MaterialData>,
+    }
+
+    impl From<TestShaderpack> for ShaderpackData {
+        fn from(pack: TestShaderpack) -> ShaderpackData {
+            ShaderpackData {
+                passes: pack.passes,
+                pipelines: pack.pipelines,
+                materials: pack.materials,
+                ..ShaderpackData::default()
+            }
+        }
+    }
+
+    #[test]
+    fn culls_passes_that_dont_reach_the_backbuffer() {
+        let mut data = shaderpack();
+
+        let report = cull_unused_passes(&mut data).unwrap();
+        assert_eq!(report.passes, vec!["Debug"]);
+        assert_eq!(report.pipelines, vec!["debug"]);
+        assert_eq!(
+            report.material_passes,
+            vec![
+                ("terrain".to_owned(), "debug".to_owned()),
+                ("debug_overlay".to_owned(), "main".to_owned())
+            ]
+        );
+        assert_eq!(report.materials, vec!["debug_overlay"]);
+
+        let passes: Vec<_> = data.passes.iter().map(|pass| pass.name.as_str()).collect();
+        assert_eq!(passes, vec!["Shadow", "Sky", "Forward", "Final"]);
+        assert_eq!(data.pipelines.len(), 2);
+        assert_eq!(data.materials.len(), 1);
+        assert_eq!(data.materials[0].passes.len(), 2);
+    }
+
+    #[test]
+    fn cleared_outputs_hide_earlier_writes() {
+        let mut data = shaderpack();
+        data.passes[3].texture_outputs[0].clear = true;
+
+        let report = cull_unused_passes(&mut data).unwrap();
+        assert_eq!(report.passes, vec!["Debug", "Sky"]);
+    }
+
+    #[test]
+    fn keeps_writers_whose_output_is_loaded_after_a_read() {
+        let mut data: ShaderpackData = serde_json::from_str::<TestShaderpack>(
+            r#"{
+                "passes": [
+                    { "name": "A", "textureOutputs": [{ "name": "X" }] },
+                    { "name": "B", "textureInputs": ["X"], "textureOutputs": [{ "name": "Y" }] },
+                    { "name": "C", "textureOutputs": [{ "name": "X" }] },
+                    { "name": "D", "textureInputs": ["X"], "textureOutputs": [{ "name": "Backbuffer" }] }
+                ],
+                "pipelines": [],
+                "materials": []
+            }"#,
+        )
+        .unwrap()
+        .into();
+
+        let report = cull_unused_passes(&mut data).unwrap();
+        assert_eq!(report.passes, vec!["B"]);
+
+        let passes: Vec<_> = data.passes.iter().map(|pass| pass.name.as_str()).collect();
+        assert_eq!(passes, vec!["A", "C", "D"]);
+    }
+
+    #[test]
+    fn keeps_everything_without_a_backbuffer() {
+        let mut data = shaderpack();
+        data.passes.pop();
+
+        let report = cull_unused_passes(&mut data).unwrap();
+        assert!(report.is_empty());
+        assert_eq!(data.passes.len(), 4);
+    }
+}
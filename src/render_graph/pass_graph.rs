@@ -0,0 +1,476 @@
+//! Working out the order that render passes run in
+
+use crate::render_graph::*;
+use crate::shaderpack::*;
+use failure::Fail;
+use log::warn;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Why one pass has to run before another
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum DependencyReason {
+    /// The later pass lists the earlier pass in its `dependencies`
+    Explicit,
+
+    /// The later pass reads a resource that the earlier pass writes
+    ReadAfterWrite(String),
+
+    /// Both passes write to a resource, and the later pass was declared after the earlier one
+    WriteAfterWrite(String),
+
+    /// The later pass writes to a resource that the earlier pass reads, so it has to wait until the earlier pass is
+    /// done reading
+    WriteAfterRead(String),
+}
+
+/// A pass that has to run before another pass
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PassDependency {
+    /// The name of the pass that has to run first
+    pub pass: String,
+
+    pub reason: DependencyReason,
+}
+
+impl fmt::Display for PassDependency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.reason {
+            DependencyReason::Explicit => write!(f, "{} is listed as a dependency", self.pass),
+            DependencyReason::ReadAfterWrite(resource) => {
+                write!(f, "{} writes {}, which this pass reads", self.pass, resource)
+            }
+            DependencyReason::WriteAfterWrite(resource) => {
+                write!(f, "{} writes {} before this pass does", self.pass, resource)
+            }
+            DependencyReason::WriteAfterRead(resource) => {
+                write!(f, "{} reads {} before this pass overwrites it", self.pass, resource)
+            }
+        }
+    }
+}
+
+/// A pass in the order that passes run in
+#[derive(Debug, Clone)]
+pub struct OrderedPass {
+    /// The name of the pass
+    pub name: String,
+
+    /// Where the pass is in the list of passes that the graph was built from
+    pub index: usize,
+
+    /// Every pass that has to run before this one, and why. This explains why the pass is where it is
+    pub dependencies: Vec<PassDependency>,
+}
+
+/// A pass that reads a resource before any pass has written to it this frame
+///
+/// The pass gets whatever was left in the resource by the last frame, or garbage if this is the first frame. That's
+/// probably not what the shaderpack author meant. Usually the pass is missing a dependency on the pass that writes to
+/// the resource
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ReadBeforeWrite {
+    /// The pass that reads the resource
+    pub pass: String,
+
+    pub resource: String,
+
+    /// The first pass that writes to the resource, after `pass` reads it
+    pub writer: String,
+}
+
+/// Errors that stop passes from being put in order
+#[derive(Fail, Debug)]
+pub enum RenderGraphError {
+    #[fail(display = "There's more than one pass named {}", _0)]
+    DuplicatePass(String),
+
+    #[fail(display = "Pass {} depends on {}, which doesn't exist", pass, dependency)]
+    UnknownDependency { pass: String, dependency: String },
+
+    #[fail(display = "Passes {:?} depend on each other in a loop", passes)]
+    DependencyCycle {
+        /// The passes in the loop. Each one has to run before the next, and the last one has to run before the first
+        passes: Vec<String>,
+    },
+}
+
+/// Render passes, in the order that they run in
+///
+/// A pass runs after:
+/// - Every pass in its `dependencies`
+/// - The last pass declared before it that writes to a resource that it reads
+/// - For each resource that it writes to, the passes declared before it that read the resource since it was last
+///   written to, and the last pass declared before it that writes the resource. The last writer is left out if
+///   something has read the resource since and this pass clears it, since nothing that pass wrote is kept
+///
+/// Resource usage is matched up in the order that passes are declared, so that a pass that writes to a resource that
+/// other passes have already written to replaces what they wrote. The exception is that a pass is always treated as if
+/// it were declared after its explicit dependencies, so explicit dependencies can move passes around
+///
+/// Passes that don't have to run in a specific order run in the order they were declared in
+#[derive(Debug, Clone)]
+pub struct RenderGraph {
+    passes: Vec<OrderedPass>,
+    hazards: Vec<ReadBeforeWrite>,
+}
+
+impl RenderGraph {
+    /// Puts passes in order
+    ///
+    /// Logs a warning for each pass that reads a resource before it's written
+    ///
+    /// # Parameters
+    ///
+    /// * `passes` - The passes to put in order, in the order that they were declared
+    pub fn build(passes: &[RenderPassCreationInfo]) -> Result<RenderGraph, RenderGraphError> {
+        let mut indices = HashMap::new();
+        for (index, pass) in passes.iter().enumerate() {
+            if indices.insert(pass.name.as_str(), index).is_some() {
+                return Err(RenderGraphError::DuplicatePass(pass.name.clone()));
+            }
+        }
+
+        let mut dependencies: Vec<Vec<(usize, DependencyReason)>> = vec![Vec::new(); passes.len()];
+        for (index, pass) in passes.iter().enumerate() {
+            for dependency in &pass.dependencies {
+                match indices.get(dependency.as_str()) {
+                    Some(dependency) => {
+                        add_dependency(&mut dependencies, index, *dependency, DependencyReason::Explicit)
+                    }
+                    None => {
+                        return Err(RenderGraphError::UnknownDependency {
+                            pass: pass.name.clone(),
+                            dependency: dependency.clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        let cycle_error = |cycle: Vec<usize>| RenderGraphError::DependencyCycle {
+            passes: cycle.into_iter().map(|index| passes[index].name.clone()).collect(),
+        };
+
+        // Resource usage is matched up in an order where every pass comes after its explicit dependencies, so that a
+        // pass that depends on another sees what the other pass wrote. That also means resource usage always agrees
+        // with explicit dependencies, so only explicit dependencies can form a loop
+        let declaration_order = sort(&dependencies).map_err(cycle_error)?;
+
+        // The last pass to write each resource, and the passes that have read it since
+        let mut last_writers: HashMap<&str, usize> = HashMap::new();
+        let mut readers: HashMap<&str, Vec<usize>> = HashMap::new();
+        for index in declaration_order {
+            for resource_use in resource_uses(&passes[index]) {
+                let resource = resource_use.resource;
+                if resource_use.usage.is_write() {
+                    let resource_readers = readers.remove(resource);
+                    let read_since_written = resource_readers.is_some();
+                    for reader in resource_readers.into_iter().flatten() {
+                        add_dependency(
+                            &mut dependencies,
+                            index,
+                            reader,
+                            DependencyReason::WriteAfterRead(resource.to_owned()),
+                        );
+                    }
+
+                    // A pass that doesn't clear the resource keeps what the last writer wrote, even if other passes
+                    // have read it since
+                    if !read_since_written || !clears(&passes[index], resource) {
+                        if let Some(writer) = last_writers.get(resource) {
+                            add_dependency(
+                                &mut dependencies,
+                                index,
+                                *writer,
+                                DependencyReason::WriteAfterWrite(resource.to_owned()),
+                            );
+                        }
+                    }
+                    last_writers.insert(resource, index);
+                } else {
+                    if let Some(writer) = last_writers.get(resource) {
+                        add_dependency(
+                            &mut dependencies,
+                            index,
+                            *writer,
+                            DependencyReason::ReadAfterWrite(resource.to_owned()),
+                        );
+                    }
+                    readers.entry(resource).or_default().push(index);
+                }
+            }
+        }
+
+        let order = sort(&dependencies).map_err(cycle_error)?;
+
+        let hazards = find_reads_before_writes(passes, &order);
+        for hazard in &hazards {
+            warn!(
+                "Pass {} reads {} before {} writes it, so it will read what was left over from the last frame",
+                hazard.pass, hazard.resource, hazard.writer
+            );
+        }
+
+        let passes = order
+            .into_iter()
+            .map(|index| OrderedPass {
+                name: passes[index].name.clone(),
+                index,
+                dependencies: dependencies[index]
+                    .iter()
+                    .map(|(dependency, reason)| PassDependency {
+                        pass: passes[*dependency].name.clone(),
+                        reason: reason.clone(),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Ok(RenderGraph { passes, hazards })
+    }
+
+    /// The passes, in the order that they run in
+    pub fn passes(&self) -> &[OrderedPass] {
+        &self.passes
+    }
+
+    /// The names of the passes, in the order that they run in
+    pub fn order(&self) -> impl Iterator<Item = &str> {
+        self.passes.iter().map(|pass| pass.name.as_str())
+    }
+
+    /// Every time a pass reads a resource before it's written
+    pub fn hazards(&self) -> &[ReadBeforeWrite] {
+        &self.hazards
+    }
+}
+
+fn add_dependency(
+    dependencies: &mut [Vec<(usize, DependencyReason)>],
+    pass: usize,
+    dependency: usize,
+    reason: DependencyReason,
+) {
+    if pass != dependency && !dependencies[pass].contains(&(dependency, reason.clone())) {
+        dependencies[pass].push((dependency, reason));
+    }
+}
+
+/// Sorts passes so that every pass comes after its dependencies, keeping passes in declaration order where possible
+///
+/// Returns the passes in a dependency cycle if there is one
+fn sort(dependencies: &[Vec<(usize, DependencyReason)>]) -> Result<Vec<usize>, Vec<usize>> {
+    let dependencies: Vec<BTreeSet<usize>> = dependencies
+        .iter()
+        .map(|pass_dependencies| pass_dependencies.iter().map(|(dependency, _)| *dependency).collect())
+        .collect();
+    let mut dependents = vec![Vec::new(); dependencies.len()];
+    for (pass, pass_dependencies) in dependencies.iter().enumerate() {
+        for dependency in pass_dependencies {
+            dependents[*dependency].push(pass);
+        }
+    }
+
+    let mut remaining: Vec<usize> = dependencies.iter().map(|dependencies| dependencies.len()).collect();
+    let mut ready: BTreeSet<usize> = (0..dependencies.len()).filter(|pass| remaining[*pass] == 0).collect();
+    let mut order = Vec::with_capacity(dependencies.len());
+
+    while let Some(pass) = ready.iter().next().cloned() {
+        ready.remove(&pass);
+        order.push(pass);
+        for dependent in &dependents[pass] {
+            remaining[*dependent] -= 1;
+            if remaining[*dependent] == 0 {
+                ready.insert(*dependent);
+            }
+        }
+    }
+
+    if order.len() == dependencies.len() {
+        return Ok(order);
+    }
+
+    // Every pass that's left has a dependency that's also left, so following dependencies must eventually loop
+    let mut path = vec![(0..dependencies.len()).find(|pass| remaining[*pass] > 0).unwrap()];
+    loop {
+        let pass = *path.last().unwrap();
+        let dependency = *dependencies[pass]
+            .iter()
+            .find(|dependency| remaining[**dependency] > 0)
+            .unwrap();
+        if let Some(start) = path.iter().position(|pass| *pass == dependency) {
+            let mut cycle: Vec<usize> = path[start..].iter().rev().cloned().collect();
+            let first = cycle.iter().enumerate().min_by_key(|(_, pass)| **pass).unwrap().0;
+            cycle.rotate_left(first);
+            return Err(cycle);
+        }
+        path.push(dependency);
+    }
+}
+
+fn find_reads_before_writes(passes: &[RenderPassCreationInfo], order: &[usize]) -> Vec<ReadBeforeWrite> {
+    let mut hazards = Vec::new();
+    let mut written = BTreeSet::new();
+
+    for (position, pass) in order.iter().enumerate() {
+        for resource_use in resource_uses(&passes[*pass]) {
+            if resource_use.usage.is_write() {
+                written.insert(resource_use.resource);
+                continue;
+            }
+            if written.contains(resource_use.resource) {
+                continue;
+            }
+
+            let writer = order[position..].iter().find(|later| {
+                resource_uses(&passes[**later])
+                    .iter()
+                    .any(|later_use| later_use.resource == resource_use.resource && later_use.usage.is_write())
+            });
+            if let Some(writer) = writer {
+                hazards.push(ReadBeforeWrite {
+                    pass: passes[*pass].name.clone(),
+                    resource: resource_use.resource.to_owned(),
+                    writer: passes[*writer].name.clone(),
+                });
+            }
+        }
+    }
+
+    hazards
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render_graph::*;
+    use crate::shaderpack::*;
+
+    fn pass(name: &str, dependencies: &[&str], inputs: &[&str], outputs: &[&str]) -> RenderPassCreationInfo {
+        RenderPassCreationInfo {
+            name: name.to_owned(),
+            dependencies: dependencies.iter().map(|name| name.to_string()).collect(),
+            texture_inputs: inputs.iter().map(|name| name.to_string()).collect(),
+            texture_outputs: outputs
+                .iter()
+                .map(|name| TextureAttachmentInfo {
+                    name: name.to_string(),
+                    pixel_format: PixelFormat::RGBA8,
+                    clear: false,
+                })
+                .collect(),
+            depth_texture: None,
+            input_buffers: Vec::new(),
+            output_buffers: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn orders_passes_by_resource_usage() {
+        let passes = vec![
+            pass("Shadow", &[], &[], &["ShadowMap"]),
+            pass("GBuffer", &[], &[], &["Albedo"]),
+            pass("Lighting", &[], &["Albedo", "ShadowMap"], &["Lit"]),
+            pass("Final", &[], &["Lit"], &["Backbuffer"]),
+            pass("Overlay", &["Final"], &[], &["Backbuffer"]),
+        ];
+
+        let graph = RenderGraph::build(&passes).unwrap();
+        assert_eq!(
+            graph.order().collect::<Vec<_>>(),
+            vec!["Shadow", "GBuffer", "Lighting", "Final", "Overlay"]
+        );
+        assert!(graph.hazards().is_empty());
+
+        let lighting = &graph.passes()[2];
+        assert_eq!(lighting.index, 2);
+        let explanations: Vec<_> = lighting
+            .dependencies
+            .iter()
+            .map(|dependency| dependency.to_string())
+            .collect();
+        assert_eq!(
+            explanations,
+            vec![
+                "GBuffer writes Albedo, which this pass reads",
+                "Shadow writes ShadowMap, which this pass reads"
+            ]
+        );
+
+        let overlay = &graph.passes()[4].dependencies;
+        assert_eq!(overlay.len(), 2);
+        assert_eq!(overlay[0].reason, DependencyReason::Explicit);
+        assert_eq!(
+            overlay[1].reason,
+            DependencyReason::WriteAfterWrite("Backbuffer".to_owned())
+        );
+    }
+
+    #[test]
+    fn explicit_dependencies_move_passes() {
+        let passes = vec![
+            pass("Composite", &[], &["Albedo"], &["Backbuffer"]),
+            pass("GBuffer", &[], &[], &["Albedo"]),
+        ];
+        let graph = RenderGraph::build(&passes).unwrap();
+        assert_eq!(graph.order().collect::<Vec<_>>(), vec!["Composite", "GBuffer"]);
+        assert_eq!(
+            graph.hazards(),
+            &[ReadBeforeWrite {
+                pass: "Composite".to_owned(),
+                resource: "Albedo".to_owned(),
+                writer: "GBuffer".to_owned(),
+            }]
+        );
+
+        let passes = vec![
+            pass("Composite", &["GBuffer"], &["Albedo"], &["Backbuffer"]),
+            pass("GBuffer", &[], &[], &["Albedo"]),
+        ];
+        let graph = RenderGraph::build(&passes).unwrap();
+        assert_eq!(graph.order().collect::<Vec<_>>(), vec!["GBuffer", "Composite"]);
+        assert!(graph.hazards().is_empty());
+    }
+
+    #[test]
+    fn readers_finish_before_resources_are_overwritten() {
+        let passes = vec![
+            pass("Blur", &[], &["Ping"], &["Pong"]),
+            pass("Write", &[], &[], &["Ping"]),
+            pass("Bloom", &["Write"], &["Pong"], &["Backbuffer"]),
+        ];
+        let graph = RenderGraph::build(&passes).unwrap();
+        assert_eq!(graph.order().collect::<Vec<_>>(), vec!["Blur", "Write", "Bloom"]);
+        assert_eq!(
+            graph.passes()[1].dependencies[0].reason,
+            DependencyReason::WriteAfterRead("Ping".to_owned())
+        );
+    }
+
+    #[test]
+    fn reports_bad_graphs() {
+        let passes = vec![
+            pass("A", &["C"], &[], &["X"]),
+            pass("B", &["A"], &["X"], &["Y"]),
+            pass("C", &["B"], &["Y"], &[]),
+        ];
+        match RenderGraph::build(&passes) {
+            Err(RenderGraphError::DependencyCycle { passes }) => assert_eq!(passes, vec!["A", "B", "C"]),
+            other => panic!("Expected DependencyCycle, got {:?}", other),
+        }
+
+        match RenderGraph::build(&[pass("A", &["Missing"], &[], &[])]) {
+            Err(RenderGraphError::UnknownDependency { pass, dependency }) => {
+                assert_eq!(pass, "A");
+                assert_eq!(dependency, "Missing");
+            }
+            other => panic!("Expected UnknownDependency, got {:?}", other),
+        }
+
+        match RenderGraph::build(&[pass("A", &[], &[], &[]), pass("A", &[], &[], &[])]) {
+            Err(RenderGraphError::DuplicatePass(name)) => assert_eq!(name, "A"),
+            other => panic!("Expected DuplicatePass, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! How render passes use resources
+
+use crate::shaderpack::*;
+
+/// The name of the texture that gets presented to the screen
+pub const BACKBUFFER_NAME: &str = "Backbuffer";
+
+/// A way that a render pass can use a resource
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ResourceUsage {
+    /// The pass samples the texture in its shaders
+    SampledTexture,
+
+    /// The pass renders to the texture as a color attachment
+    ColorAttachment,
+
+    /// The pass uses the texture as its depth attachment
+    DepthAttachment,
+
+    /// The pass reads from the buffer
+    ReadBuffer,
+
+    /// The pass writes to the buffer
+    WriteBuffer,
+}
+
+impl ResourceUsage {
+    /// Whether this usage changes the contents of the resource
+    pub fn is_write(self) -> bool {
+        match self {
+            ResourceUsage::SampledTexture | ResourceUsage::ReadBuffer => false,
+            ResourceUsage::ColorAttachment | ResourceUsage::DepthAttachment | ResourceUsage::WriteBuffer => true,
+        }
+    }
+}
+
+/// Whether a pass clears a resource when it renders to it, instead of loading what's already there
+///
+/// # Parameters
+///
+/// * `pass` - The pass that renders to the resource
+/// * `resource` - The name of the resource
+pub fn clears(pass: &RenderPassCreationInfo, resource: &str) -> bool {
+    pass.texture_outputs
+        .iter()
+        .chain(pass.depth_texture.iter())
+        .any(|attachment| attachment.name == resource && attachment.clear)
+}
+
+/// A resource that a render pass uses, and how it uses it
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ResourceUse<'a> {
+    /// The name of the resource
+    pub resource: &'a str,
+
+    pub usage: ResourceUsage,
+}
+
+/// Lists every resource that a pass uses, in the order that they're declared
+///
+/// Reads come before writes, so a pass that reads from and writes to the same resource reads the old contents first
+///
+/// # Parameters
+///
+/// * `pass` - The pass to list the resources of
+pub fn resource_uses(pass: &RenderPassCreationInfo) -> Vec<ResourceUse<'_>> {
+    let sampled = pass
+        .texture_inputs
+        .iter()
+        .map(|name| (name, ResourceUsage::SampledTexture));
+    let read_buffers = pass.input_buffers.iter().map(|name| (name, ResourceUsage::ReadBuffer));
+    let color = pass
+        .texture_outputs
+        .iter()
+        .map(|attachment| (&attachment.name, ResourceUsage::ColorAttachment));
+    let depth = pass
+        .depth_texture
+        .iter()
+        .map(|attachment| (&attachment.name, ResourceUsage::DepthAttachment));
+    let write_buffers = pass
+        .output_buffers
+        .iter()
+        .map(|name| (name, ResourceUsage::WriteBuffer));
+
+    sampled
+        .chain(read_buffers)
+        .chain(color)
+        .chain(depth)
+        .chain(write_buffers)
+        .map(|(resource, usage)| ResourceUse {
+            resource: resource.as_str(),
+            usage,
+        })
+        .collect()
+}
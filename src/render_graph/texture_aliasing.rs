@@ -0,0 +1,322 @@
+//! Letting textures that are never used at the same time share memory
+
+use crate::render_graph::*;
+use crate::shaderpack::*;
+use cgmath::Vector2;
+use std::collections::HashMap;
+
+/// When a texture is in use during a frame
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TextureLifetime {
+    /// The name of the texture
+    pub texture: String,
+
+    /// The position in the pass order of the first pass that uses the texture
+    pub first_use: usize,
+
+    /// The position in the pass order of the last pass that uses the texture
+    pub last_use: usize,
+
+    /// Whether the texture has to keep its contents between frames
+    ///
+    /// This is true when the first pass to use the texture reads from it, or renders to it without clearing it, since
+    /// that pass uses what was left in the texture by the last frame. These textures are live for the whole frame, so
+    /// they never share memory
+    pub whole_frame: bool,
+
+    /// How much memory the texture needs, in bytes
+    pub size: u64,
+}
+
+impl TextureLifetime {
+    fn overlaps(&self, other: &TextureLifetime) -> bool {
+        self.first_use <= other.last_use && other.first_use <= self.last_use
+    }
+}
+
+/// A block of memory that one or more textures live in
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TextureAllocation {
+    /// The size of the allocation in bytes. This is the size of the biggest texture in it
+    pub size: u64,
+
+    /// Whether the textures in this allocation are depth textures. Depth textures and color textures can't share
+    /// memory on every GPU, so they're kept apart
+    pub depth: bool,
+
+    /// The names of the textures in this allocation, in the order that they're used. None of their lifetimes overlap
+    pub textures: Vec<String>,
+}
+
+/// How the textures that a shaderpack declares can share memory
+#[derive(Debug, Clone)]
+pub struct AliasingPlan {
+    /// The lifetime of every texture that's used by a pass, in the order that the textures were declared
+    pub lifetimes: Vec<TextureLifetime>,
+
+    pub allocations: Vec<TextureAllocation>,
+
+    /// Textures that are declared, but that no pass uses. They don't need any memory
+    pub unused_textures: Vec<String>,
+}
+
+impl AliasingPlan {
+    /// Finds the allocation that a texture lives in
+    ///
+    /// # Parameters
+    ///
+    /// * `texture` - The name of the texture to find
+    pub fn allocation_of(&self, texture: &str) -> Option<&TextureAllocation> {
+        self.allocations
+            .iter()
+            .find(|allocation| allocation.textures.iter().any(|name| name == texture))
+    }
+
+    /// How much memory the textures would need if each had its own allocation, in bytes
+    pub fn bytes_without_aliasing(&self) -> u64 {
+        self.lifetimes.iter().map(|lifetime| lifetime.size).sum()
+    }
+
+    /// How much memory the textures need with this plan, in bytes
+    pub fn bytes_with_aliasing(&self) -> u64 {
+        self.allocations.iter().map(|allocation| allocation.size).sum()
+    }
+
+    /// How much memory this plan saves, in bytes
+    pub fn bytes_saved(&self) -> u64 {
+        self.bytes_without_aliasing() - self.bytes_with_aliasing()
+    }
+}
+
+/// Works out when each texture is used, and which textures can share memory because they're never used at the same time
+///
+/// Textures are placed greedily, in the order that they're first used. Each texture goes in the allocation that it
+/// would grow the least, out of the allocations whose textures are all done before it's first used. If there isn't
+/// one, it gets a new allocation
+///
+/// Only textures declared by the shaderpack are planned. Textures that Nova provides, like the backbuffer, have their
+/// own memory
+///
+/// # Parameters
+///
+/// * `graph` - The order that the passes run in
+/// * `passes` - The passes that `graph` was built from
+/// * `textures` - The textures to plan memory for
+/// * `screen_size` - The size of the screen in pixels, used to size screen-relative textures
+pub fn plan_texture_aliasing(
+    graph: &RenderGraph,
+    passes: &[RenderPassCreationInfo],
+    textures: &[TextureCreateInfo],
+    screen_size: Vector2<u32>,
+) -> AliasingPlan {
+    // The first and last use of each texture, and whether the first use kept what was already in the texture
+    let mut uses: HashMap<&str, (usize, usize, bool)> = HashMap::new();
+    for (position, pass) in graph.passes().iter().enumerate() {
+        let pass = &passes[pass.index];
+        for resource_use in resource_uses(pass) {
+            let keeps_contents = !resource_use.usage.is_write() || !clears(pass, resource_use.resource);
+            uses.entry(resource_use.resource)
+                .and_modify(|(_, last_use, _)| *last_use = position)
+                .or_insert((position, position, keeps_contents));
+        }
+    }
+
+    let last_position = graph.passes().len().saturating_sub(1);
+    let mut lifetimes = Vec::new();
+    let mut unused_textures = Vec::new();
+    for texture in textures {
+        match uses.get(texture.name.as_str()) {
+            Some((first_use, last_use, first_use_keeps_contents)) => lifetimes.push(TextureLifetime {
+                texture: texture.name.clone(),
+                first_use: if *first_use_keeps_contents { 0 } else { *first_use },
+                last_use: if *first_use_keeps_contents {
+                    last_position
+                } else {
+                    *last_use
+                },
+                whole_frame: *first_use_keeps_contents,
+                size: texture.format.size_in_bytes(screen_size),
+            }),
+            None => unused_textures.push(texture.name.clone()),
+        }
+    }
+
+    let depth: HashMap<&str, bool> = textures
+        .iter()
+        .map(|texture| (texture.name.as_str(), texture.format.pixel_format.is_depth()))
+        .collect();
+
+    let mut placement_order: Vec<&TextureLifetime> = lifetimes.iter().collect();
+    placement_order.sort_by_key(|lifetime| lifetime.first_use);
+
+    let mut allocations: Vec<TextureAllocation> = Vec::new();
+    let mut allocation_lifetimes: Vec<Vec<&TextureLifetime>> = Vec::new();
+    for lifetime in placement_order {
+        let is_depth = depth[lifetime.texture.as_str()];
+        let best = allocations
+            .iter()
+            .enumerate()
+            .filter(|(index, allocation)| {
+                allocation.depth == is_depth
+                    && !allocation_lifetimes[*index]
+                        .iter()
+                        .any(|other| other.overlaps(lifetime))
+            })
+            .min_by_key(|(_, allocation)| {
+                let growth = lifetime.size.saturating_sub(allocation.size);
+                let waste = allocation.size.saturating_sub(lifetime.size);
+                (growth, waste)
+            })
+            .map(|(index, _)| index);
+
+        match best {
+            Some(index) => {
+                let allocation = &mut allocations[index];
+                allocation.size = allocation.size.max(lifetime.size);
+                allocation.textures.push(lifetime.texture.clone());
+                allocation_lifetimes[index].push(lifetime);
+            }
+            None => {
+                allocations.push(TextureAllocation {
+                    size: lifetime.size,
+                    depth: is_depth,
+                    textures: vec![lifetime.texture.clone()],
+                });
+                allocation_lifetimes.push(vec![lifetime]);
+            }
+        }
+    }
+
+    AliasingPlan {
+        lifetimes,
+        allocations,
+        unused_textures,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render_graph::*;
+    use crate::shaderpack::*;
+    use cgmath::Vector2;
+
+    fn pass(name: &str, inputs: &[&str], outputs: &[&str]) -> RenderPassCreationInfo {
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "textureInputs": inputs,
+            "textureOutputs": outputs
+                .iter()
+                .map(|name| serde_json::json!({ "name": name, "clear": true }))
+                .collect::<Vec<_>>(),
+        }))
+        .unwrap()
+    }
+
+    fn texture(name: &str, pixel_format: &str, width: f32) -> TextureCreateInfo {
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "format": { "pixelFormat": pixel_format, "width": width, "height": width },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn textures_with_separate_lifetimes_share_memory() {
+        let passes = vec![
+            pass("GBuffer", &[], &["Albedo", "Normals"]),
+            pass("Lighting", &["Albedo", "Normals"], &["Lit"]),
+            pass("BloomDown", &["Lit"], &["BloomHalf"]),
+            pass("BloomUp", &["BloomHalf"], &["Bloom"]),
+            pass("Final", &["Lit", "Bloom", "History"], &["Backbuffer", "History"]),
+        ];
+        let textures = vec![
+            texture("Albedo", "RGBA8", 1.0),
+            texture("Normals", "RGBA16F", 1.0),
+            texture("Lit", "RGBA16F", 1.0),
+            texture("BloomHalf", "RGBA16F", 0.5),
+            texture("Bloom", "RGBA16F", 1.0),
+            texture("History", "RGBA16F", 1.0),
+            texture("Unused", "RGBA8", 1.0),
+        ];
+        let graph = RenderGraph::build(&passes).unwrap();
+
+        let plan = plan_texture_aliasing(&graph, &passes, &textures, Vector2::new(100, 100));
+
+        assert_eq!(plan.unused_textures, vec!["Unused"]);
+        let history = plan
+            .lifetimes
+            .iter()
+            .find(|lifetime| lifetime.texture == "History")
+            .unwrap();
+        assert!(history.whole_frame);
+        assert_eq!((history.first_use, history.last_use), (0, 4));
+        let albedo = &plan.lifetimes[0];
+        assert_eq!((albedo.first_use, albedo.last_use, albedo.size), (0, 1, 40_000));
+        assert_eq!(plan.lifetimes[3].size, 20_000);
+
+        assert_eq!(plan.allocation_of("History").unwrap().textures, vec!["History"]);
+        assert_eq!(
+            plan.allocation_of("Albedo").unwrap().textures,
+            vec!["Albedo", "BloomHalf"]
+        );
+        assert_eq!(
+            plan.allocation_of("Normals").unwrap().textures,
+            vec!["Normals", "Bloom"]
+        );
+        assert_eq!(plan.allocation_of("Lit").unwrap().textures, vec!["Lit"]);
+
+        assert_eq!(plan.bytes_without_aliasing(), 40_000 + 80_000 * 4 + 20_000);
+        assert_eq!(plan.bytes_with_aliasing(), 40_000 + 80_000 * 3);
+        assert_eq!(plan.bytes_saved(), 100_000);
+    }
+
+    #[test]
+    fn loaded_attachments_keep_their_memory() {
+        let mut passes = vec![
+            pass("Sky", &[], &["Sky"]),
+            pass("Composite", &["Sky"], &["Backbuffer"]),
+            pass("Accumulate", &[], &["Accumulation"]),
+            pass("Final", &["Accumulation"], &["Backbuffer"]),
+        ];
+        passes[2].texture_outputs[0].clear = false;
+        let textures = vec![texture("Sky", "RGBA8", 1.0), texture("Accumulation", "RGBA8", 1.0)];
+        let graph = RenderGraph::build(&passes).unwrap();
+
+        let plan = plan_texture_aliasing(&graph, &passes, &textures, Vector2::new(10, 10));
+
+        // Accumulate blends with what the last frame left in Accumulation, so it can't share with Sky
+        let accumulation = &plan.lifetimes[1];
+        assert!(accumulation.whole_frame);
+        assert_eq!((accumulation.first_use, accumulation.last_use), (0, 3));
+        assert_eq!(plan.allocations.len(), 2);
+
+        passes[2].texture_outputs[0].clear = true;
+        let plan = plan_texture_aliasing(&graph, &passes, &textures, Vector2::new(10, 10));
+        assert_eq!(plan.allocation_of("Sky").unwrap().textures, vec!["Sky", "Accumulation"]);
+    }
+
+    #[test]
+    fn depth_textures_dont_share_with_color_textures() {
+        let mut passes = vec![
+            pass("Shadow", &[], &["ShadowColor"]),
+            pass("Blur", &["ShadowColor"], &["Blurred"]),
+            pass("Final", &["Blurred"], &["Backbuffer"]),
+        ];
+        passes[2].depth_texture = Some(TextureAttachmentInfo {
+            name: "Depth".to_owned(),
+            pixel_format: PixelFormat::Depth,
+            clear: true,
+        });
+        let textures = vec![
+            texture("ShadowColor", "RGBA8", 1.0),
+            texture("Blurred", "RGBA8", 1.0),
+            texture("Depth", "Depth", 1.0),
+        ];
+        let graph = RenderGraph::build(&passes).unwrap();
+
+        let plan = plan_texture_aliasing(&graph, &passes, &textures, Vector2::new(10, 10));
+        assert_eq!(plan.allocations.len(), 3);
+        assert!(plan.allocation_of("Depth").unwrap().depth);
+        assert_eq!(plan.bytes_saved(), 0);
+    }
+}
@@ -0,0 +1,206 @@
+//! Working out how big each of a shaderpack's textures is, in pixels
+
+use crate::shaderpack::*;
+use cgmath::Vector2;
+use failure::Fail;
+use std::collections::HashMap;
+
+/// The largest width or height that a texture may have, in pixels
+///
+/// Every GPU that Nova supports can make textures at least this big. A texture that's bigger than this is almost
+/// certainly a mistake in the shaderpack, like a screen-relative size that was meant to be absolute
+pub const MAX_TEXTURE_EXTENT: u32 = 16384;
+
+/// Errors in the sizes of a shaderpack's textures
+#[derive(Fail, Debug)]
+pub enum TextureExtentError {
+    #[fail(
+        display = "Texture {} would be {}x{} pixels, but textures must be at least 1x1",
+        texture, width, height
+    )]
+    Empty { texture: String, width: u32, height: u32 },
+
+    #[fail(
+        display = "Texture {} would be {}x{} pixels, but textures may be at most {}x{}",
+        texture, width, height, max, max
+    )]
+    TooLarge {
+        texture: String,
+        width: u32,
+        height: u32,
+        max: u32,
+    },
+}
+
+/// The size of each of a shaderpack's textures, for the current size of the screen
+#[derive(Debug, Clone)]
+pub struct TextureExtents {
+    screen_size: Vector2<u32>,
+
+    /// The size of each texture in pixels, by the name of the texture
+    extents: HashMap<String, Vector2<u32>>,
+}
+
+impl TextureExtents {
+    /// Works out the size of every texture
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for each texture that would be empty, or larger than `MAX_TEXTURE_EXTENT` in either direction
+    ///
+    /// # Parameters
+    ///
+    /// * `textures` - The textures to find the sizes of
+    /// * `screen_size` - The size of the screen, in pixels
+    pub fn resolve(
+        textures: &[TextureCreateInfo],
+        screen_size: Vector2<u32>,
+    ) -> Result<TextureExtents, Vec<TextureExtentError>> {
+        let mut errors = Vec::new();
+        let mut extents = HashMap::new();
+        for texture in textures {
+            match extent_of(texture, screen_size) {
+                Ok(extent) => {
+                    extents.insert(texture.name.clone(), extent);
+                }
+                Err(error) => errors.push(error),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(TextureExtents { screen_size, extents })
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Works out the size of every texture for a new screen size
+    ///
+    /// If any texture would have an invalid size, the sizes aren't changed
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for each texture that would be empty, or larger than `MAX_TEXTURE_EXTENT` in either direction
+    ///
+    /// # Returns
+    ///
+    /// The names of the textures whose size changed, in the order that they're listed in `textures`. These textures
+    /// need to be recreated. Textures that weren't in the shaderpack before are included too
+    ///
+    /// # Parameters
+    ///
+    /// * `textures` - The shaderpack's textures
+    /// * `screen_size` - The new size of the screen, in pixels
+    pub fn resize(
+        &mut self,
+        textures: &[TextureCreateInfo],
+        screen_size: Vector2<u32>,
+    ) -> Result<Vec<String>, Vec<TextureExtentError>> {
+        let resized = TextureExtents::resolve(textures, screen_size)?;
+        let changed = textures
+            .iter()
+            .filter(|texture| self.extents.get(&texture.name) != resized.extents.get(&texture.name))
+            .map(|texture| texture.name.clone())
+            .collect();
+
+        *self = resized;
+        Ok(changed)
+    }
+
+    /// The size of a texture in pixels, if it's one of the textures that the sizes were worked out for
+    ///
+    /// # Parameters
+    ///
+    /// * `texture` - The name of the texture
+    pub fn get(&self, texture: &str) -> Option<Vector2<u32>> {
+        self.extents.get(texture).copied()
+    }
+
+    /// The size of the screen that the sizes were worked out for
+    pub fn screen_size(&self) -> Vector2<u32> {
+        self.screen_size
+    }
+}
+
+fn extent_of(texture: &TextureCreateInfo, screen_size: Vector2<u32>) -> Result<Vector2<u32>, TextureExtentError> {
+    let extent = texture.format.size_in_pixels(screen_size);
+    if extent.x == 0 || extent.y == 0 {
+        Err(TextureExtentError::Empty {
+            texture: texture.name.clone(),
+            width: extent.x,
+            height: extent.y,
+        })
+    } else if extent.x > MAX_TEXTURE_EXTENT || extent.y > MAX_TEXTURE_EXTENT {
+        Err(TextureExtentError::TooLarge {
+            texture: texture.name.clone(),
+            width: extent.x,
+            height: extent.y,
+            max: MAX_TEXTURE_EXTENT,
+        })
+    } else {
+        Ok(extent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render_graph::*;
+    use crate::shaderpack::*;
+    use cgmath::Vector2;
+
+    fn texture(name: &str, dimension_type: &str, width: f32, height: f32) -> TextureCreateInfo {
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "format": { "dimensionType": dimension_type, "width": width, "height": height },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn reports_textures_that_change_size() {
+        let textures = vec![
+            texture("Lit", "ScreenRelative", 1.0, 1.0),
+            texture("BloomHalf", "ScreenRelative", 0.5, 0.5),
+            texture("Shadow", "Absolute", 2048.0, 2048.0),
+        ];
+        let mut extents = TextureExtents::resolve(&textures, Vector2::new(1920, 1080)).unwrap();
+        assert_eq!(extents.get("BloomHalf"), Some(Vector2::new(960, 540)));
+        assert_eq!(extents.get("Shadow"), Some(Vector2::new(2048, 2048)));
+        assert_eq!(extents.get("Missing"), None);
+
+        let changed = extents.resize(&textures, Vector2::new(1280, 720)).unwrap();
+        assert_eq!(changed, vec!["Lit", "BloomHalf"]);
+        assert_eq!(extents.get("Lit"), Some(Vector2::new(1280, 720)));
+        assert_eq!(extents.screen_size(), Vector2::new(1280, 720));
+
+        let mut with_history = textures.clone();
+        with_history.push(texture("History", "ScreenRelative", 1.0, 1.0));
+        let changed = extents.resize(&with_history, Vector2::new(1280, 720)).unwrap();
+        assert_eq!(changed, vec!["History"]);
+    }
+
+    #[test]
+    fn rejects_empty_and_huge_textures() {
+        let textures = vec![
+            texture("Lit", "ScreenRelative", 1.0, 1.0),
+            texture("Tiny", "ScreenRelative", 0.0001, 0.5),
+            texture("Huge", "Absolute", 32768.0, 16.0),
+        ];
+        let errors: Vec<_> = TextureExtents::resolve(&textures, Vector2::new(1920, 1080))
+            .unwrap_err()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "Texture Tiny would be 0x540 pixels, but textures must be at least 1x1",
+                "Texture Huge would be 32768x16 pixels, but textures may be at most 16384x16384",
+            ]
+        );
+
+        let mut extents = TextureExtents::resolve(&textures[..1], Vector2::new(1920, 1080)).unwrap();
+        assert!(extents.resize(&textures[..1], Vector2::new(0, 0)).is_err());
+        assert_eq!(extents.get("Lit"), Some(Vector2::new(1920, 1080)));
+    }
+}
@@ -162,6 +162,9 @@ pub enum ResourceState {
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum DescriptorType {
     CombinedImageSampler,
+    /// A texture that's sampled with a separate sampler
+    SampledImage,
+    Sampler,
     UniformBuffer,
     StorageBuffer,
 }
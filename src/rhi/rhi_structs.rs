@@ -74,22 +74,24 @@ pub struct DescriptorSetWrite {
     update_info: DescriptorUpdateInfo,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct ResourceBindingDescription {
     /// Descriptor set that his binding belongs to
-    set: u32,
+    pub set: u32,
 
     /// Binding of this resource binding
-    binding: u32,
+    pub binding: u32,
 
     /// Number of bindings. Useful if you have an array of descriptors
-    count: u32,
+    ///
+    /// 0 for an array whose size isn't known until runtime
+    pub count: u32,
 
     /// The type of object that will be bound
-    descriptor_type: DescriptorType,
+    pub descriptor_type: DescriptorType,
 
     /// The shader stages that need access to this binding
-    stages: ShaderStageFlags,
+    pub stages: ShaderStageFlags,
 }
 
 #[derive(Debug, Clone)]
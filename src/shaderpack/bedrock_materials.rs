@@ -0,0 +1,703 @@
+//! Importing Bedrock-style material definitions
+//!
+//! Bedrock keeps its materials in `.material` files in a pack's `materials` folder. Each file is a JSON object whose
+//! `materials` object maps material names to definitions, like this:
+//!
+//! ```json
+//! {
+//!     "materials": {
+//!         "version": "1.0.0",
+//!         "entity_static": {
+//!             "vertexShader": "shaders/entity.vertex",
+//!             "fragmentShader": "shaders/entity.fragment",
+//!             "vertexFields": [{ "field": "Position" }, { "field": "UV0" }],
+//!             "+samplerStates": [{ "samplerIndex": 0, "textureFilter": "Point" }]
+//!         },
+//!         "entity_alphatest:entity_static": {
+//!             "+defines": ["ALPHA_TEST"],
+//!             "+states": ["DisableCulling"]
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! A material named `child:parent` starts with everything its parent has. Fields that start with `+` add to one of its
+//! parent's lists, fields that start with `-` remove from one, and other fields replace the parent's value. The parent
+//! may be in any `.material` file in the pack
+
+use crate::loading::*;
+use crate::shaderpack::*;
+use failure::Fail;
+use log::warn;
+use serde::de::DeserializeOwned;
+use serde_json::Map;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// The folder that Bedrock packs keep their materials in
+const MATERIALS_FOLDER: &str = "materials";
+
+/// Errors that can happen while importing Bedrock materials
+#[derive(Fail, Debug)]
+pub enum BedrockImportError {
+    #[fail(display = "Could not read Bedrock material file {:?}", file)]
+    CouldNotRead {
+        file: PathBuf,
+        #[cause]
+        error: ResourcePackError,
+    },
+
+    #[fail(display = "{:?} isn't a valid material file: {}", file, message)]
+    InvalidJson { file: PathBuf, message: String },
+
+    #[fail(display = "Material {} is defined in both {:?} and {:?}", material, first_file, file)]
+    DuplicateMaterial {
+        material: String,
+        first_file: PathBuf,
+        file: PathBuf,
+    },
+
+    #[fail(display = "Material {} inherits from {}, which doesn't exist", material, parent)]
+    UnknownParent { material: String, parent: String },
+
+    /// Some materials inherit from each other in a loop. Each one inherits from the next, and the last one inherits
+    /// from the first
+    #[fail(display = "Materials {:?} inherit from each other in a loop", materials)]
+    InheritanceCycle { materials: Vec<String> },
+
+    #[fail(display = "Material {} has an invalid {}: {}", material, field, message)]
+    InvalidValue {
+        material: String,
+        field: String,
+        message: String,
+    },
+
+    #[fail(display = "Material {} has no vertexShader, and doesn't inherit one", material)]
+    MissingVertexShader { material: String },
+}
+
+/// The pipelines, materials and samplers made from a pack's Bedrock materials
+#[derive(Debug, Clone, Default)]
+pub struct BedrockImport {
+    /// One pipeline for each material, with the same name as the material. Inheritance has already been applied, so
+    /// none of them have a parent
+    pub pipelines: Vec<PipelineCreationInfo>,
+
+    /// One material for each Bedrock material, with a single pass named `main`
+    ///
+    /// Bedrock picks materials by name rather than with geometry filters, so these materials have no geometry filter
+    pub materials: Vec<MaterialData>,
+
+    /// The samplers that the materials' `samplerStates` describe, named `<material>_sampler<index>`
+    ///
+    /// Bedrock shaders sample `TEXTURE_<index>`, so each material binds `TEXTURE_<index>` to the sampler with that
+    /// index
+    pub samplers: Vec<SamplerCreateInfo>,
+}
+
+/// Imports every material in a Bedrock pack's `materials` folder
+///
+/// Materials are imported in order of their names. If anything is wrong with any material, returns every problem
+/// instead
+///
+/// # Parameters
+///
+/// * `pack` - The pack to import the materials of
+/// * `pass` - The name of the render pass that the pipelines belong to
+pub fn import_bedrock_materials(pack: &dyn ResourcePack, pass: &str) -> Result<BedrockImport, Vec<BedrockImportError>> {
+    let mut errors = Vec::new();
+    let definitions = read_definitions(pack, &mut errors);
+
+    let mut resolved = HashMap::new();
+    for name in definitions.keys() {
+        resolve(name, &definitions, &mut resolved, &mut Vec::new(), &mut errors);
+    }
+
+    let mut import = BedrockImport::default();
+    for name in definitions.keys() {
+        let fields = match &resolved[name] {
+            Some(fields) => fields,
+            None => continue,
+        };
+
+        let mut converter = MaterialConverter {
+            material: name,
+            errors: &mut errors,
+        };
+        if let Some((pipeline, samplers)) = converter.convert(fields, pass) {
+            let bindings = samplers
+                .iter()
+                .map(|(index, sampler)| (format!("TEXTURE_{}", index), sampler.name.clone()))
+                .collect();
+            import.materials.push(MaterialData {
+                name: name.clone(),
+                passes: vec![MaterialPass {
+                    name: "main".to_owned(),
+                    material_name: name.clone(),
+                    pipeline: pipeline.name.clone(),
+                    bindings,
+                }],
+                geometry_filter: String::new(),
+            });
+            import.pipelines.push(pipeline);
+            import.samplers.extend(samplers.into_iter().map(|(_, sampler)| sampler));
+        }
+    }
+
+    if errors.is_empty() { Ok(import) } else { Err(errors) }
+}
+
+/// A material as it's written in its file, before inheritance is applied
+struct Definition {
+    file: PathBuf,
+    parent: Option<String>,
+    fields: Map<String, Value>,
+}
+
+fn read_definitions(pack: &dyn ResourcePack, errors: &mut Vec<BedrockImportError>) -> BTreeMap<String, Definition> {
+    let mut definitions: BTreeMap<String, Definition> = BTreeMap::new();
+
+    let folder = Path::new(MATERIALS_FOLDER);
+    let files = match pack.list_directory(folder) {
+        Ok(files) => files,
+        Err(error) => {
+            errors.push(BedrockImportError::CouldNotRead {
+                file: folder.to_path_buf(),
+                error,
+            });
+            return definitions;
+        }
+    };
+
+    for file in files {
+        if file.extension().is_none_or(|extension| extension != "material") {
+            continue;
+        }
+
+        let json = match pack.read_string(&file) {
+            Ok(text) => serde_json::from_str::<Value>(&text),
+            Err(error) => {
+                errors.push(BedrockImportError::CouldNotRead { file, error });
+                continue;
+            }
+        };
+        let json = match json {
+            Ok(json) => json,
+            Err(error) => {
+                let message = error.to_string();
+                errors.push(BedrockImportError::InvalidJson { file, message });
+                continue;
+            }
+        };
+        let entries = match json.get("materials").unwrap_or(&json).as_object() {
+            Some(entries) => entries,
+            None => {
+                let message = "Materials must be in a JSON object".to_owned();
+                errors.push(BedrockImportError::InvalidJson { file, message });
+                continue;
+            }
+        };
+
+        for (key, value) in entries {
+            if key == "version" {
+                continue;
+            }
+
+            let (name, parent) = match key.find(':') {
+                Some(separator) => (&key[..separator], Some(key[separator + 1..].to_owned())),
+                None => (key.as_str(), None),
+            };
+            let fields = match value.as_object() {
+                Some(fields) => fields.clone(),
+                None => {
+                    errors.push(BedrockImportError::InvalidValue {
+                        material: name.to_owned(),
+                        field: "definition".to_owned(),
+                        message: "A material must be a JSON object".to_owned(),
+                    });
+                    continue;
+                }
+            };
+
+            if let Some(first) = definitions.get(name) {
+                errors.push(BedrockImportError::DuplicateMaterial {
+                    material: name.to_owned(),
+                    first_file: first.file.clone(),
+                    file: file.clone(),
+                });
+                continue;
+            }
+            definitions.insert(
+                name.to_owned(),
+                Definition {
+                    file: file.clone(),
+                    parent,
+                    fields,
+                },
+            );
+        }
+    }
+
+    definitions
+}
+
+/// Works out every field of a material, including the ones it inherits. Returns `None` if the material can't inherit
+/// from its parent
+fn resolve(
+    name: &str,
+    definitions: &BTreeMap<String, Definition>,
+    resolved: &mut HashMap<String, Option<Map<String, Value>>>,
+    resolving: &mut Vec<String>,
+    errors: &mut Vec<BedrockImportError>,
+) -> Option<Map<String, Value>> {
+    if let Some(fields) = resolved.get(name) {
+        return fields.clone();
+    }
+    if let Some(start) = resolving.iter().position(|material| material == name) {
+        errors.push(BedrockImportError::InheritanceCycle {
+            materials: resolving[start..].to_vec(),
+        });
+        return None;
+    }
+
+    let definition = &definitions[name];
+    let inherited = match &definition.parent {
+        None => Some(Map::new()),
+        Some(parent) if !definitions.contains_key(parent) => {
+            errors.push(BedrockImportError::UnknownParent {
+                material: name.to_owned(),
+                parent: parent.clone(),
+            });
+            None
+        }
+        Some(parent) => {
+            resolving.push(name.to_owned());
+            let inherited = resolve(parent, definitions, resolved, resolving, errors);
+            resolving.pop();
+            inherited
+        }
+    };
+
+    let fields = inherited.map(|inherited| inherit(inherited, &definition.fields));
+    resolved.insert(name.to_owned(), fields.clone());
+    fields
+}
+
+/// Applies a material's fields on top of the fields it inherits
+///
+/// Fields that replace a value are applied first, then fields that add to a list, then fields that remove from a list
+fn inherit(mut fields: Map<String, Value>, definition: &Map<String, Value>) -> Map<String, Value> {
+    for (key, value) in definition {
+        if !key.starts_with('+') && !key.starts_with('-') {
+            fields.insert(key.clone(), value.clone());
+        }
+    }
+
+    for (key, value) in definition {
+        if let Some(key) = key.strip_prefix('+') {
+            let list = fields.entry(key).or_insert_with(|| Value::Array(Vec::new()));
+            match (list.as_array_mut(), value.as_array()) {
+                (Some(list), Some(added)) => {
+                    for value in added {
+                        if !list.contains(value) {
+                            list.push(value.clone());
+                        }
+                    }
+                }
+                // Not a list, so let converting the field report it
+                _ => *list = value.clone(),
+            }
+        }
+    }
+
+    for (key, value) in definition {
+        if let Some(key) = key.strip_prefix('-') {
+            if let (Some(list), Some(removed)) = (fields.get_mut(key).and_then(Value::as_array_mut), value.as_array()) {
+                list.retain(|value| !removed.contains(value));
+            }
+        }
+    }
+
+    fields
+}
+
+const BLEND_FACTORS: &[(&str, BlendFactor)] = &[
+    ("One", BlendFactor::One),
+    ("Zero", BlendFactor::Zero),
+    ("SourceColor", BlendFactor::SrcColor),
+    ("DestColor", BlendFactor::DstColor),
+    ("OneMinusSrcColor", BlendFactor::OneMinusSrcColor),
+    ("OneMinusDestColor", BlendFactor::OneMinusDstColor),
+    ("SourceAlpha", BlendFactor::SrcAlpha),
+    ("DestAlpha", BlendFactor::DstAlpha),
+    ("OneMinusSrcAlpha", BlendFactor::OneMinusSrcAlpha),
+    ("OneMinusDestAlpha", BlendFactor::OneMinusDstAlpha),
+];
+
+const MSAA_SUPPORT: &[(&str, MSAASupport)] = &[
+    ("Both", MSAASupport::Both),
+    ("MSAA", MSAASupport::MSAA),
+    ("NonMSAA", MSAASupport::None),
+];
+
+/// Nova only draws lists of triangles and lines. Vertices laid out as strips or quads would be drawn wrong as lists, so
+/// `TriangleStrip`, `QuadList` and `LineStrip` aren't supported
+const PRIMITIVE_MODES: &[(&str, PrimitiveTopology)] = &[
+    ("TriangleList", PrimitiveTopology::Triangles),
+    ("Line", PrimitiveTopology::Lines),
+];
+
+const VERTEX_FIELDS: &[(&str, VertexField)] = &[
+    ("Position", VertexField::Position),
+    ("Color", VertexField::Color),
+    ("Normal", VertexField::Normal),
+    ("Tangent", VertexField::Tangent),
+    ("UV0", VertexField::UV0),
+    ("UV1", VertexField::UV1),
+];
+
+const TEXTURE_FILTERS: &[(&str, TextureFilter)] = &[
+    ("Point", TextureFilter::Point),
+    ("Bilinear", TextureFilter::Bilinear),
+    ("Trilinear", TextureFilter::Bilinear),
+    ("MipMapBilinear", TextureFilter::Bilinear),
+    ("TexelAA", TextureFilter::TexelAA),
+];
+
+const WRAP_MODES: &[(&str, WrapMode)] = &[("Clamp", WrapMode::Clamp), ("Repeat", WrapMode::Repeat)];
+
+/// Turns the fields of one material into a pipeline and samplers, collecting every problem with them
+struct MaterialConverter<'a> {
+    material: &'a str,
+    errors: &'a mut Vec<BedrockImportError>,
+}
+
+impl<'a> MaterialConverter<'a> {
+    /// Returns the pipeline, and the samplers with their sampler index
+    fn convert(
+        &mut self,
+        fields: &Map<String, Value>,
+        pass: &str,
+    ) -> Option<(PipelineCreationInfo, Vec<(u64, SamplerCreateInfo)>)> {
+        let error_count = self.errors.len();
+
+        let vertex_shader = self.field(fields, "vertexShader");
+        let mut pipeline = PipelineCreationInfo {
+            name: self.material.to_owned(),
+            parent: None,
+            pass: pass.to_owned(),
+            defines: self.list(fields, "defines", |converter, value| converter.parse(value, "defines")),
+            states: self.list(fields, "states", |converter, value| converter.parse(value, "states")),
+            vertex_fields: self.list(fields, "vertexFields", |converter, value| {
+                let name = value.get("field").and_then(Value::as_str).unwrap_or_default();
+                let field = converter.lookup(name, "vertexFields", VERTEX_FIELDS)?;
+                Some(VertexFieldData {
+                    semantic_name: name.to_owned(),
+                    field,
+                })
+            }),
+            front_face: self.stencil_op_state(fields, "frontFace"),
+            back_face: self.stencil_op_state(fields, "backFace"),
+            fallback: None,
+            depth_bias: self.field(fields, "depthBias"),
+            slope_scaled_depth_bias: self.field(fields, "slopeScaledDepthBias"),
+            stencil_ref: self.field(fields, "stencilRef"),
+            stencil_read_mask: self.field(fields, "stencilReadMask"),
+            stencil_write_mask: self.field(fields, "stencilWriteMask"),
+            msaa_support: self.mapped(fields, "msaaSupport", MSAA_SUPPORT),
+            primitive_mode: self.mapped(fields, "primitiveMode", PRIMITIVE_MODES),
+            src_blend_factor: self.mapped(fields, "blendSrc", BLEND_FACTORS),
+            dst_blend_factor: self.mapped(fields, "blendDst", BLEND_FACTORS),
+            alpha_src: self.mapped(fields, "alphaSrc", BLEND_FACTORS),
+            alpha_dst: self.mapped(fields, "alphaDst", BLEND_FACTORS),
+            depth_func: self.field(fields, "depthFunc"),
+            render_queue: None,
+            vertex_shader: ShaderSource::default(),
+            geometry_shader: self.field(fields, "geometryShader"),
+            tessellation_control_shader: None,
+            tessellation_evaluation_shader: None,
+            fragment_shader: self.field(fields, "fragmentShader"),
+        };
+        match vertex_shader {
+            Some(vertex_shader) => pipeline.vertex_shader = vertex_shader,
+            None => self.errors.push(BedrockImportError::MissingVertexShader {
+                material: self.material.to_owned(),
+            }),
+        }
+        if pipeline.states.contains(&RasterizerState::Blending) {
+            pipeline.render_queue = Some(RenderQueue::Transparent);
+        }
+
+        let mut samplers: BTreeMap<u64, SamplerCreateInfo> = BTreeMap::new();
+        for (index, sampler) in self.list(fields, "samplerStates", |converter, value| converter.sampler(value)) {
+            samplers.insert(index, sampler);
+        }
+
+        for key in fields.keys() {
+            if !KNOWN_FIELDS.contains(&key.as_str()) {
+                warn!("Bedrock material {} sets {}, which Nova ignores", self.material, key);
+            }
+        }
+
+        if self.errors.len() == error_count {
+            Some((pipeline, samplers.into_iter().collect()))
+        } else {
+            None
+        }
+    }
+
+    fn invalid(&mut self, field: &str, message: String) {
+        self.errors.push(BedrockImportError::InvalidValue {
+            material: self.material.to_owned(),
+            field: field.to_owned(),
+            message,
+        });
+    }
+
+    /// Parses a value with serde, for the fields whose values have the same names in Bedrock and Nova
+    fn parse<T: DeserializeOwned>(&mut self, value: &Value, field: &str) -> Option<T> {
+        match serde_json::from_value(value.clone()) {
+            Ok(value) => Some(value),
+            Err(error) => {
+                self.invalid(field, error.to_string());
+                None
+            }
+        }
+    }
+
+    fn field<T: DeserializeOwned>(&mut self, fields: &Map<String, Value>, key: &str) -> Option<T> {
+        fields.get(key).and_then(|value| self.parse(value, key))
+    }
+
+    fn lookup<T: Clone>(&mut self, name: &str, field: &str, table: &[(&str, T)]) -> Option<T> {
+        match table.iter().find(|(table_name, _)| *table_name == name) {
+            Some((_, value)) => Some(value.clone()),
+            None => {
+                self.invalid(field, format!("Nova doesn't support `{}`", name));
+                None
+            }
+        }
+    }
+
+    fn mapped<T: Clone>(&mut self, fields: &Map<String, Value>, key: &str, table: &[(&str, T)]) -> Option<T> {
+        let name: String = self.field(fields, key)?;
+        self.lookup(&name, key, table)
+    }
+
+    /// Converts each element of a list, skipping the ones that can't be converted
+    fn list<T, F>(&mut self, fields: &Map<String, Value>, key: &str, mut convert: F) -> Vec<T>
+    where
+        F: FnMut(&mut Self, &Value) -> Option<T>,
+    {
+        match fields.get(key) {
+            None => Vec::new(),
+            Some(Value::Array(values)) => values.iter().filter_map(|value| convert(self, value)).collect(),
+            Some(_) => {
+                self.invalid(key, "Expected a list".to_owned());
+                Vec::new()
+            }
+        }
+    }
+
+    fn stencil_op_state(&mut self, fields: &Map<String, Value>, key: &str) -> Option<StencilOpState> {
+        let face = fields.get(key)?;
+        Some(StencilOpState {
+            fail_op: self.field_of(face, key, "stencilFailOp").unwrap_or_default(),
+            pass_op: self.field_of(face, key, "stencilPassOp").unwrap_or_default(),
+            depth_fail_op: self.field_of(face, key, "stencilDepthFailOp").unwrap_or_default(),
+            compare_op: self.field_of(face, key, "stencilFunc").unwrap_or(CompareOp::Always),
+            compare_mask: 0,
+            write_mask: 0,
+        })
+    }
+
+    fn field_of<T: DeserializeOwned>(&mut self, object: &Value, key: &str, field: &str) -> Option<T> {
+        object
+            .get(field)
+            .and_then(|value| self.parse(value, &format!("{}.{}", key, field)))
+    }
+
+    fn sampler(&mut self, value: &Value) -> Option<(u64, SamplerCreateInfo)> {
+        let index: u64 = match value.get("samplerIndex") {
+            Some(index) => self.parse(index, "samplerStates.samplerIndex")?,
+            None => {
+                self.invalid("samplerStates", "Every sampler state needs a samplerIndex".to_owned());
+                return None;
+            }
+        };
+        let filter = match value.get("textureFilter").and_then(Value::as_str) {
+            Some(filter) => self.lookup(filter, "samplerStates.textureFilter", TEXTURE_FILTERS)?,
+            None => TextureFilter::Point,
+        };
+        let wrap_mode = match value.get("textureWrap").and_then(Value::as_str) {
+            Some(wrap_mode) => self.lookup(wrap_mode, "samplerStates.textureWrap", WRAP_MODES)?,
+            None => WrapMode::Repeat,
+        };
+
+        Some((
+            index,
+            SamplerCreateInfo {
+                name: format!("{}_sampler{}", self.material, index),
+                filter,
+                wrap_mode,
+            },
+        ))
+    }
+}
+
+/// The fields that Nova reads from a Bedrock material. Nova logs a warning for every other field
+const KNOWN_FIELDS: &[&str] = &[
+    "vertexShader",
+    "fragmentShader",
+    "geometryShader",
+    "defines",
+    "states",
+    "vertexFields",
+    "samplerStates",
+    "frontFace",
+    "backFace",
+    "depthBias",
+    "slopeScaledDepthBias",
+    "stencilRef",
+    "stencilReadMask",
+    "stencilWriteMask",
+    "msaaSupport",
+    "primitiveMode",
+    "blendSrc",
+    "blendDst",
+    "alphaSrc",
+    "alphaDst",
+    "depthFunc",
+];
+
+#[cfg(test)]
+mod tests {
+    use crate::loading::*;
+    use crate::shaderpack::*;
+
+    const COMMON: &str = r#"{
+        "materials": {
+            "version": "1.0.0",
+            "entity_static": {
+                "vertexShader": "shaders/entity.vertex",
+                "fragmentShader": "shaders/entity.fragment",
+                "vertexFields": [{ "field": "Position" }, { "field": "Normal" }, { "field": "UV0" }],
+                "defines": ["USE_OVERLAY"],
+                "states": ["DisableAlphaWrite"],
+                "+samplerStates": [{ "samplerIndex": 0, "textureFilter": "Point", "textureWrap": "Clamp" }],
+                "depthFunc": "LessEqual",
+                "msaaSupport": "Both"
+            }
+        }
+    }"#;
+
+    const ENTITY: &str = r#"{
+        "materials": {
+            "entity_alphatest:entity_static": {
+                "+defines": ["ALPHA_TEST"],
+                "+states": ["DisableCulling"],
+                "+samplerStates": [{ "samplerIndex": 1, "textureFilter": "Bilinear" }]
+            },
+            "entity_alphablend:entity_alphatest": {
+                "-defines": ["ALPHA_TEST", "USE_OVERLAY"],
+                "+states": ["Blending"],
+                "blendSrc": "SourceAlpha",
+                "blendDst": "OneMinusSrcAlpha",
+                "fragmentShader": "shaders/entity_blend.fragment"
+            }
+        }
+    }"#;
+
+    #[test]
+    fn flattens_inherited_materials() {
+        let (_dir, pack) = make_pack(&[
+            ("materials/common.material", COMMON),
+            ("materials/entity.material", ENTITY),
+            ("materials/notes.txt", "Not a material"),
+        ]);
+
+        let import = import_bedrock_materials(&pack, "Forward").unwrap();
+        let names: Vec<_> = import.pipelines.iter().map(|pipeline| pipeline.name.as_str()).collect();
+        assert_eq!(names, vec!["entity_alphablend", "entity_alphatest", "entity_static"]);
+
+        let alphatest = &import.pipelines[1];
+        assert_eq!(alphatest.parent, None);
+        assert_eq!(alphatest.pass, "Forward");
+        assert_eq!(alphatest.defines, vec!["USE_OVERLAY", "ALPHA_TEST"]);
+        assert_eq!(
+            alphatest.states,
+            vec![RasterizerState::DisableAlphaWrite, RasterizerState::DisableCulling]
+        );
+        assert_eq!(alphatest.vertex_fields.len(), 3);
+        assert_eq!(alphatest.depth_func, Some(CompareOp::LessEqual));
+        assert_eq!(alphatest.msaa_support, Some(MSAASupport::Both));
+
+        let alphablend = &import.pipelines[0];
+        assert!(alphablend.defines.is_empty());
+        assert_eq!(alphablend.src_blend_factor, Some(BlendFactor::SrcAlpha));
+        assert_eq!(alphablend.render_queue, Some(RenderQueue::Transparent));
+        assert_eq!(
+            alphablend.vertex_shader.filename.to_str().unwrap(),
+            "shaders/entity.vertex"
+        );
+        assert_eq!(
+            alphablend.fragment_shader.as_ref().unwrap().filename.to_str().unwrap(),
+            "shaders/entity_blend.fragment"
+        );
+
+        let material = &import.materials[0];
+        assert_eq!(material.passes[0].pipeline, "entity_alphablend");
+        assert_eq!(material.passes[0].bindings["TEXTURE_1"], "entity_alphablend_sampler1");
+        let samplers: Vec<_> = import.samplers.iter().map(|sampler| sampler.name.as_str()).collect();
+        assert_eq!(
+            samplers,
+            vec![
+                "entity_alphablend_sampler0",
+                "entity_alphablend_sampler1",
+                "entity_alphatest_sampler0",
+                "entity_alphatest_sampler1",
+                "entity_static_sampler0"
+            ]
+        );
+        assert_eq!(import.samplers[0].wrap_mode, WrapMode::Clamp);
+    }
+
+    #[test]
+    fn reports_every_broken_material() {
+        let (_dir, pack) = make_pack(&[(
+            "materials/broken.material",
+            r#"{
+                "materials": {
+                    "orphan:missing": {},
+                    "a:b": {},
+                    "b:a": {},
+                    "no_shader": { "states": ["Wireframe"] },
+                    "bad_field": { "vertexShader": "shaders/bad.vertex", "vertexFields": [{ "field": "BoneId0" }] },
+                    "strip": { "vertexShader": "shaders/strip.vertex", "primitiveMode": "TriangleStrip" }
+                }
+            }"#,
+        )]);
+
+        let errors: Vec<_> = import_bedrock_materials(&pack, "Forward")
+            .unwrap_err()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                r#"Materials ["a", "b"] inherit from each other in a loop"#,
+                "Material orphan inherits from missing, which doesn't exist",
+                "Material bad_field has an invalid vertexFields: Nova doesn't support `BoneId0`",
+                "Material no_shader has an invalid states: unknown variant `Wireframe`, expected one of `Blending`, \
+                 `InvertCulling`, `DisableCulling`, `DisableDepthWrite`, `DisableDepthTest`, `EnableStencilTest`, \
+                 `StencilWrite`, `DisableColorWrite`, `EnableAlphaToCoverage`, `DisableAlphaWrite`",
+                "Material no_shader has no vertexShader, and doesn't inherit one",
+                "Material strip has an invalid primitiveMode: Nova doesn't support `TriangleStrip`",
+            ]
+        );
+    }
+}
@@ -0,0 +1,83 @@
+//! The textures that Nova provides to every shaderpack
+
+use crate::render_graph::*;
+
+/// How passes may use a built-in texture
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BuiltinTextureUsage {
+    /// Passes and materials can read the texture, but nothing can write to it
+    InputOnly,
+    /// Passes can write to the texture, but nothing can read it
+    OutputOnly,
+}
+
+/// A texture that Nova provides, which shaderpacks can use without declaring it
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct BuiltinTexture {
+    pub name: &'static str,
+    pub usage: BuiltinTextureUsage,
+}
+
+impl BuiltinTexture {
+    /// Whether passes and materials can read this texture
+    pub fn can_be_read(&self) -> bool {
+        self.usage == BuiltinTextureUsage::InputOnly
+    }
+
+    /// Whether passes can write to this texture
+    pub fn can_be_written(&self) -> bool {
+        self.usage == BuiltinTextureUsage::OutputOnly
+    }
+}
+
+/// Every texture that Nova provides. See the docs of `TextureCreateInfo::name` for what's in each of them
+pub const BUILTIN_TEXTURES: &[BuiltinTexture] = &[
+    BuiltinTexture {
+        name: "ColorVirtualTexture",
+        usage: BuiltinTextureUsage::InputOnly,
+    },
+    BuiltinTexture {
+        name: "NormalVirtualTexture",
+        usage: BuiltinTextureUsage::InputOnly,
+    },
+    BuiltinTexture {
+        name: "DataVirtualTexture",
+        usage: BuiltinTextureUsage::InputOnly,
+    },
+    BuiltinTexture {
+        name: "Lightmap",
+        usage: BuiltinTextureUsage::InputOnly,
+    },
+    BuiltinTexture {
+        name: BACKBUFFER_NAME,
+        usage: BuiltinTextureUsage::OutputOnly,
+    },
+];
+
+/// Finds the built-in texture with a name, if there is one
+///
+/// # Parameters
+///
+/// * `name` - The name of the texture
+pub fn builtin_texture(name: &str) -> Option<&'static BuiltinTexture> {
+    BUILTIN_TEXTURES.iter().find(|texture| texture.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shaderpack::*;
+
+    #[test]
+    fn finds_builtin_textures_by_name() {
+        let backbuffer = builtin_texture("Backbuffer").unwrap();
+        assert!(backbuffer.can_be_written());
+        assert!(!backbuffer.can_be_read());
+
+        let lightmap = builtin_texture("Lightmap").unwrap();
+        assert!(lightmap.can_be_read());
+        assert!(!lightmap.can_be_written());
+
+        assert_eq!(builtin_texture("lightmap"), None);
+        assert_eq!(builtin_texture("Albedo"), None);
+    }
+}
@@ -0,0 +1,542 @@
+//! Geometry filters, which select the objects that a material is used for
+//!
+//! A geometry filter is an expression like `geometry_type::block AND NOT transparent`. It's made of these terms:
+//! - `geometry_type::<type>` matches objects of a type of geometry, such as `block` or `entity`
+//! - `name::<name>` matches objects with exactly that name
+//! - `name_part::<text>` matches objects whose name contains that text
+//! - `transparent` matches transparent objects
+//! - `emissive` matches objects that give off light
+//!
+//! Terms can be combined with `NOT`, `AND` and `OR`, which bind in that order from tightest to loosest. Parentheses
+//! group terms
+
+use failure::Fail;
+use std::str::FromStr;
+
+/// The kinds of geometry that Nova renders
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum GeometryType {
+    Block,
+    Entity,
+    FallingBlock,
+    Gui,
+    GuiItem,
+    GuiBackground,
+    Cloud,
+    SkyDecoration,
+    SelectionBox,
+    Glint,
+    Weather,
+    Hand,
+    FullscreenQuad,
+    Particle,
+    LitParticle,
+    Eyes,
+}
+
+const GEOMETRY_TYPE_NAMES: &[(&str, GeometryType)] = &[
+    ("block", GeometryType::Block),
+    ("entity", GeometryType::Entity),
+    ("falling_block", GeometryType::FallingBlock),
+    ("gui", GeometryType::Gui),
+    ("gui_item", GeometryType::GuiItem),
+    ("gui_background", GeometryType::GuiBackground),
+    ("cloud", GeometryType::Cloud),
+    ("sky_decoration", GeometryType::SkyDecoration),
+    ("selection_box", GeometryType::SelectionBox),
+    ("glint", GeometryType::Glint),
+    ("weather", GeometryType::Weather),
+    ("hand", GeometryType::Hand),
+    ("fullscreen_quad", GeometryType::FullscreenQuad),
+    ("particle", GeometryType::Particle),
+    ("lit_particle", GeometryType::LitParticle),
+    ("eyes", GeometryType::Eyes),
+];
+
+impl GeometryType {
+    /// The name of this geometry type in geometry filters
+    pub fn name(self) -> &'static str {
+        GEOMETRY_TYPE_NAMES
+            .iter()
+            .find(|(_, geometry_type)| *geometry_type == self)
+            .map(|(name, _)| *name)
+            .unwrap()
+    }
+
+    /// Finds a geometry type by its name in geometry filters
+    ///
+    /// # Parameters
+    ///
+    /// * `name` - The name of the geometry type, such as `block`
+    pub fn from_name(name: &str) -> Option<GeometryType> {
+        GEOMETRY_TYPE_NAMES
+            .iter()
+            .find(|(geometry_type_name, _)| *geometry_type_name == name)
+            .map(|(_, geometry_type)| *geometry_type)
+    }
+}
+
+/// Something that Nova can render, as far as geometry filters are concerned
+pub trait Renderable {
+    fn geometry_type(&self) -> GeometryType;
+
+    /// The name of the object, such as `minecraft:stone`
+    fn name(&self) -> &str;
+
+    fn is_transparent(&self) -> bool;
+
+    fn is_emissive(&self) -> bool;
+}
+
+/// A parsed geometry filter
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum GeometryFilter {
+    GeometryType(GeometryType),
+    Name(String),
+    NamePart(String),
+    Transparent,
+    Emissive,
+    Not(Box<GeometryFilter>),
+    And(Box<GeometryFilter>, Box<GeometryFilter>),
+    Or(Box<GeometryFilter>, Box<GeometryFilter>),
+}
+
+/// Problems with the text of a geometry filter
+///
+/// Positions are byte offsets into the filter
+#[derive(Fail, Debug, Clone, Eq, PartialEq)]
+pub enum GeometryFilterError {
+    #[fail(display = "Unexpected {} at position {}", token, position)]
+    UnexpectedToken { position: usize, token: String },
+
+    #[fail(display = "The filter ends at position {}, but more was expected", position)]
+    UnexpectedEnd { position: usize },
+
+    #[fail(display = "Unknown geometry type {} at position {}", name, position)]
+    UnknownGeometryType { position: usize, name: String },
+
+    #[fail(display = "Unknown term {} at position {}", term, position)]
+    UnknownTerm { position: usize, term: String },
+
+    #[fail(display = "{} at position {} needs a value after the ::", term, position)]
+    MissingValue { position: usize, term: String },
+
+    /// More than `MAX_FILTER_DEPTH` `NOT`s and parentheses are nested inside each other
+    #[fail(display = "Too many NOTs and parentheses are nested at position {}", position)]
+    TooDeep { position: usize },
+}
+
+/// How many `NOT`s and parentheses a filter may nest inside each other. Deeper filters would overflow the stack while
+/// they're parsed
+pub const MAX_FILTER_DEPTH: usize = 256;
+
+impl GeometryFilter {
+    /// Parses a geometry filter
+    ///
+    /// # Parameters
+    ///
+    /// * `filter` - The text of the filter, such as `geometry_type::block AND NOT transparent`
+    pub fn parse(filter: &str) -> Result<GeometryFilter, GeometryFilterError> {
+        let mut parser = Parser {
+            tokens: tokenize(filter),
+            next: 0,
+            end: filter.len(),
+            depth: 0,
+        };
+
+        let expression = parser.parse_or()?;
+        match parser.tokens.get(parser.next) {
+            Some(token) => Err(token.unexpected()),
+            None => Ok(expression),
+        }
+    }
+
+    /// Checks if this filter selects an object
+    ///
+    /// # Parameters
+    ///
+    /// * `renderable` - The object to check
+    pub fn matches(&self, renderable: &dyn Renderable) -> bool {
+        match self {
+            GeometryFilter::GeometryType(geometry_type) => renderable.geometry_type() == *geometry_type,
+            GeometryFilter::Name(name) => renderable.name() == name,
+            GeometryFilter::NamePart(part) => renderable.name().contains(part.as_str()),
+            GeometryFilter::Transparent => renderable.is_transparent(),
+            GeometryFilter::Emissive => renderable.is_emissive(),
+            GeometryFilter::Not(filter) => !filter.matches(renderable),
+            GeometryFilter::And(left, right) => left.matches(renderable) && right.matches(renderable),
+            GeometryFilter::Or(left, right) => left.matches(renderable) || right.matches(renderable),
+        }
+    }
+}
+
+impl FromStr for GeometryFilter {
+    type Err = GeometryFilterError;
+
+    fn from_str(filter: &str) -> Result<GeometryFilter, GeometryFilterError> {
+        GeometryFilter::parse(filter)
+    }
+}
+
+struct Token<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl<'a> Token<'a> {
+    fn unexpected(&self) -> GeometryFilterError {
+        GeometryFilterError::UnexpectedToken {
+            position: self.position,
+            token: self.text.to_owned(),
+        }
+    }
+}
+
+/// Splits a filter into parentheses and words
+fn tokenize(filter: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut word_start = None;
+
+    for (position, character) in filter.char_indices() {
+        let is_separator = character.is_whitespace() || character == '(' || character == ')';
+        if is_separator {
+            if let Some(start) = word_start.take() {
+                tokens.push(Token {
+                    text: &filter[start..position],
+                    position: start,
+                });
+            }
+            if !character.is_whitespace() {
+                tokens.push(Token {
+                    text: &filter[position..position + 1],
+                    position,
+                });
+            }
+        } else if word_start.is_none() {
+            word_start = Some(position);
+        }
+    }
+    if let Some(start) = word_start {
+        tokens.push(Token {
+            text: &filter[start..],
+            position: start,
+        });
+    }
+
+    tokens
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    next: usize,
+
+    /// The length of the filter, where an unexpected end of the filter is reported
+    end: usize,
+
+    /// How many `NOT`s and parentheses the next token is inside of
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.next).map(|token| token.text)
+    }
+
+    fn take(&mut self) -> Result<&Token<'a>, GeometryFilterError> {
+        let token = self
+            .tokens
+            .get(self.next)
+            .ok_or(GeometryFilterError::UnexpectedEnd { position: self.end })?;
+        self.next += 1;
+        Ok(token)
+    }
+
+    /// Parses something inside a `NOT` or parentheses, unless that would nest them too deeply
+    ///
+    /// # Parameters
+    ///
+    /// * `position` - The position of the `NOT` or opening parenthesis
+    /// * `parse` - Parses what's inside
+    fn nested(
+        &mut self,
+        position: usize,
+        parse: impl FnOnce(&mut Self) -> Result<GeometryFilter, GeometryFilterError>,
+    ) -> Result<GeometryFilter, GeometryFilterError> {
+        if self.depth == MAX_FILTER_DEPTH {
+            return Err(GeometryFilterError::TooDeep { position });
+        }
+
+        self.depth += 1;
+        let filter = parse(self);
+        self.depth -= 1;
+        filter
+    }
+
+    fn parse_or(&mut self) -> Result<GeometryFilter, GeometryFilterError> {
+        let mut filter = self.parse_and()?;
+        while self.peek() == Some("OR") {
+            self.next += 1;
+            filter = GeometryFilter::Or(Box::new(filter), Box::new(self.parse_and()?));
+        }
+
+        Ok(filter)
+    }
+
+    fn parse_and(&mut self) -> Result<GeometryFilter, GeometryFilterError> {
+        let mut filter = self.parse_not()?;
+        while self.peek() == Some("AND") {
+            self.next += 1;
+            filter = GeometryFilter::And(Box::new(filter), Box::new(self.parse_not()?));
+        }
+
+        Ok(filter)
+    }
+
+    fn parse_not(&mut self) -> Result<GeometryFilter, GeometryFilterError> {
+        if self.peek() == Some("NOT") {
+            let position = self.take()?.position;
+            let filter = self.nested(position, Parser::parse_not)?;
+            return Ok(GeometryFilter::Not(Box::new(filter)));
+        }
+
+        self.parse_term()
+    }
+
+    fn parse_term(&mut self) -> Result<GeometryFilter, GeometryFilterError> {
+        let token = self.take()?;
+        let (text, position) = (token.text, token.position);
+
+        match text {
+            "(" => {
+                let filter = self.nested(position, Parser::parse_or)?;
+                let closing = self.take()?;
+                if closing.text == ")" {
+                    Ok(filter)
+                } else {
+                    Err(closing.unexpected())
+                }
+            }
+            ")" | "AND" | "OR" => Err(token.unexpected()),
+            "transparent" => Ok(GeometryFilter::Transparent),
+            "emissive" => Ok(GeometryFilter::Emissive),
+            _ => parse_valued_term(text, position),
+        }
+    }
+}
+
+fn parse_valued_term(text: &str, position: usize) -> Result<GeometryFilter, GeometryFilterError> {
+    let separator = match text.find("::") {
+        Some(separator) => separator,
+        None => {
+            return Err(GeometryFilterError::UnknownTerm {
+                position,
+                term: text.to_owned(),
+            });
+        }
+    };
+
+    let (term, value) = (&text[..separator], &text[separator + 2..]);
+    if value.is_empty() {
+        return Err(GeometryFilterError::MissingValue {
+            position,
+            term: term.to_owned(),
+        });
+    }
+
+    match term {
+        "geometry_type" => GeometryType::from_name(value).map(GeometryFilter::GeometryType).ok_or(
+            GeometryFilterError::UnknownGeometryType {
+                position: position + separator + 2,
+                name: value.to_owned(),
+            },
+        ),
+        "name" => Ok(GeometryFilter::Name(value.to_owned())),
+        "name_part" => Ok(GeometryFilter::NamePart(value.to_owned())),
+        _ => Err(GeometryFilterError::UnknownTerm {
+            position,
+            term: term.to_owned(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shaderpack::*;
+
+    struct TestRenderable {
+        geometry_type: GeometryType,
+        name: &'static str,
+        transparent: bool,
+        emissive: bool,
+    }
+
+    impl Renderable for TestRenderable {
+        fn geometry_type(&self) -> GeometryType {
+            self.geometry_type
+        }
+
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn is_transparent(&self) -> bool {
+            self.transparent
+        }
+
+        fn is_emissive(&self) -> bool {
+            self.emissive
+        }
+    }
+
+    const STONE: TestRenderable = TestRenderable {
+        geometry_type: GeometryType::Block,
+        name: "minecraft:stone",
+        transparent: false,
+        emissive: false,
+    };
+
+    const STAINED_GLASS: TestRenderable = TestRenderable {
+        geometry_type: GeometryType::Block,
+        name: "minecraft:red_stained_glass",
+        transparent: true,
+        emissive: false,
+    };
+
+    const BLAZE: TestRenderable = TestRenderable {
+        geometry_type: GeometryType::Entity,
+        name: "minecraft:blaze",
+        transparent: false,
+        emissive: true,
+    };
+
+    fn matching(filter: &str) -> Vec<&'static str> {
+        let filter = GeometryFilter::parse(filter).unwrap();
+        [STONE, STAINED_GLASS, BLAZE]
+            .iter()
+            .filter(|renderable| filter.matches(*renderable))
+            .map(|renderable| renderable.name)
+            .collect()
+    }
+
+    #[test]
+    fn evaluates_filters() {
+        assert_eq!(
+            matching("geometry_type::block"),
+            vec!["minecraft:stone", "minecraft:red_stained_glass"]
+        );
+        assert_eq!(
+            matching("geometry_type::block AND NOT transparent"),
+            vec!["minecraft:stone"]
+        );
+        assert_eq!(matching("name::minecraft:blaze"), vec!["minecraft:blaze"]);
+        assert_eq!(
+            matching("name_part::glass OR emissive"),
+            vec!["minecraft:red_stained_glass", "minecraft:blaze"]
+        );
+        assert_eq!(
+            matching("NOT (geometry_type::entity OR transparent)"),
+            vec!["minecraft:stone"]
+        );
+        assert_eq!(
+            matching("geometry_type::entity OR geometry_type::block AND transparent"),
+            vec!["minecraft:red_stained_glass", "minecraft:blaze"]
+        );
+        assert_eq!(
+            matching("(geometry_type::entity OR geometry_type::block) AND NOT NOT transparent"),
+            vec!["minecraft:red_stained_glass"]
+        );
+    }
+
+    #[test]
+    fn parses_into_a_tree() {
+        assert_eq!(
+            "geometry_type::gui_item AND (emissive)".parse(),
+            Ok(GeometryFilter::And(
+                Box::new(GeometryFilter::GeometryType(GeometryType::GuiItem)),
+                Box::new(GeometryFilter::Emissive)
+            ))
+        );
+        assert_eq!(GeometryType::from_name("lit_particle"), Some(GeometryType::LitParticle));
+        assert_eq!(GeometryType::FallingBlock.name(), "falling_block");
+    }
+
+    #[test]
+    fn reports_where_errors_are() {
+        let error = |filter: &str| GeometryFilter::parse(filter).unwrap_err();
+
+        assert_eq!(
+            error("geometry_type::boat"),
+            GeometryFilterError::UnknownGeometryType {
+                position: 15,
+                name: "boat".to_owned()
+            }
+        );
+        assert_eq!(
+            error("transparent AND"),
+            GeometryFilterError::UnexpectedEnd { position: 15 }
+        );
+        assert_eq!(
+            error("(emissive transparent"),
+            GeometryFilterError::UnexpectedToken {
+                position: 10,
+                token: "transparent".to_owned()
+            }
+        );
+        assert_eq!(
+            error("emissive)"),
+            GeometryFilterError::UnexpectedToken {
+                position: 8,
+                token: ")".to_owned()
+            }
+        );
+        assert_eq!(
+            error("shiny"),
+            GeometryFilterError::UnknownTerm {
+                position: 0,
+                term: "shiny".to_owned()
+            }
+        );
+        assert_eq!(
+            error("emissive AND name::"),
+            GeometryFilterError::MissingValue {
+                position: 13,
+                term: "name".to_owned()
+            }
+        );
+        assert_eq!(error(""), GeometryFilterError::UnexpectedEnd { position: 0 });
+    }
+
+    #[test]
+    fn limits_how_deeply_filters_nest() {
+        let nots = |count: usize| format!("{}emissive", "NOT ".repeat(count));
+        assert!(GeometryFilter::parse(&nots(MAX_FILTER_DEPTH)).is_ok());
+        assert_eq!(
+            GeometryFilter::parse(&nots(MAX_FILTER_DEPTH + 1)),
+            Err(GeometryFilterError::TooDeep {
+                position: MAX_FILTER_DEPTH * 4
+            })
+        );
+        assert_eq!(
+            GeometryFilter::parse(&nots(100_000)),
+            Err(GeometryFilterError::TooDeep {
+                position: MAX_FILTER_DEPTH * 4
+            })
+        );
+
+        let parentheses = |count: usize| format!("{}emissive{}", "(".repeat(count), ")".repeat(count));
+        assert!(GeometryFilter::parse(&parentheses(MAX_FILTER_DEPTH)).is_ok());
+        assert_eq!(
+            GeometryFilter::parse(&parentheses(100_000)),
+            Err(GeometryFilterError::TooDeep {
+                position: MAX_FILTER_DEPTH
+            })
+        );
+
+        assert_eq!(
+            GeometryFilter::parse(&format!("{}(transparent)", "NOT ".repeat(MAX_FILTER_DEPTH))),
+            Err(GeometryFilterError::TooDeep {
+                position: MAX_FILTER_DEPTH * 4
+            })
+        );
+    }
+}
@@ -0,0 +1,394 @@
+//! Working out which material passes draw each object
+
+use crate::shaderpack::*;
+use failure::Fail;
+use log::warn;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+
+/// Identifies an object that's been added to a `MaterialIndex`
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct RenderableId(pub u64);
+
+/// Identifies a pass of a material
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct MaterialPassId {
+    /// The name of the material
+    pub material: String,
+
+    /// The name of the pass in the material
+    pub pass: String,
+}
+
+/// The objects drawn by each material pass that uses a pipeline
+pub type PipelineGroup = BTreeMap<MaterialPassId, BTreeSet<RenderableId>>;
+
+/// The objects drawn in a render pass, grouped by the pipeline that draws them
+pub type PassGroup = BTreeMap<String, PipelineGroup>;
+
+/// Problems with a shaderpack's materials that stop them from being indexed
+#[derive(Fail, Debug)]
+pub enum MaterialIndexError {
+    #[fail(display = "Material {} has an invalid geometry filter: {}", material, error)]
+    InvalidGeometryFilter {
+        material: String,
+        #[cause]
+        error: GeometryFilterError,
+    },
+
+    #[fail(
+        display = "Pass {} of material {} uses pipeline {}, which doesn't exist",
+        pass, material, pipeline
+    )]
+    UnknownPipeline {
+        material: String,
+        pass: String,
+        pipeline: String,
+    },
+}
+
+struct IndexedMaterialPass {
+    id: MaterialPassId,
+    render_pass: String,
+    pipeline: String,
+}
+
+struct IndexedMaterial {
+    /// The material's geometry filter. Materials without a filter don't draw anything
+    filter: Option<GeometryFilter>,
+    passes: Vec<IndexedMaterialPass>,
+}
+
+/// Keeps track of which material passes draw each object, grouped the way Nova draws them
+///
+/// Nova draws one render pass at a time. Within a render pass, it binds each pipeline once and then draws everything
+/// that uses the pipeline. The index groups objects the same way: by render pass, then pipeline, then material pass
+///
+/// Objects are matched against each material's geometry filter when they're added, so drawing doesn't need to evaluate
+/// any filters. Every material whose filter matches an object draws it
+pub struct MaterialIndex {
+    materials: Vec<IndexedMaterial>,
+    passes: BTreeMap<String, PassGroup>,
+
+    /// The material passes that each object is drawn by, as indices into `materials` and their `passes`
+    renderables: HashMap<RenderableId, Vec<(usize, usize)>>,
+}
+
+impl MaterialIndex {
+    /// Creates an index with no objects in it for a shaderpack's materials
+    ///
+    /// Materials without a geometry filter can't draw anything, so this logs a warning for each of them
+    ///
+    /// # Parameters
+    ///
+    /// * `data` - The shaderpack to index the materials of
+    pub fn new(data: &ShaderpackData) -> Result<MaterialIndex, MaterialIndexError> {
+        let pipeline_passes: HashMap<&str, &str> = data
+            .pipelines
+            .iter()
+            .map(|pipeline| (pipeline.name.as_str(), pipeline.pass.as_str()))
+            .collect();
+
+        let mut materials = Vec::new();
+        let mut passes: BTreeMap<String, PassGroup> = BTreeMap::new();
+        for material in &data.materials {
+            let filter = if material.geometry_filter.trim().is_empty() {
+                warn!(
+                    "Material {} doesn't have a geometry filter, so it won't draw anything",
+                    material.name
+                );
+                None
+            } else {
+                let filter = GeometryFilter::parse(&material.geometry_filter).map_err(|error| {
+                    MaterialIndexError::InvalidGeometryFilter {
+                        material: material.name.clone(),
+                        error,
+                    }
+                })?;
+                Some(filter)
+            };
+
+            let mut material_passes = Vec::new();
+            for pass in &material.passes {
+                let render_pass =
+                    pipeline_passes
+                        .get(pass.pipeline.as_str())
+                        .ok_or_else(|| MaterialIndexError::UnknownPipeline {
+                            material: material.name.clone(),
+                            pass: pass.name.clone(),
+                            pipeline: pass.pipeline.clone(),
+                        })?;
+                let id = MaterialPassId {
+                    material: material.name.clone(),
+                    pass: pass.name.clone(),
+                };
+
+                passes
+                    .entry(render_pass.to_string())
+                    .or_default()
+                    .entry(pass.pipeline.clone())
+                    .or_default()
+                    .insert(id.clone(), BTreeSet::new());
+                material_passes.push(IndexedMaterialPass {
+                    id,
+                    render_pass: render_pass.to_string(),
+                    pipeline: pass.pipeline.clone(),
+                });
+            }
+
+            materials.push(IndexedMaterial {
+                filter,
+                passes: material_passes,
+            });
+        }
+
+        Ok(MaterialIndex {
+            materials,
+            passes,
+            renderables: HashMap::new(),
+        })
+    }
+
+    /// Adds an object to the index, replacing it if it's already in the index
+    ///
+    /// Returns how many material passes draw the object
+    ///
+    /// # Parameters
+    ///
+    /// * `id` - The ID of the object. Used to remove the object later
+    /// * `renderable` - The object to add
+    pub fn add(&mut self, id: RenderableId, renderable: &dyn Renderable) -> usize {
+        self.remove(id);
+
+        let mut memberships = Vec::new();
+        for (material_index, material) in self.materials.iter().enumerate() {
+            let matches = material
+                .filter
+                .as_ref()
+                .is_some_and(|filter| filter.matches(renderable));
+            if !matches {
+                continue;
+            }
+
+            for (pass_index, pass) in material.passes.iter().enumerate() {
+                self.passes
+                    .get_mut(&pass.render_pass)
+                    .and_then(|pipelines| pipelines.get_mut(&pass.pipeline))
+                    .and_then(|material_passes| material_passes.get_mut(&pass.id))
+                    .unwrap()
+                    .insert(id);
+                memberships.push((material_index, pass_index));
+            }
+        }
+
+        let count = memberships.len();
+        if count > 0 {
+            self.renderables.insert(id, memberships);
+        }
+        count
+    }
+
+    /// Removes an object from the index
+    ///
+    /// Returns whether the object was in the index
+    ///
+    /// # Parameters
+    ///
+    /// * `id` - The ID that the object was added with
+    pub fn remove(&mut self, id: RenderableId) -> bool {
+        let memberships = match self.renderables.remove(&id) {
+            Some(memberships) => memberships,
+            None => return false,
+        };
+
+        for (material_index, pass_index) in memberships {
+            let pass = &self.materials[material_index].passes[pass_index];
+            if let Some(renderables) = self
+                .passes
+                .get_mut(&pass.render_pass)
+                .and_then(|pipelines| pipelines.get_mut(&pass.pipeline))
+                .and_then(|material_passes| material_passes.get_mut(&pass.id))
+            {
+                renderables.remove(&id);
+            }
+        }
+
+        true
+    }
+
+    /// The objects drawn in a render pass, grouped by pipeline and then by material pass
+    ///
+    /// # Parameters
+    ///
+    /// * `render_pass` - The name of the render pass
+    pub fn pass(&self, render_pass: &str) -> Option<&PassGroup> {
+        self.passes.get(render_pass)
+    }
+
+    /// The material passes that draw an object
+    ///
+    /// # Parameters
+    ///
+    /// * `id` - The ID that the object was added with
+    pub fn material_passes_of(&self, id: RenderableId) -> Vec<&MaterialPassId> {
+        self.renderables
+            .get(&id)
+            .map(|memberships| {
+                memberships
+                    .iter()
+                    .map(|(material_index, pass_index)| &self.materials[*material_index].passes[*pass_index].id)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// How many objects are drawn by at least one material pass
+    pub fn len(&self) -> usize {
+        self.renderables.len()
+    }
+
+    /// Whether no objects are drawn
+    pub fn is_empty(&self) -> bool {
+        self.renderables.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shaderpack::*;
+    use serde_json::json;
+
+    struct Object(GeometryType, &'static str, bool);
+
+    impl Renderable for Object {
+        fn geometry_type(&self) -> GeometryType {
+            self.0
+        }
+
+        fn name(&self) -> &str {
+            self.1
+        }
+
+        fn is_transparent(&self) -> bool {
+            self.2
+        }
+
+        fn is_emissive(&self) -> bool {
+            false
+        }
+    }
+
+    fn shaderpack() -> ShaderpackData {
+        let pipelines = json!([
+            { "name": "shadow", "pass": "Shadow", "vertexShader": "shadow.vert" },
+            { "name": "opaque", "pass": "Forward", "vertexShader": "opaque.vert" },
+            { "name": "translucent", "pass": "Forward", "vertexShader": "translucent.vert" }
+        ]);
+        let materials = json!([
+            {
+                "name": "terrain",
+                "filter": "geometry_type::block AND NOT transparent",
+                "passes": [{ "name": "shadow", "pipeline": "shadow" }, { "name": "main", "pipeline": "opaque" }]
+            },
+            {
+                "name": "water",
+                "filter": "geometry_type::block AND transparent",
+                "passes": [{ "name": "main", "pipeline": "translucent" }]
+            },
+            { "name": "unfiltered", "passes": [{ "name": "main", "pipeline": "opaque" }] }
+        ]);
+
+        ShaderpackData {
+            pipelines: serde_json::from_value(pipelines).unwrap(),
+            materials: serde_json::from_value(materials).unwrap(),
+            ..ShaderpackData::default()
+        }
+    }
+
+    fn ids(group: &PipelineGroup, material: &str, pass: &str) -> Vec<u64> {
+        let id = MaterialPassId {
+            material: material.to_owned(),
+            pass: pass.to_owned(),
+        };
+        group[&id].iter().map(|id| id.0).collect()
+    }
+
+    #[test]
+    fn groups_renderables_by_pass_and_pipeline() {
+        let mut index = MaterialIndex::new(&shaderpack()).unwrap();
+
+        assert_eq!(
+            index.add(RenderableId(1), &Object(GeometryType::Block, "stone", false)),
+            2
+        );
+        assert_eq!(
+            index.add(RenderableId(2), &Object(GeometryType::Block, "water", true)),
+            1
+        );
+        assert_eq!(
+            index.add(RenderableId(3), &Object(GeometryType::Block, "dirt", false)),
+            2
+        );
+        assert_eq!(
+            index.add(RenderableId(4), &Object(GeometryType::Entity, "pig", false)),
+            0
+        );
+        assert_eq!(index.len(), 3);
+
+        let forward = index.pass("Forward").unwrap();
+        assert_eq!(forward.keys().collect::<Vec<_>>(), vec!["opaque", "translucent"]);
+        assert_eq!(ids(&forward["opaque"], "terrain", "main"), vec![1, 3]);
+        assert!(ids(&forward["opaque"], "unfiltered", "main").is_empty());
+        assert_eq!(ids(&forward["translucent"], "water", "main"), vec![2]);
+        assert_eq!(
+            ids(&index.pass("Shadow").unwrap()["shadow"], "terrain", "shadow"),
+            vec![1, 3]
+        );
+
+        let passes: Vec<_> = index
+            .material_passes_of(RenderableId(1))
+            .iter()
+            .map(|id| format!("{}.{}", id.material, id.pass))
+            .collect();
+        assert_eq!(passes, vec!["terrain.shadow", "terrain.main"]);
+    }
+
+    #[test]
+    fn updates_incrementally() {
+        let mut index = MaterialIndex::new(&shaderpack()).unwrap();
+        index.add(RenderableId(1), &Object(GeometryType::Block, "ice", false));
+        index.add(RenderableId(2), &Object(GeometryType::Block, "stone", false));
+
+        // Re-adding an object moves it to the materials that match it now
+        index.add(RenderableId(1), &Object(GeometryType::Block, "ice", true));
+        let forward = index.pass("Forward").unwrap();
+        assert_eq!(ids(&forward["opaque"], "terrain", "main"), vec![2]);
+        assert_eq!(ids(&forward["translucent"], "water", "main"), vec![1]);
+
+        assert!(index.remove(RenderableId(2)));
+        assert!(!index.remove(RenderableId(2)));
+        assert!(ids(&index.pass("Shadow").unwrap()["shadow"], "terrain", "shadow").is_empty());
+        assert_eq!(index.len(), 1);
+        assert!(index.material_passes_of(RenderableId(2)).is_empty());
+    }
+
+    #[test]
+    fn reports_broken_materials() {
+        let mut data = shaderpack();
+        data.materials[0].geometry_filter = "geometry_type::boat".to_owned();
+        match MaterialIndex::new(&data) {
+            Err(MaterialIndexError::InvalidGeometryFilter { material, .. }) => assert_eq!(material, "terrain"),
+            Err(error) => panic!("Expected InvalidGeometryFilter, got {:?}", error),
+            Ok(_) => panic!("Expected InvalidGeometryFilter"),
+        }
+
+        let mut data = shaderpack();
+        data.materials[1].passes[0].pipeline = "missing".to_owned();
+        match MaterialIndex::new(&data) {
+            Err(MaterialIndexError::UnknownPipeline { pipeline, .. }) => assert_eq!(pipeline, "missing"),
+            Err(error) => panic!("Expected UnknownPipeline, got {:?}", error),
+            Ok(_) => panic!("Expected UnknownPipeline"),
+        }
+    }
+}
@@ -1,5 +1,18 @@
 //! Data and utilities for working with shaderpacks
 
+mod bedrock_materials;
+mod builtin_resources;
+mod geometry_filter;
+mod material_index;
+mod optifine;
+mod pipeline_inheritance;
 mod shaderpack_data;
+mod shaderpack_loading;
 
+pub use bedrock_materials::*;
+pub use builtin_resources::*;
+pub use geometry_filter::*;
+pub use material_index::*;
+pub use optifine::*;
 pub use shaderpack_data::*;
+pub use shaderpack_loading::*;
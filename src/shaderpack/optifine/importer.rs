@@ -0,0 +1,1025 @@
+//! Translating Optifine shaderpacks into Nova shaderpacks
+
+use crate::loading::*;
+use crate::render_graph::*;
+use crate::shaderpack::optifine::program_source::*;
+use crate::shaderpack::optifine::programs::*;
+use crate::shaderpack::*;
+use failure::Fail;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// The folder that Optifine shaderpacks keep their programs in
+const SHADERS_FOLDER: &str = "shaders";
+
+/// The size of the shadow map if a shaderpack doesn't set `shadowMapResolution`
+const DEFAULT_SHADOW_MAP_RESOLUTION: f32 = 2048.0;
+
+/// The depth buffer that gbuffers programs draw to
+const DEPTH_BUFFER: &str = "depthtex0";
+
+/// The depth buffer that the shadow program draws to
+const SHADOW_DEPTH_BUFFER: &str = "shadowtex0";
+
+/// The blend mode that translucent geometry uses unless `shaders.properties` sets another one
+const DEFAULT_TRANSLUCENT_BLEND: [BlendFactor; 4] = [
+    BlendFactor::SrcAlpha,
+    BlendFactor::OneMinusSrcAlpha,
+    BlendFactor::One,
+    BlendFactor::OneMinusSrcAlpha,
+];
+
+/// Errors that stop an Optifine shaderpack from being imported at all
+#[derive(Fail, Debug)]
+pub enum OptifineImportError {
+    #[fail(display = "Could not read Optifine shaderpack file {:?}", file)]
+    CouldNotRead {
+        file: PathBuf,
+        #[cause]
+        error: ResourcePackError,
+    },
+
+    #[fail(display = "The pack has no shaders folder, so it isn't an Optifine shaderpack")]
+    NotAnOptifinePack,
+}
+
+/// Something in an Optifine shaderpack that Nova couldn't import
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct UnmappedFeature {
+    /// The file that the feature is in, relative to the root of the pack
+    pub file: PathBuf,
+
+    /// The line that the feature is on, starting at 1. `None` if the feature isn't on one line
+    pub line: Option<usize>,
+
+    /// What couldn't be imported, and why
+    pub reason: String,
+}
+
+impl fmt::Display for UnmappedFeature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.file.display(), line, self.reason),
+            None => write!(f, "{}: {}", self.file.display(), self.reason),
+        }
+    }
+}
+
+/// Everything that didn't translate directly while importing an Optifine shaderpack
+#[derive(Debug, Clone, Default)]
+pub struct OptifineImportReport {
+    /// Programs that the shaderpack doesn't have, and the program that was used in their place like Optifine would, as
+    /// (missing program, program used)
+    pub fallbacks: Vec<(String, String)>,
+
+    /// Everything that Nova couldn't import. The imported shaderpack may look different to how it looks in Optifine
+    /// because of these
+    pub unmapped: Vec<UnmappedFeature>,
+}
+
+/// An Optifine shaderpack, translated into a Nova shaderpack
+#[derive(Debug, Clone)]
+pub struct OptifineImport {
+    /// The shaderpack, with the options at their default values
+    pub data: ShaderpackData,
+
+    /// The options that the shaderpack lets players change. Use `ShaderOptions::apply_to` to change the defines in
+    /// `data` after changing an option
+    pub options: ShaderOptions,
+    pub report: OptifineImportReport,
+}
+
+/// Imports an Optifine shaderpack, such as SEUS, Molly or Continuum
+///
+/// Each program that the shaderpack has becomes a pass with one pipeline, and a material that selects the geometry that
+/// Optifine draws with that program. Passes run in Optifine's order: `shadow`, the gbuffers programs for opaque
+/// geometry, `deferred` to `deferred15`, the gbuffers programs for translucent geometry, `composite` to `composite15`,
+/// and `final`. When a gbuffers program is missing, its geometry is drawn with the program that Optifine falls back
+/// to
+///
+/// Render targets keep their Optifine names, like `colortex3` and `shadowtex0`, and samplers like `texture` and
+/// `lightmap` are bound to the textures that Nova provides. `depthtex1`, `depthtex2` and `shadowtex1` read the depth
+/// buffer that Optifine copies them from, since Nova doesn't copy depth buffers. Everything that can't be translated is
+/// listed in the report instead of stopping the import
+///
+/// # Parameters
+///
+/// * `pack` - The pack to import
+pub fn import_optifine_shaderpack(pack: &dyn ResourcePack) -> Result<OptifineImport, OptifineImportError> {
+    if !pack.exists(Path::new(SHADERS_FOLDER)) {
+        return Err(OptifineImportError::NotAnOptifinePack);
+    }
+
+    let mut importer = OptifineImporter {
+        pack,
+        report: OptifineImportReport::default(),
+        properties: ShaderProperties::default(),
+        programs: BTreeMap::new(),
+        disabled_programs: HashSet::new(),
+        blend_modes: HashMap::new(),
+        buffer_sizes: HashMap::new(),
+        formats: HashMap::new(),
+        shadow_map_resolution: DEFAULT_SHADOW_MAP_RESOLUTION,
+        data: ShaderpackData::default(),
+        cleared_targets: HashSet::new(),
+        used_targets: Vec::new(),
+    };
+
+    importer.read_properties()?;
+    importer.read_programs()?;
+    importer.read_constants();
+    importer.add_passes();
+    importer.add_textures();
+    let options = importer.read_options();
+
+    Ok(OptifineImport {
+        data: importer.data,
+        options,
+        report: importer.report,
+    })
+}
+
+/// The shaders of an Optifine program
+struct Program {
+    vertex_shader: PathBuf,
+    fragment_shader: PathBuf,
+    geometry_shader: Option<PathBuf>,
+
+    /// The text of every file in each of the program's shaders, including the files that they include
+    vertex_files: Vec<(PathBuf, String)>,
+    fragment_files: Vec<(PathBuf, String)>,
+    geometry_files: Vec<(PathBuf, String)>,
+}
+
+impl Program {
+    fn files(&self) -> impl Iterator<Item = &(PathBuf, String)> {
+        self.vertex_files
+            .iter()
+            .chain(&self.fragment_files)
+            .chain(&self.geometry_files)
+    }
+}
+
+/// The files that make up a program, before they're read
+#[derive(Default)]
+struct ProgramFiles {
+    vertex_shader: Option<PathBuf>,
+    fragment_shader: Option<PathBuf>,
+    geometry_shader: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum PassKind {
+    Shadow,
+    Gbuffers { translucent: bool },
+    Fullscreen,
+    Final,
+}
+
+struct OptifineImporter<'a> {
+    pack: &'a dyn ResourcePack,
+    report: OptifineImportReport,
+    properties: ShaderProperties,
+    programs: BTreeMap<String, Program>,
+    disabled_programs: HashSet<String>,
+
+    /// The blend modes that `shaders.properties` sets for each program. `None` turns blending off
+    blend_modes: HashMap<String, Option<[BlendFactor; 4]>>,
+
+    /// The sizes that `shaders.properties` sets for render targets, as (dimension type, width, height)
+    buffer_sizes: HashMap<String, (TextureDimensionType, f32, f32)>,
+    formats: HashMap<String, PixelFormat>,
+    shadow_map_resolution: f32,
+    data: ShaderpackData,
+
+    /// Render targets that an earlier pass has already cleared
+    cleared_targets: HashSet<String>,
+
+    /// Every render target that a pass uses, in the order that they're first used
+    used_targets: Vec<String>,
+}
+
+impl<'a> OptifineImporter<'a> {
+    fn unmapped(&mut self, file: &Path, line: Option<usize>, reason: String) {
+        let feature = UnmappedFeature {
+            file: file.to_path_buf(),
+            line,
+            reason,
+        };
+        if !self.report.unmapped.contains(&feature) {
+            self.report.unmapped.push(feature);
+        }
+    }
+
+    fn read_string(&self, file: &Path) -> Result<String, OptifineImportError> {
+        self.pack
+            .read_string(file)
+            .map_err(|error| OptifineImportError::CouldNotRead {
+                file: file.to_path_buf(),
+                error,
+            })
+    }
+
+    fn read_properties(&mut self) -> Result<(), OptifineImportError> {
+        let file = Path::new(SHADERS_FOLDER).join("shaders.properties");
+        if !self.pack.exists(&file) {
+            return Ok(());
+        }
+        let properties = ShaderProperties::parse(&self.read_string(&file)?);
+
+        for (line, directive) in &properties.directives {
+            if !directive.starts_with("#else") && !directive.starts_with("#endif") {
+                let reason = format!(
+                    "Nova doesn't evaluate `{}`, so every property around it was read",
+                    directive
+                );
+                self.unmapped(&file, Some(*line), reason);
+            }
+        }
+
+        for property in &properties.properties {
+            let key = property.key.as_str();
+            let value = property.value.as_str();
+            let line = Some(property.line);
+
+            if let Some(program) = key
+                .strip_prefix("program.")
+                .and_then(|key| key.strip_suffix(".enabled"))
+            {
+                match value {
+                    "true" => {
+                        self.disabled_programs.remove(program);
+                    }
+                    "false" => {
+                        self.disabled_programs.insert(program.to_owned());
+                    }
+                    _ => self.unmapped(
+                        &file,
+                        line,
+                        format!(
+                            "Program {} is enabled by `{}`, which Nova can't evaluate, so it's enabled",
+                            program, value
+                        ),
+                    ),
+                }
+            } else if let Some(program) = key.strip_prefix("blend.") {
+                if program.contains('.') {
+                    self.unmapped(
+                        &file,
+                        line,
+                        format!("Nova can't blend render targets separately ({})", key),
+                    );
+                    continue;
+                }
+                match parse_blend_mode(value) {
+                    Some(blend_mode) => {
+                        self.blend_modes.insert(program.to_owned(), blend_mode);
+                    }
+                    None => self.unmapped(&file, line, format!("Nova can't use blend mode `{}`", value)),
+                }
+            } else if let Some(buffer) = key.strip_prefix("size.buffer.") {
+                match (render_target_name(buffer), parse_buffer_size(value)) {
+                    (Some(target), Some(size)) => {
+                        self.buffer_sizes.insert(target, size);
+                    }
+                    (None, _) => self.unmapped(&file, line, format!("{} isn't a render target", buffer)),
+                    (_, None) => self.unmapped(&file, line, format!("Nova can't use buffer size `{}`", value)),
+                }
+            } else if key != "sliders" && !key.starts_with("profile.") && !key.starts_with("screen") {
+                // Sliders, profiles and screens are read along with the options
+                self.unmapped(&file, line, format!("Nova doesn't support the {} property", key));
+            }
+        }
+
+        self.properties = properties;
+        Ok(())
+    }
+
+    fn read_programs(&mut self) -> Result<(), OptifineImportError> {
+        let shaders = Path::new(SHADERS_FOLDER);
+        let entries = self
+            .pack
+            .list_directory(shaders)
+            .map_err(|error| OptifineImportError::CouldNotRead {
+                file: shaders.to_path_buf(),
+                error,
+            })?;
+
+        let mut files: BTreeMap<String, ProgramFiles> = BTreeMap::new();
+        for path in entries {
+            let name = match path.file_stem() {
+                Some(name) => name.to_string_lossy().into_owned(),
+                None => continue,
+            };
+            let extension = path
+                .extension()
+                .map(|extension| extension.to_string_lossy().into_owned());
+
+            match extension.as_deref() {
+                Some("vsh") => files.entry(name).or_default().vertex_shader = Some(path),
+                Some("fsh") => files.entry(name).or_default().fragment_shader = Some(path),
+                Some("gsh") => files.entry(name).or_default().geometry_shader = Some(path),
+                None if name.starts_with("world") => self.unmapped(
+                    &path,
+                    None,
+                    "Nova doesn't support programs for specific dimensions, so the main programs are used everywhere"
+                        .to_owned(),
+                ),
+                _ => {}
+            }
+        }
+
+        for (name, files) in files {
+            let ProgramFiles {
+                vertex_shader,
+                fragment_shader,
+                geometry_shader,
+            } = files;
+
+            if !is_known_program(&name) {
+                let file = vertex_shader.or(fragment_shader).or(geometry_shader).unwrap();
+                self.unmapped(&file, None, format!("Nova doesn't know when to run program {}", name));
+                continue;
+            }
+            if self.disabled_programs.contains(&name) {
+                continue;
+            }
+
+            let (vertex_shader, fragment_shader) = match (vertex_shader, fragment_shader) {
+                (Some(vertex_shader), Some(fragment_shader)) => (vertex_shader, fragment_shader),
+                (None, Some(file)) => {
+                    self.unmapped(
+                        &file,
+                        None,
+                        format!("Program {} has no vertex shader, so it's skipped", name),
+                    );
+                    continue;
+                }
+                (Some(file), None) => {
+                    self.unmapped(
+                        &file,
+                        None,
+                        format!("Program {} has no fragment shader, so it's skipped", name),
+                    );
+                    continue;
+                }
+                (None, None) => {
+                    self.unmapped(
+                        &geometry_shader.unwrap(),
+                        None,
+                        format!("Program {} only has a geometry shader, so it's skipped", name),
+                    );
+                    continue;
+                }
+            };
+
+            let program = Program {
+                vertex_files: self.read_with_includes(&vertex_shader)?,
+                fragment_files: self.read_with_includes(&fragment_shader)?,
+                geometry_files: match &geometry_shader {
+                    Some(geometry_shader) => self.read_with_includes(geometry_shader)?,
+                    None => Vec::new(),
+                },
+                vertex_shader,
+                fragment_shader,
+                geometry_shader,
+            };
+            self.programs.insert(name, program);
+        }
+
+        Ok(())
+    }
+
+    /// Reads a shader, and every file that it includes
+    fn read_with_includes(&mut self, file: &Path) -> Result<Vec<(PathBuf, String)>, OptifineImportError> {
+        let mut files = Vec::new();
+        let mut pending = vec![file.to_path_buf()];
+        let mut seen = HashSet::new();
+        while let Some(file) = pending.pop() {
+            if !seen.insert(file.clone()) {
+                continue;
+            }
+
+            let text = self.read_string(&file)?;
+            for (line, include) in includes(&text) {
+                // Paths that start with `/` are relative to the shaders folder, not to the root of the pack
+                let included = if include.starts_with('/') {
+                    Path::new(SHADERS_FOLDER).join(include.trim_start_matches('/'))
+                } else {
+                    file.parent().unwrap_or_else(|| Path::new("")).join(&include)
+                };
+
+                match normalize_pack_path(&included) {
+                    Ok(included) if self.pack.exists(&included) => pending.push(included),
+                    _ => self.unmapped(&file, Some(line), format!("Included file {} doesn't exist", include)),
+                }
+            }
+            files.push((file, text));
+        }
+
+        Ok(files)
+    }
+
+    /// Reads the settings that programs declare as `const int`s, like `colortex0Format`
+    fn read_constants(&mut self) {
+        let mut constants = Vec::new();
+        for program in self.programs.values() {
+            for (file, text) in program.files() {
+                for (line, name, value) in int_constants(text) {
+                    constants.push((file.clone(), line, name, value));
+                }
+            }
+        }
+
+        for (file, line, name, value) in constants {
+            if name == "shadowMapResolution" {
+                match value.parse() {
+                    Ok(resolution) => self.shadow_map_resolution = resolution,
+                    Err(_) => self.unmapped(
+                        &file,
+                        Some(line),
+                        format!(
+                            "Nova can't evaluate shadowMapResolution `{}`, so the shadow map is {} pixels",
+                            value, DEFAULT_SHADOW_MAP_RESOLUTION
+                        ),
+                    ),
+                }
+                continue;
+            }
+
+            let target = match name.strip_suffix("Format").and_then(render_target_name) {
+                Some(target) => target,
+                None => continue,
+            };
+            let format = match value.as_str() {
+                "RGBA8" => PixelFormat::RGBA8,
+                "RGBA16F" => PixelFormat::RGBA16F,
+                "RGBA32F" => PixelFormat::RGBA32F,
+                _ => {
+                    let format = if value.contains("32") {
+                        PixelFormat::RGBA32F
+                    } else if value.contains("16") || value.contains("F") {
+                        PixelFormat::RGBA16F
+                    } else {
+                        PixelFormat::RGBA8
+                    };
+                    self.unmapped(
+                        &file,
+                        Some(line),
+                        format!("Nova doesn't have format {}, so {} is {:?}", value, target, format),
+                    );
+                    format
+                }
+            };
+            self.formats.insert(target, format);
+        }
+    }
+
+    /// Reads the options that the shaders declare, and adds their defines to every pipeline
+    fn read_options(&mut self) -> ShaderOptions {
+        let sources: BTreeMap<PathBuf, String> = self.programs.values().flat_map(Program::files).cloned().collect();
+        let sources: Vec<_> = sources.into_iter().collect();
+
+        let properties_file = Path::new(SHADERS_FOLDER).join("shaders.properties");
+        let (options, unmapped) = ShaderOptions::parse(&sources, &self.properties, &properties_file);
+        for feature in unmapped {
+            self.unmapped(&feature.file, feature.line, feature.reason);
+        }
+
+        options.apply_to(&mut self.data);
+        options
+    }
+
+    fn add_passes(&mut self) {
+        if self.programs.contains_key(SHADOW_PROGRAM) {
+            self.add_pass(SHADOW_PROGRAM, SHADOW_PROGRAM, PassKind::Shadow, SHADOW_FILTER);
+        }
+        for program in GBUFFERS_PROGRAMS.iter().filter(|program| !program.translucent) {
+            self.add_gbuffers_pass(program);
+        }
+        for program in numbered_programs("deferred") {
+            if self.programs.contains_key(&program) {
+                self.add_pass(&program, &program, PassKind::Fullscreen, FULLSCREEN_FILTER);
+            }
+        }
+        for program in GBUFFERS_PROGRAMS.iter().filter(|program| program.translucent) {
+            self.add_gbuffers_pass(program);
+        }
+        for program in numbered_programs("composite") {
+            if self.programs.contains_key(&program) {
+                self.add_pass(&program, &program, PassKind::Fullscreen, FULLSCREEN_FILTER);
+            }
+        }
+
+        if self.programs.contains_key(FINAL_PROGRAM) {
+            self.add_pass(FINAL_PROGRAM, FINAL_PROGRAM, PassKind::Final, FULLSCREEN_FILTER);
+        } else {
+            self.unmapped(
+                Path::new(SHADERS_FOLDER),
+                None,
+                format!("There's no final program, so nothing is drawn to {}", BACKBUFFER_NAME),
+            );
+        }
+    }
+
+    fn add_gbuffers_pass(&mut self, program: &GbuffersProgram) {
+        let mut used = Some(program.name);
+        while let Some(name) = used {
+            if self.programs.contains_key(name) {
+                break;
+            }
+            used = GBUFFERS_PROGRAMS
+                .iter()
+                .find(|program| program.name == name)
+                .and_then(|program| program.fallback);
+        }
+
+        match used {
+            Some(used) => {
+                if used != program.name {
+                    self.report.fallbacks.push((program.name.to_owned(), used.to_owned()));
+                }
+                let kind = PassKind::Gbuffers {
+                    translucent: program.translucent,
+                };
+                self.add_pass(program.name, used, kind, program.filter);
+            }
+            None => self.unmapped(
+                Path::new(SHADERS_FOLDER),
+                None,
+                format!(
+                    "Neither {} nor any program it falls back to exists, so the geometry it draws is skipped",
+                    program.name
+                ),
+            ),
+        }
+    }
+
+    /// Adds a pass, pipeline and material for a program
+    ///
+    /// # Parameters
+    ///
+    /// * `name` - The name of the pass, pipeline and material
+    /// * `program_name` - The program whose shaders to use. Not the same as `name` when a program falls back to another
+    /// * `kind` - What the pass draws
+    /// * `filter` - The geometry filter for the material
+    fn add_pass(&mut self, name: &str, program_name: &str, kind: PassKind, filter: &str) {
+        let program = &self.programs[program_name];
+
+        let fragment_text = || program.fragment_files.iter().map(|(_, text)| text.as_str());
+        let draw_buffers = fragment_text()
+            .find_map(draw_buffers)
+            .or_else(|| {
+                let outputs: Vec<_> = fragment_text().flat_map(frag_data_outputs).collect();
+                if outputs.is_empty() { None } else { Some(outputs) }
+            })
+            .unwrap_or_else(|| vec![0]);
+
+        let mut samplers = Vec::new();
+        for (file, text) in program.files() {
+            for (line, sampler) in sampler_uniforms(text) {
+                samplers.push((file.clone(), line, sampler));
+            }
+        }
+
+        let vertex_text: String = program.vertex_files.iter().map(|(_, text)| text.as_str()).collect();
+        let mut pipeline = new_pipeline(name, program);
+        pipeline.vertex_fields = vertex_fields(&vertex_text);
+        let fragment_shader = program.fragment_shader.clone();
+
+        let mut outputs = Vec::new();
+        match kind {
+            PassKind::Final => outputs.push(BACKBUFFER_NAME.to_owned()),
+            PassKind::Shadow => {
+                for buffer in draw_buffers {
+                    match render_target_name(&format!("shadowcolor{}", buffer)) {
+                        Some(target) => outputs.push(target),
+                        None => self.unmapped(
+                            &fragment_shader,
+                            None,
+                            format!("The shadow program can't draw to shadowcolor{}", buffer),
+                        ),
+                    }
+                }
+            }
+            PassKind::Gbuffers { .. } | PassKind::Fullscreen => {
+                for buffer in draw_buffers {
+                    match render_target_name(&format!("colortex{}", buffer)) {
+                        Some(target) => outputs.push(target),
+                        None => self.unmapped(
+                            &fragment_shader,
+                            None,
+                            format!("Program {} can't draw to colortex{}", program_name, buffer),
+                        ),
+                    }
+                }
+            }
+        }
+
+        let mut texture_inputs = Vec::new();
+        let mut bindings = HashMap::new();
+        for (file, line, sampler) in samplers {
+            match sampler_resource(&sampler) {
+                Some(resource) => {
+                    if let Some((target, left_out)) = depth_copy(&sampler) {
+                        let reason = format!(
+                            "Nova doesn't keep a copy of the depth buffer without {}, so {} reads {}",
+                            left_out, sampler, target
+                        );
+                        self.unmapped(&file, Some(line), reason);
+                    }
+                    if is_render_target(&resource) && !texture_inputs.contains(&resource) {
+                        self.use_target(&resource);
+                        texture_inputs.push(resource.clone());
+                    }
+                    bindings.insert(sampler, resource);
+                }
+                None => self.unmapped(&file, Some(line), format!("Nova doesn't provide sampler {}", sampler)),
+            }
+        }
+
+        let texture_outputs = outputs.into_iter().map(|target| self.attachment(&target)).collect();
+        let depth_texture = match kind {
+            PassKind::Shadow => Some(self.attachment(SHADOW_DEPTH_BUFFER)),
+            PassKind::Gbuffers { .. } => Some(self.attachment(DEPTH_BUFFER)),
+            PassKind::Fullscreen | PassKind::Final => None,
+        };
+
+        match kind {
+            PassKind::Gbuffers { translucent } => {
+                if translucent {
+                    pipeline.render_queue = Some(RenderQueue::Transparent);
+                }
+                let default_blend_mode = if translucent {
+                    Some(DEFAULT_TRANSLUCENT_BLEND)
+                } else {
+                    None
+                };
+                let blend_mode = self.blend_modes.get(name).cloned().unwrap_or(default_blend_mode);
+                if let Some([src, dst, alpha_src, alpha_dst]) = blend_mode {
+                    pipeline.states.push(RasterizerState::Blending);
+                    pipeline.src_blend_factor = Some(src);
+                    pipeline.dst_blend_factor = Some(dst);
+                    pipeline.alpha_src = Some(alpha_src);
+                    pipeline.alpha_dst = Some(alpha_dst);
+                }
+            }
+            PassKind::Fullscreen | PassKind::Final => {
+                pipeline.states.push(RasterizerState::DisableDepthTest);
+                pipeline.states.push(RasterizerState::DisableDepthWrite);
+            }
+            PassKind::Shadow => {}
+        }
+
+        self.data.passes.push(RenderPassCreationInfo {
+            name: name.to_owned(),
+            dependencies: Vec::new(),
+            texture_inputs,
+            texture_outputs,
+            depth_texture,
+            input_buffers: Vec::new(),
+            output_buffers: Vec::new(),
+        });
+        self.data.pipelines.push(pipeline);
+        self.data.materials.push(MaterialData {
+            name: name.to_owned(),
+            passes: vec![MaterialPass {
+                name: "main".to_owned(),
+                material_name: name.to_owned(),
+                pipeline: name.to_owned(),
+                bindings,
+            }],
+            geometry_filter: filter.to_owned(),
+        });
+    }
+
+    /// Makes an attachment for a render target. Optifine clears render targets at the start of the frame, so the first
+    /// pass that draws to each one clears it
+    fn attachment(&mut self, target: &str) -> TextureAttachmentInfo {
+        let clear = target != BACKBUFFER_NAME && self.cleared_targets.insert(target.to_owned());
+        if target != BACKBUFFER_NAME {
+            self.use_target(target);
+        }
+
+        TextureAttachmentInfo {
+            name: target.to_owned(),
+            pixel_format: self.format_of(target),
+            clear,
+        }
+    }
+
+    fn use_target(&mut self, target: &str) {
+        if !self.used_targets.iter().any(|used| used == target) {
+            self.used_targets.push(target.to_owned());
+        }
+    }
+
+    fn format_of(&self, target: &str) -> PixelFormat {
+        if target == DEPTH_BUFFER || target == SHADOW_DEPTH_BUFFER {
+            PixelFormat::Depth
+        } else {
+            self.formats.get(target).cloned().unwrap_or_default()
+        }
+    }
+
+    fn add_textures(&mut self) {
+        let mut textures = Vec::new();
+        for target in &self.used_targets {
+            let (dimension_type, width, height) = match self.buffer_sizes.get(target) {
+                Some(size) => size.clone(),
+                None if target.starts_with("shadow") => (
+                    TextureDimensionType::Absolute,
+                    self.shadow_map_resolution,
+                    self.shadow_map_resolution,
+                ),
+                None => (TextureDimensionType::ScreenRelative, 1.0, 1.0),
+            };
+
+            textures.push(TextureCreateInfo {
+                name: target.clone(),
+                format: TextureFormat {
+                    pixel_format: self.format_of(target),
+                    dimension_type,
+                    width,
+                    height,
+                },
+            });
+        }
+        self.data.resources.textures = textures;
+    }
+}
+
+/// Makes a pipeline that uses a program's shaders, with every other field at its default
+fn new_pipeline(name: &str, program: &Program) -> PipelineCreationInfo {
+    PipelineCreationInfo {
+        name: name.to_owned(),
+        parent: None,
+        pass: name.to_owned(),
+        defines: Vec::new(),
+        states: Vec::new(),
+        vertex_fields: Vec::new(),
+        front_face: None,
+        back_face: None,
+        fallback: None,
+        depth_bias: None,
+        slope_scaled_depth_bias: None,
+        stencil_ref: None,
+        stencil_read_mask: None,
+        stencil_write_mask: None,
+        msaa_support: None,
+        primitive_mode: None,
+        src_blend_factor: None,
+        dst_blend_factor: None,
+        alpha_src: None,
+        alpha_dst: None,
+        depth_func: None,
+        render_queue: None,
+        vertex_shader: program.vertex_shader.clone().into(),
+        geometry_shader: program.geometry_shader.clone().map(ShaderSource::from),
+        tessellation_control_shader: None,
+        tessellation_evaluation_shader: None,
+        fragment_shader: Some(program.fragment_shader.clone().into()),
+    }
+}
+
+/// Parses a blend mode from `shaders.properties`, like `SRC_ALPHA ONE_MINUS_SRC_ALPHA ONE ZERO` or `off`
+fn parse_blend_mode(value: &str) -> Option<Option<[BlendFactor; 4]>> {
+    if value == "off" {
+        return Some(None);
+    }
+
+    let factors = value
+        .split_whitespace()
+        .map(|factor| match factor {
+            "ZERO" => Some(BlendFactor::Zero),
+            "ONE" => Some(BlendFactor::One),
+            "SRC_COLOR" => Some(BlendFactor::SrcColor),
+            "ONE_MINUS_SRC_COLOR" => Some(BlendFactor::OneMinusSrcColor),
+            "DST_COLOR" => Some(BlendFactor::DstColor),
+            "ONE_MINUS_DST_COLOR" => Some(BlendFactor::OneMinusDstColor),
+            "SRC_ALPHA" => Some(BlendFactor::SrcAlpha),
+            "ONE_MINUS_SRC_ALPHA" => Some(BlendFactor::OneMinusSrcAlpha),
+            "DST_ALPHA" => Some(BlendFactor::DstAlpha),
+            "ONE_MINUS_DST_ALPHA" => Some(BlendFactor::OneMinusDstAlpha),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    match factors.as_slice() {
+        [src, dst, alpha_src, alpha_dst] => {
+            Some(Some([src.clone(), dst.clone(), alpha_src.clone(), alpha_dst.clone()]))
+        }
+        _ => None,
+    }
+}
+
+/// Parses a render target size from `shaders.properties`. Sizes with a decimal point are multiples of the screen's
+/// size, like `0.5 0.5`, and sizes without one are in pixels, like `512 512`
+fn parse_buffer_size(value: &str) -> Option<(TextureDimensionType, f32, f32)> {
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    match parts.as_slice() {
+        [width, height] => {
+            let dimension_type = if width.contains('.') || height.contains('.') {
+                TextureDimensionType::ScreenRelative
+            } else {
+                TextureDimensionType::Absolute
+            };
+            Some((dimension_type, width.parse().ok()?, height.parse().ok()?))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::loading::*;
+    use crate::render_graph::*;
+    use crate::shaderpack::*;
+    use std::path::Path;
+
+    const VERTEX_SHADER: &str = "void main() { gl_Position = ftransform(); uv = gl_MultiTexCoord0.xy; }";
+
+    #[test]
+    fn imports_programs_in_optifine_order() {
+        let (_dir, pack) = make_pack(&[
+            ("shaders/gbuffers_basic.vsh", VERTEX_SHADER),
+            ("shaders/gbuffers_basic.fsh", ""),
+            ("shaders/gbuffers_textured_lit.vsh", VERTEX_SHADER),
+            (
+                "shaders/gbuffers_textured_lit.fsh",
+                "uniform sampler2D texture;\nuniform sampler2D lightmap;",
+            ),
+            ("shaders/gbuffers_terrain.vsh", VERTEX_SHADER),
+            (
+                "shaders/gbuffers_terrain.fsh",
+                "#include \"/lib/gbuffer.glsl\"\n/* DRAWBUFFERS:02 */",
+            ),
+            (
+                "shaders/lib/gbuffer.glsl",
+                "uniform sampler2D texture;\n\
+                 /* const int colortex2Format = RGBA16F; */\n\
+                 //#define WAVING_PLANTS\n\
+                 #ifdef WAVING_PLANTS\n\
+                 #endif",
+            ),
+            ("shaders/composite.vsh", VERTEX_SHADER),
+            (
+                "shaders/composite.fsh",
+                "uniform sampler2D gcolor;\nuniform sampler2D gnormal;\nvoid main() { gl_FragData[1] = vec4(0.0); }",
+            ),
+            ("shaders/final.vsh", VERTEX_SHADER),
+            ("shaders/final.fsh", "uniform sampler2D colortex1;"),
+            (
+                "shaders/shaders.properties",
+                "blend.gbuffers_water=ONE ONE ONE ZERO\nsize.buffer.colortex1=0.5 0.5",
+            ),
+        ]);
+
+        let import = import_optifine_shaderpack(&pack).unwrap();
+        let data = &import.data;
+
+        let passes: Vec<_> = data.passes.iter().map(|pass| pass.name.as_str()).collect();
+        assert_eq!(
+            &passes[passes.len() - 4..],
+            ["gbuffers_hand_water", "gbuffers_weather", "composite", "final"]
+        );
+        assert!(
+            import
+                .report
+                .fallbacks
+                .contains(&("gbuffers_water".to_owned(), "gbuffers_terrain".to_owned()))
+        );
+        assert!(
+            !import
+                .report
+                .fallbacks
+                .iter()
+                .any(|(program, _)| program == "gbuffers_terrain")
+        );
+
+        let terrain = data.passes.iter().find(|pass| pass.name == "gbuffers_terrain").unwrap();
+        let outputs: Vec<_> = terrain
+            .texture_outputs
+            .iter()
+            .map(|output| (output.name.as_str(), output.pixel_format.clone(), output.clear))
+            .collect();
+        assert_eq!(
+            outputs,
+            vec![
+                ("colortex0", PixelFormat::RGBA8, false),
+                ("colortex2", PixelFormat::RGBA16F, true)
+            ]
+        );
+        assert_eq!(terrain.depth_texture.as_ref().unwrap().name, "depthtex0");
+
+        let water = data
+            .pipelines
+            .iter()
+            .find(|pipeline| pipeline.name == "gbuffers_water")
+            .unwrap();
+        assert_eq!(water.vertex_shader.filename, Path::new("shaders/gbuffers_terrain.vsh"));
+        assert_eq!(water.src_blend_factor, Some(BlendFactor::One));
+        assert_eq!(water.render_queue, Some(RenderQueue::Transparent));
+        assert_eq!(water.defines, vec!["!WAVING_PLANTS"]);
+        assert_eq!(
+            import.options.value("WAVING_PLANTS"),
+            Some(&OptionValue::Boolean(false))
+        );
+
+        let composite = data.passes.iter().find(|pass| pass.name == "composite").unwrap();
+        assert_eq!(composite.texture_inputs, vec!["colortex0", "colortex2"]);
+        assert_eq!(composite.texture_outputs[0].name, "colortex1");
+        assert!(composite.texture_outputs[0].clear);
+        let composite_material = data
+            .materials
+            .iter()
+            .find(|material| material.name == "composite")
+            .unwrap();
+        assert_eq!(composite_material.passes[0].bindings["gnormal"], "colortex2");
+
+        let terrain_material = data
+            .materials
+            .iter()
+            .find(|material| material.name == "gbuffers_terrain")
+            .unwrap();
+        assert_eq!(terrain_material.passes[0].bindings["texture"], "ColorVirtualTexture");
+
+        let colortex1 = data
+            .resources
+            .textures
+            .iter()
+            .find(|texture| texture.name == "colortex1")
+            .unwrap();
+        assert_eq!(colortex1.format.width, 0.5);
+        assert_eq!(data.passes.last().unwrap().texture_outputs[0].name, BACKBUFFER_NAME);
+
+        // The imported shaderpack should work with the rest of Nova
+        RenderGraph::build(&data.passes).unwrap();
+        MaterialIndex::new(data).unwrap();
+        assert!(import.report.unmapped.is_empty(), "{:?}", import.report.unmapped);
+    }
+
+    #[test]
+    fn reports_what_it_cant_import() {
+        let (_dir, pack) = make_pack(&[
+            ("shaders/gbuffers_basic.vsh", VERTEX_SHADER),
+            (
+                "shaders/gbuffers_basic.fsh",
+                "#include \"missing.glsl\"\n\
+                 uniform sampler2D noisetex;\n\
+                 uniform sampler2D depthtex1;\n\
+                 /* const int colortex3Format = R11F_G11F_B10F; */",
+            ),
+            ("shaders/gbuffers_block.vsh", VERTEX_SHADER),
+            ("shaders/gbuffers_block.fsh", ""),
+            ("shaders/composite1.fsh", ""),
+            ("shaders/composite3.gsh", ""),
+            ("shaders/composite2.vsh", VERTEX_SHADER),
+            ("shaders/composite2.fsh", ""),
+            ("shaders/world-1/composite.vsh", VERTEX_SHADER),
+            (
+                "shaders/shaders.properties",
+                "#ifdef BLOOM\nprogram.composite2.enabled=false\n#endif\noldLighting=false",
+            ),
+        ]);
+
+        let import = import_optifine_shaderpack(&pack).unwrap();
+        let unmapped: Vec<_> = import.report.unmapped.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            unmapped,
+            vec![
+                "shaders/shaders.properties:1: Nova doesn't evaluate `#ifdef BLOOM`, so every property around it was \
+                 read",
+                "shaders/shaders.properties:4: Nova doesn't support the oldLighting property",
+                "shaders/world-1: Nova doesn't support programs for specific dimensions, so the main programs are used \
+                 everywhere",
+                "shaders/composite1.fsh: Program composite1 has no vertex shader, so it's skipped",
+                "shaders/composite3.gsh: Program composite3 only has a geometry shader, so it's skipped",
+                "shaders/gbuffers_basic.fsh:1: Included file missing.glsl doesn't exist",
+                "shaders/gbuffers_block.vsh: Nova doesn't know when to run program gbuffers_block",
+                "shaders/gbuffers_basic.fsh:4: Nova doesn't have format R11F_G11F_B10F, so colortex3 is RGBA16F",
+                "shaders/gbuffers_basic.fsh:2: Nova doesn't provide sampler noisetex",
+                "shaders/gbuffers_basic.fsh:3: Nova doesn't keep a copy of the depth buffer without translucent \
+                 geometry, so depthtex1 reads depthtex0",
+                "shaders: There's no final program, so nothing is drawn to Backbuffer",
+            ]
+        );
+
+        // Every gbuffers program falls back to gbuffers_basic, and composite2 is disabled
+        assert_eq!(import.report.fallbacks.len(), 15);
+        assert!(import.data.passes.iter().all(|pass| pass.name.starts_with("gbuffers")));
+    }
+
+    #[test]
+    fn rejects_packs_without_shaders() {
+        let (_dir, pack) = make_pack(&[("pack.mcmeta", "{}")]);
+        match import_optifine_shaderpack(&pack) {
+            Err(OptifineImportError::NotAnOptifinePack) => {}
+            other => panic!("Expected NotAnOptifinePack, got {:?}", other.map(|import| import.data)),
+        }
+    }
+}
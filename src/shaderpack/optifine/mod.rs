@@ -0,0 +1,15 @@
+//! Importing Optifine shaderpacks
+//!
+//! Optifine shaderpacks describe their passes with the names of their programs and with special comments in their
+//! shaders, instead of with JSON files. This module works out what Nova passes, pipelines and materials would do the
+//! same thing
+
+mod importer;
+mod options;
+mod program_source;
+mod programs;
+mod properties;
+
+pub use importer::*;
+pub use options::*;
+pub use properties::*;
@@ -0,0 +1,751 @@
+//! The options that Optifine shaderpacks let players change
+//!
+//! Options are declared in the shaders themselves:
+//! - `#define SHADOWS` is a boolean option that's on, and `//#define SHADOWS` is one that's off. A boolean option is
+//!   only an option if a shader checks it with `#ifdef`, `#ifndef` or `defined`
+//! - `#define SHADOW_QUALITY 2 // [1 2 4]` is an option with a list of values. The comment lists the values
+//!
+//! `shaders.properties` decides how they're shown: `sliders` lists the options that are shown as sliders, `profile.*`
+//! sets many options at once, and `screen` and `screen.*` lay the options out in screens
+
+use crate::shaderpack::*;
+use failure::Fail;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// The value of a shader option
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum OptionValue {
+    Boolean(bool),
+    Value(String),
+}
+
+/// What values a shader option can have
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ShaderOptionKind {
+    /// The option is on or off
+    Boolean,
+
+    /// The option is one of a list of values, and is shown as a button that goes through them
+    Enumerated(Vec<String>),
+
+    /// The option is one of a list of values, and is shown as a slider
+    Slider(Vec<String>),
+}
+
+/// An option that a shaderpack declares
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ShaderOption {
+    pub name: String,
+    pub kind: ShaderOptionKind,
+
+    /// The value that the shaders give the option
+    pub default: OptionValue,
+
+    /// The text of the comment after the option, without the list of values
+    pub description: Option<String>,
+
+    /// The file that declares the option, relative to the root of the pack
+    pub file: PathBuf,
+
+    /// The line that declares the option, starting at 1
+    pub line: usize,
+}
+
+/// A named set of option values, such as `LOW` or `ULTRA`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct OptionProfile {
+    pub name: String,
+
+    /// The value of each option that the profile sets, including the options set by the profiles that it includes
+    pub values: Vec<(String, OptionValue)>,
+}
+
+/// Something shown on an option screen
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ScreenEntry {
+    /// The option with this name
+    Option(String),
+
+    /// A button that opens the screen with this name
+    Screen(String),
+
+    /// The button that picks a profile
+    Profile,
+
+    /// An empty space
+    Empty,
+
+    /// Every option that isn't on any screen
+    Rest,
+}
+
+/// A screen of options, from `screen` or `screen.<name>` in `shaders.properties`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct OptionScreen {
+    /// How many columns to lay the entries out in
+    ///
+    /// Defaults to 2
+    pub columns: u32,
+
+    pub entries: Vec<ScreenEntry>,
+}
+
+impl Default for OptionScreen {
+    fn default() -> OptionScreen {
+        OptionScreen {
+            columns: 2,
+            entries: Vec::new(),
+        }
+    }
+}
+
+/// Problems with changing the value of an option
+#[derive(Fail, Debug)]
+pub enum ShaderOptionError {
+    #[fail(display = "There's no option named {}", _0)]
+    UnknownOption(String),
+
+    #[fail(display = "There's no profile named {}", _0)]
+    UnknownProfile(String),
+
+    #[fail(display = "Option {} can't be set to {:?}", option, value)]
+    InvalidValue { option: String, value: OptionValue },
+}
+
+/// All the options that a shaderpack declares, and the values that they're set to
+#[derive(Debug, Clone, Default)]
+pub struct ShaderOptions {
+    options: Vec<ShaderOption>,
+    profiles: Vec<OptionProfile>,
+    main_screen: Option<OptionScreen>,
+    screens: BTreeMap<String, OptionScreen>,
+
+    /// The options that have been set to something other than their default
+    values: HashMap<String, OptionValue>,
+}
+
+impl ShaderOptions {
+    /// Finds the options that shaders declare, and reads how `shaders.properties` shows them
+    ///
+    /// Returns the options, and everything in `shaders.properties` that refers to options that don't exist
+    ///
+    /// # Parameters
+    ///
+    /// * `sources` - The text of every shader file, and its path relative to the root of the pack
+    /// * `properties` - The pack's `shaders.properties` file
+    /// * `properties_file` - The path to `shaders.properties`, relative to the root of the pack
+    pub(crate) fn parse(
+        sources: &[(PathBuf, String)],
+        properties: &ShaderProperties,
+        properties_file: &Path,
+    ) -> (ShaderOptions, Vec<UnmappedFeature>) {
+        let mut options = ShaderOptions::default();
+        let mut unmapped = Vec::new();
+        let mut problem = |line: Option<usize>, reason: String| {
+            unmapped.push(UnmappedFeature {
+                file: properties_file.to_path_buf(),
+                line,
+                reason,
+            })
+        };
+
+        let checked: HashSet<&str> = sources.iter().flat_map(|(_, text)| checked_symbols(text)).collect();
+        for (file, text) in sources {
+            for (index, line) in text.lines().enumerate() {
+                let option = match parse_option(line) {
+                    Some(option) => option,
+                    None => continue,
+                };
+                if options.option(&option.name).is_some()
+                    || (option.kind == ShaderOptionKind::Boolean && !checked.contains(option.name.as_str()))
+                {
+                    continue;
+                }
+
+                options.options.push(ShaderOption {
+                    file: file.clone(),
+                    line: index + 1,
+                    ..option
+                });
+            }
+        }
+
+        let mut profile_entries = BTreeMap::new();
+        for property in &properties.properties {
+            let words = property.value.split_whitespace();
+            if property.key == "sliders" {
+                for name in words {
+                    let option = options.options.iter_mut().find(|option| option.name == name);
+                    match option {
+                        Some(option) if option.kind != ShaderOptionKind::Boolean => {
+                            if let ShaderOptionKind::Enumerated(values) = &option.kind {
+                                option.kind = ShaderOptionKind::Slider(values.clone());
+                            }
+                        }
+                        _ => problem(
+                            Some(property.line),
+                            format!("Slider {} isn't an option with a list of values", name),
+                        ),
+                    }
+                }
+            } else if let Some(profile) = property.key.strip_prefix("profile.") {
+                profile_entries.insert(profile.to_owned(), (property.line, property.value.clone()));
+            } else if let Some(screen) = property.key.strip_prefix("screen") {
+                let (screen, is_columns) = match screen.strip_suffix(".columns") {
+                    Some(screen) => (screen, true),
+                    None => (screen, false),
+                };
+                let screen = if screen.is_empty() {
+                    options.main_screen.get_or_insert_with(OptionScreen::default)
+                } else if let Some(name) = screen.strip_prefix('.') {
+                    options.screens.entry(name.to_owned()).or_default()
+                } else {
+                    continue;
+                };
+
+                if is_columns {
+                    match property.value.parse() {
+                        Ok(columns) => screen.columns = columns,
+                        Err(_) => problem(
+                            Some(property.line),
+                            format!("{} isn't a number of columns", property.value),
+                        ),
+                    }
+                } else {
+                    screen.entries = words.map(screen_entry).collect();
+                }
+            }
+        }
+
+        for (name, (line, _)) in &profile_entries {
+            let mut values = Vec::new();
+            let mut visited = vec![name.as_str()];
+            for reason in resolve_profile(&options, &profile_entries, name, &mut visited, &mut values) {
+                problem(Some(*line), reason);
+            }
+            options.profiles.push(OptionProfile {
+                name: name.clone(),
+                values,
+            });
+        }
+
+        let screens = options.main_screen.iter().chain(options.screens.values());
+        for entry in screens.flat_map(|screen| &screen.entries) {
+            match entry {
+                ScreenEntry::Option(name) if options.option(name).is_none() => {
+                    let line = properties
+                        .properties
+                        .iter()
+                        .find(|property| property.key.starts_with("screen") && property.value.contains(name.as_str()))
+                        .map(|property| property.line);
+                    problem(line, format!("Option {} is on a screen, but doesn't exist", name));
+                }
+                ScreenEntry::Screen(name) if !options.screens.contains_key(name) => {
+                    problem(None, format!("Screen {} is linked to, but doesn't exist", name));
+                }
+                _ => {}
+            }
+        }
+
+        (options, unmapped)
+    }
+
+    /// Every option, in the order that they're declared
+    pub fn options(&self) -> &[ShaderOption] {
+        &self.options
+    }
+
+    /// Finds an option by its name
+    ///
+    /// # Parameters
+    ///
+    /// * `name` - The name of the option
+    pub fn option(&self, name: &str) -> Option<&ShaderOption> {
+        self.options.iter().find(|option| option.name == name)
+    }
+
+    /// Every profile, sorted by name
+    pub fn profiles(&self) -> &[OptionProfile] {
+        &self.profiles
+    }
+
+    /// The first screen that's shown, if `shaders.properties` lays options out in screens
+    pub fn main_screen(&self) -> Option<&OptionScreen> {
+        self.main_screen.as_ref()
+    }
+
+    /// Finds a screen that a `ScreenEntry::Screen` links to
+    ///
+    /// # Parameters
+    ///
+    /// * `name` - The name of the screen
+    pub fn screen(&self, name: &str) -> Option<&OptionScreen> {
+        self.screens.get(name)
+    }
+
+    /// The current value of an option
+    ///
+    /// # Parameters
+    ///
+    /// * `name` - The name of the option
+    pub fn value(&self, name: &str) -> Option<&OptionValue> {
+        let option = self.option(name)?;
+        Some(self.values.get(name).unwrap_or(&option.default))
+    }
+
+    /// Changes the value of an option
+    ///
+    /// # Parameters
+    ///
+    /// * `name` - The name of the option
+    /// * `value` - The new value. Boolean options must be set to `OptionValue::Boolean`, and other options must be set
+    ///   to one of their values
+    pub fn set(&mut self, name: &str, value: OptionValue) -> Result<(), ShaderOptionError> {
+        let option = self
+            .option(name)
+            .ok_or_else(|| ShaderOptionError::UnknownOption(name.to_owned()))?;
+        let valid = match (&option.kind, &value) {
+            (ShaderOptionKind::Boolean, OptionValue::Boolean(_)) => true,
+            (ShaderOptionKind::Enumerated(values), OptionValue::Value(value))
+            | (ShaderOptionKind::Slider(values), OptionValue::Value(value)) => values.contains(value),
+            _ => false,
+        };
+        if !valid {
+            return Err(ShaderOptionError::InvalidValue {
+                option: name.to_owned(),
+                value,
+            });
+        }
+
+        if value == option.default {
+            self.values.remove(name);
+        } else {
+            self.values.insert(name.to_owned(), value);
+        }
+        Ok(())
+    }
+
+    /// Sets every option back to its default value
+    pub fn reset(&mut self) {
+        self.values.clear();
+    }
+
+    /// Sets every option that a profile sets. Options that the profile doesn't set are left alone
+    ///
+    /// # Parameters
+    ///
+    /// * `name` - The name of the profile
+    pub fn apply_profile(&mut self, name: &str) -> Result<(), ShaderOptionError> {
+        let profile = self
+            .profiles
+            .iter()
+            .find(|profile| profile.name == name)
+            .ok_or_else(|| ShaderOptionError::UnknownProfile(name.to_owned()))?
+            .clone();
+        for (option, value) in profile.values {
+            self.set(&option, value)?;
+        }
+
+        Ok(())
+    }
+
+    /// The first profile whose values match the current values, if there is one
+    pub fn current_profile(&self) -> Option<&str> {
+        self.profiles
+            .iter()
+            .find(|profile| {
+                profile
+                    .values
+                    .iter()
+                    .all(|(option, value)| self.value(option) == Some(value))
+            })
+            .map(|profile| profile.name.as_str())
+    }
+
+    /// The defines that give every option its current value, in the format of `PipelineCreationInfo::defines`
+    ///
+    /// Options with a value become `NAME=VALUE`. Boolean options become `NAME` when they're on and `!NAME` when
+    /// they're off
+    pub fn defines(&self) -> Vec<String> {
+        self.options
+            .iter()
+            .map(|option| match self.value(&option.name).unwrap() {
+                OptionValue::Boolean(true) => option.name.clone(),
+                OptionValue::Boolean(false) => format!("!{}", option.name),
+                OptionValue::Value(value) => format!("{}={}", option.name, value),
+            })
+            .collect()
+    }
+
+    /// Adds the defines for the current option values to every pipeline, replacing any that were added before
+    ///
+    /// # Parameters
+    ///
+    /// * `data` - The shaderpack whose pipelines to add the defines to
+    pub fn apply_to(&self, data: &mut ShaderpackData) {
+        let defines = self.defines();
+        for pipeline in &mut data.pipelines {
+            pipeline.defines.retain(|define| {
+                let name = define.trim_start_matches('!').split('=').next().unwrap();
+                self.option(name).is_none()
+            });
+            pipeline.defines.extend(defines.iter().cloned());
+        }
+    }
+}
+
+/// Parses a line that might declare an option. The option's file and line are left empty
+fn parse_option(line: &str) -> Option<ShaderOption> {
+    let line = line.trim();
+    let (enabled, declaration) = match line.strip_prefix("//") {
+        Some(declaration) => (false, declaration.trim_start()),
+        None => (true, line),
+    };
+    let declaration = declaration.strip_prefix("#define")?;
+    if !declaration.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    let (definition, comment) = match declaration.find("//") {
+        Some(start) => (&declaration[..start], Some(&declaration[start + 2..])),
+        None => (declaration, None),
+    };
+    let mut words = definition.split_whitespace();
+    let name = words.next()?;
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+    let value: Vec<&str> = words.collect();
+
+    let list = comment.and_then(|comment| {
+        let start = comment.find('[')?;
+        let end = start + comment[start..].find(']')?;
+        Some((start, end))
+    });
+    let description = comment.map(|comment| match list {
+        Some((start, end)) => format!("{}{}", &comment[..start], &comment[end + 1..]),
+        None => comment.to_owned(),
+    });
+    let description = description
+        .map(|description| description.trim().to_owned())
+        .filter(|description| !description.is_empty());
+
+    let (kind, default) = if value.is_empty() {
+        (ShaderOptionKind::Boolean, OptionValue::Boolean(enabled))
+    } else {
+        // A define with a value is only an option if it lists the values it can have
+        let (start, end) = list?;
+        if !enabled {
+            return None;
+        }
+        let values = comment?[start + 1..end].split_whitespace().map(str::to_owned).collect();
+        (
+            ShaderOptionKind::Enumerated(values),
+            OptionValue::Value(value.join(" ")),
+        )
+    };
+
+    Some(ShaderOption {
+        name: name.to_owned(),
+        kind,
+        default,
+        description,
+        file: PathBuf::new(),
+        line: 0,
+    })
+}
+
+/// The symbols that a shader checks with `#ifdef`, `#ifndef` or `defined`
+fn checked_symbols(text: &str) -> Vec<&str> {
+    let mut symbols = Vec::new();
+    for line in text.lines() {
+        let line = line.trim();
+        let checked = line
+            .strip_prefix("#ifdef")
+            .or_else(|| line.strip_prefix("#ifndef"))
+            .and_then(|rest| rest.split_whitespace().next());
+        symbols.extend(checked);
+
+        for (start, _) in line.match_indices("defined") {
+            let rest = line[start + "defined".len()..].trim_start_matches(|c: char| c.is_whitespace() || c == '(');
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            if end > 0 {
+                symbols.push(&rest[..end]);
+            }
+        }
+    }
+
+    symbols
+}
+
+fn screen_entry(word: &str) -> ScreenEntry {
+    match word {
+        "<profile>" => ScreenEntry::Profile,
+        "<empty>" => ScreenEntry::Empty,
+        "*" => ScreenEntry::Rest,
+        _ => match word.strip_prefix('[').and_then(|word| word.strip_suffix(']')) {
+            Some(screen) => ScreenEntry::Screen(screen.to_owned()),
+            None => ScreenEntry::Option(word.to_owned()),
+        },
+    }
+}
+
+/// Works out the values that a profile sets, following the profiles that it includes
+///
+/// `visited` holds the profiles on the current chain of includes, so that a profile may include the same profile
+/// through two others, but can't include itself, either directly or through other profiles
+///
+/// Returns a description of each problem with the profile
+fn resolve_profile<'a>(
+    options: &ShaderOptions,
+    profiles: &'a BTreeMap<String, (usize, String)>,
+    name: &str,
+    visited: &mut Vec<&'a str>,
+    values: &mut Vec<(String, OptionValue)>,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    let entries = match profiles.get_key_value(name) {
+        Some((_, (_, entries))) => entries,
+        None => return problems,
+    };
+
+    for entry in entries.split_whitespace() {
+        if let Some(included) = entry.strip_prefix("profile.") {
+            match profiles.get_key_value(included) {
+                Some((included, _)) if !visited.contains(&included.as_str()) => {
+                    visited.push(included);
+                    problems.extend(resolve_profile(options, profiles, included, visited, values));
+                    visited.pop();
+                }
+                Some((included, _)) => {
+                    let start = visited.iter().position(|visited| visited == included).unwrap();
+                    let problem = if start == visited.len() - 1 {
+                        format!("Profile {} includes itself", included)
+                    } else {
+                        format!("Profiles {} include each other in a loop", visited[start..].join(", "))
+                    };
+                    problems.push(problem);
+                }
+                None => problems.push(format!(
+                    "Profile {} includes profile {}, which doesn't exist",
+                    name, included
+                )),
+            }
+            continue;
+        }
+
+        let (option, value) = if let Some(option) = entry.strip_prefix('!') {
+            (option, OptionValue::Boolean(false))
+        } else if let Some(separator) = entry.find([':', '=']) {
+            (
+                &entry[..separator],
+                OptionValue::Value(entry[separator + 1..].to_owned()),
+            )
+        } else {
+            (entry, OptionValue::Boolean(true))
+        };
+
+        if options.option(option).is_none() {
+            problems.push(format!("Profile {} sets option {}, which doesn't exist", name, option));
+            continue;
+        }
+        values.retain(|(other, _)| other != option);
+        values.push((option.to_owned(), value));
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shaderpack::*;
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    const SHADER: &str = "#define SHADOWS // Whether to draw shadows\n\
+        //#define VOLUMETRIC_LIGHT\n\
+        #define UNUSED_FLAG\n\
+        #define SHADOW_QUALITY 2 // Shadow quality [1 2 4]\n\
+        #define SUN_ANGLE -40 // [-60 -40 -20 0]\n\
+        #define PI 3.14159\n\
+        #ifdef SHADOWS\n\
+        #endif\n\
+        #if defined(VOLUMETRIC_LIGHT) && SHADOW_QUALITY > 1\n\
+        #endif\n";
+
+    const PROPERTIES: &str = "sliders=SUN_ANGLE MISSING\n\
+        profile.LOW=!SHADOWS SHADOW_QUALITY:1\n\
+        profile.HIGH=profile.LOW SHADOWS VOLUMETRIC_LIGHT SHADOW_QUALITY=4\n\
+        screen=<profile> <empty> [LIGHTING] *\n\
+        screen.LIGHTING=SHADOWS VOLUMETRIC_LIGHT SHADOW_QUALITY GONE\n\
+        screen.LIGHTING.columns=1\n\
+        profile.MEDIUM=profile.LOW SHADOWS\n\
+        profile.ULTRA=profile.HIGH profile.MEDIUM\n";
+
+    fn options() -> (ShaderOptions, Vec<UnmappedFeature>) {
+        let sources = vec![(PathBuf::from("shaders/lib/settings.glsl"), SHADER.to_owned())];
+        let properties = ShaderProperties::parse(PROPERTIES);
+        ShaderOptions::parse(&sources, &properties, Path::new("shaders/shaders.properties"))
+    }
+
+    #[test]
+    fn finds_options_in_shaders_and_properties() {
+        let (options, unmapped) = options();
+
+        let names: Vec<_> = options.options().iter().map(|option| option.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["SHADOWS", "VOLUMETRIC_LIGHT", "SHADOW_QUALITY", "SUN_ANGLE"]
+        );
+
+        let shadows = options.option("SHADOWS").unwrap();
+        assert_eq!(shadows.kind, ShaderOptionKind::Boolean);
+        assert_eq!(shadows.default, OptionValue::Boolean(true));
+        assert_eq!(shadows.description.as_ref().unwrap(), "Whether to draw shadows");
+        assert_eq!(options.value("VOLUMETRIC_LIGHT"), Some(&OptionValue::Boolean(false)));
+
+        let quality = options.option("SHADOW_QUALITY").unwrap();
+        assert_eq!(quality.line, 4);
+        assert_eq!(quality.description.as_ref().unwrap(), "Shadow quality");
+        assert_eq!(
+            quality.kind,
+            ShaderOptionKind::Enumerated(vec!["1".to_owned(), "2".to_owned(), "4".to_owned()])
+        );
+        match &options.option("SUN_ANGLE").unwrap().kind {
+            ShaderOptionKind::Slider(values) => assert_eq!(values.len(), 4),
+            kind => panic!("Expected a slider, got {:?}", kind),
+        }
+
+        let high = &options.profiles()[0];
+        assert_eq!(high.name, "HIGH");
+        assert_eq!(
+            high.values,
+            vec![
+                ("SHADOWS".to_owned(), OptionValue::Boolean(true)),
+                ("VOLUMETRIC_LIGHT".to_owned(), OptionValue::Boolean(true)),
+                ("SHADOW_QUALITY".to_owned(), OptionValue::Value("4".to_owned())),
+            ]
+        );
+
+        let main_screen = options.main_screen().unwrap();
+        assert_eq!(
+            main_screen.entries,
+            vec![
+                ScreenEntry::Profile,
+                ScreenEntry::Empty,
+                ScreenEntry::Screen("LIGHTING".to_owned()),
+                ScreenEntry::Rest
+            ]
+        );
+        assert_eq!(options.screen("LIGHTING").unwrap().columns, 1);
+
+        // ULTRA includes LOW through both HIGH and MEDIUM, which isn't a loop
+        let ultra = options
+            .profiles()
+            .iter()
+            .find(|profile| profile.name == "ULTRA")
+            .unwrap();
+        assert_eq!(
+            ultra.values,
+            vec![
+                ("VOLUMETRIC_LIGHT".to_owned(), OptionValue::Boolean(true)),
+                ("SHADOW_QUALITY".to_owned(), OptionValue::Value("1".to_owned())),
+                ("SHADOWS".to_owned(), OptionValue::Boolean(true)),
+            ]
+        );
+
+        let unmapped: Vec<_> = unmapped.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            unmapped,
+            vec![
+                "shaders/shaders.properties:1: Slider MISSING isn't an option with a list of values",
+                "shaders/shaders.properties:5: Option GONE is on a screen, but doesn't exist",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_profiles_that_include_each_other() {
+        let properties = ShaderProperties::parse(
+            "profile.A=profile.B SHADOWS\n\
+             profile.B=profile.A\n\
+             profile.C=profile.C\n\
+             profile.D=profile.A",
+        );
+        let (options, unmapped) = ShaderOptions::parse(
+            &[(PathBuf::from("shaders/settings.glsl"), SHADER.to_owned())],
+            &properties,
+            Path::new("shaders/shaders.properties"),
+        );
+
+        let unmapped: Vec<_> = unmapped.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            unmapped,
+            vec![
+                "shaders/shaders.properties:1: Profiles A, B include each other in a loop",
+                "shaders/shaders.properties:2: Profiles B, A include each other in a loop",
+                "shaders/shaders.properties:3: Profile C includes itself",
+                "shaders/shaders.properties:4: Profiles A, B include each other in a loop",
+            ]
+        );
+        assert_eq!(
+            options.profiles()[3].values,
+            vec![("SHADOWS".to_owned(), OptionValue::Boolean(true))]
+        );
+    }
+
+    #[test]
+    fn values_can_be_changed_and_become_defines() {
+        let (mut options, _) = options();
+        assert_eq!(options.current_profile(), None);
+
+        options.apply_profile("LOW").unwrap();
+        assert_eq!(options.current_profile(), Some("LOW"));
+        assert_eq!(
+            options.defines(),
+            vec!["!SHADOWS", "!VOLUMETRIC_LIGHT", "SHADOW_QUALITY=1", "SUN_ANGLE=-40"]
+        );
+
+        assert!(
+            options
+                .set("SHADOW_QUALITY", OptionValue::Value("3".to_owned()))
+                .is_err()
+        );
+        assert!(options.set("SHADOWS", OptionValue::Value("1".to_owned())).is_err());
+        assert!(options.set("FOG", OptionValue::Boolean(true)).is_err());
+        options.set("SUN_ANGLE", OptionValue::Value("0".to_owned())).unwrap();
+        assert_eq!(options.value("SUN_ANGLE"), Some(&OptionValue::Value("0".to_owned())));
+
+        let mut data = ShaderpackData {
+            pipelines: serde_json::from_str(
+                r#"[{
+                    "name": "terrain",
+                    "pass": "Forward",
+                    "vertexShader": "terrain.vert",
+                    "defines": ["WAVING", "SHADOWS"]
+                }]"#,
+            )
+            .unwrap(),
+            ..ShaderpackData::default()
+        };
+        options.apply_to(&mut data);
+        options.reset();
+        options.apply_to(&mut data);
+        assert_eq!(
+            data.pipelines[0].defines,
+            vec![
+                "WAVING",
+                "SHADOWS",
+                "!VOLUMETRIC_LIGHT",
+                "SHADOW_QUALITY=2",
+                "SUN_ANGLE=-40"
+            ]
+        );
+    }
+}
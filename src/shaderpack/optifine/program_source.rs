@@ -0,0 +1,232 @@
+//! Finding the things that Optifine reads out of a program's GLSL
+//!
+//! Optifine doesn't have a separate file that describes each program. Instead, it looks for special comments and
+//! declarations in the shaders themselves, like `/* DRAWBUFFERS:012 */` and `uniform sampler2D colortex3;`. These
+//! functions look for the same things. They work on the text of the shader, without running the preprocessor
+
+use crate::shaderpack::*;
+
+/// Finds the render targets that a fragment shader writes to, from its `DRAWBUFFERS` or `RENDERTARGETS` comment
+///
+/// `/* DRAWBUFFERS:013 */` lists render targets as one hexadecimal digit each, while `/* RENDERTARGETS: 0,1,13 */`
+/// separates them with commas. Returns `None` if the shader has neither
+///
+/// # Parameters
+///
+/// * `source` - The text of the fragment shader
+pub(crate) fn draw_buffers(source: &str) -> Option<Vec<usize>> {
+    if let Some(start) = source.find("RENDERTARGETS:") {
+        let list = &source[start + "RENDERTARGETS:".len()..];
+        let end = list
+            .find(|c: char| !(c.is_ascii_digit() || c == ',' || c == ' '))
+            .unwrap_or(list.len());
+        return Some(
+            list[..end]
+                .split(',')
+                .filter_map(|target| target.trim().parse().ok())
+                .collect(),
+        );
+    }
+
+    let start = source.find("DRAWBUFFERS:")?;
+    Some(
+        source[start + "DRAWBUFFERS:".len()..]
+            .trim_start()
+            .chars()
+            .map_while(|c| c.to_digit(16))
+            .map(|digit| digit as usize)
+            .collect(),
+    )
+}
+
+/// Finds the indices of `gl_FragData` that a fragment shader writes to, in the order that they're first used
+///
+/// # Parameters
+///
+/// * `source` - The text of the fragment shader
+pub(crate) fn frag_data_outputs(source: &str) -> Vec<usize> {
+    let mut outputs = Vec::new();
+    for (start, _) in source.match_indices("gl_FragData[") {
+        let index = source[start + "gl_FragData[".len()..]
+            .split(']')
+            .next()
+            .and_then(|index| index.trim().parse().ok());
+        if let Some(index) = index {
+            if !outputs.contains(&index) {
+                outputs.push(index);
+            }
+        }
+    }
+
+    outputs
+}
+
+/// Finds the sampler uniforms that a shader declares, as (line, name). Lines start at 1
+///
+/// # Parameters
+///
+/// * `source` - The text of the shader
+pub(crate) fn sampler_uniforms(source: &str) -> Vec<(usize, String)> {
+    let mut samplers = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let line = line.split("//").next().unwrap();
+        let declaration = match line.find("uniform ") {
+            Some(start) => line[start..].split(';').next().unwrap(),
+            None => continue,
+        };
+
+        let mut words = declaration
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|word| !word.is_empty())
+            .skip(1)
+            .skip_while(|word| ["lowp", "mediump", "highp"].contains(word));
+        let is_sampler = words.next().is_some_and(|type_name| type_name.contains("sampler"));
+        if is_sampler {
+            for name in words {
+                let name = name.split('[').next().unwrap();
+                samplers.push((index + 1, name.to_owned()));
+            }
+        }
+    }
+
+    samplers
+}
+
+/// The built-in vertex attributes that Optifine provides, and the vertex fields that Nova provides in their place
+const VERTEX_ATTRIBUTES: &[(&str, VertexField)] = &[
+    ("gl_Vertex", VertexField::Position),
+    ("gl_Color", VertexField::Color),
+    ("gl_MultiTexCoord0", VertexField::UV0),
+    ("gl_MultiTexCoord1", VertexField::UV1),
+    ("gl_Normal", VertexField::Normal),
+    ("at_tangent", VertexField::Tangent),
+    ("mc_midTexCoord", VertexField::MidTexCoord),
+    ("mc_Entity", VertexField::McEntityId),
+];
+
+/// Finds the vertex attributes that a vertex shader uses
+///
+/// Every vertex shader gets the vertex position, since `ftransform()` reads it without naming it
+///
+/// # Parameters
+///
+/// * `source` - The text of the vertex shader
+pub(crate) fn vertex_fields(source: &str) -> Vec<VertexFieldData> {
+    VERTEX_ATTRIBUTES
+        .iter()
+        .filter(|(attribute, _)| *attribute == "gl_Vertex" || contains_word(source, attribute))
+        .map(|(attribute, field)| VertexFieldData {
+            semantic_name: (*attribute).to_owned(),
+            field: field.clone(),
+        })
+        .collect()
+}
+
+/// Finds the `const int` declarations in a shader, as (line, name, value). Lines start at 1
+///
+/// Optifine reads settings like `const int colortex0Format = RGBA16F;` even when they're in a comment, so this does
+/// too
+///
+/// # Parameters
+///
+/// * `source` - The text of the shader
+pub(crate) fn int_constants(source: &str) -> Vec<(usize, String, String)> {
+    let mut constants = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let declaration = match line.find("const int ") {
+            Some(start) => &line[start + "const int ".len()..],
+            None => continue,
+        };
+        let declaration = declaration.split(';').next().unwrap();
+
+        let mut parts = declaration.splitn(2, '=');
+        if let (Some(name), Some(value)) = (parts.next(), parts.next()) {
+            constants.push((index + 1, name.trim().to_owned(), value.trim().to_owned()));
+        }
+    }
+
+    constants
+}
+
+/// Finds the files that a shader includes with `#include "file"`, as (line, path). Lines start at 1
+///
+/// # Parameters
+///
+/// * `source` - The text of the shader
+pub(crate) fn includes(source: &str) -> Vec<(usize, String)> {
+    source
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let path = line.trim().strip_prefix("#include")?.trim();
+            let path = path.strip_prefix('"')?.strip_suffix('"')?;
+            Some((index + 1, path.to_owned()))
+        })
+        .collect()
+}
+
+/// Whether `word` appears in `text` as a whole identifier, rather than as part of a longer one
+fn contains_word(text: &str, word: &str) -> bool {
+    let is_identifier = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    text.match_indices(word).any(|(start, _)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + word.len()..].chars().next();
+        !before.is_some_and(is_identifier) && !after.is_some_and(is_identifier)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shaderpack::optifine::program_source::*;
+
+    const FRAGMENT_SHADER: &str = "#version 120\n\
+        #include \"/lib/common.glsl\"\n\
+        uniform sampler2D colortex0; // The lit scene\n\
+        uniform lowp sampler2D gaux1, depthtex0;\n\
+        uniform sampler2DShadow shadow;\n\
+        uniform float frameTimeCounter;\n\
+        /* const int colortex0Format = RGBA16F; */\n\
+        void main() {\n\
+            gl_FragData[0] = texture2D(colortex0, vec2(0.0));\n\
+            gl_FragData[2] = vec4(1.0);\n\
+        }\n\
+        /* DRAWBUFFERS:02 */\n";
+
+    #[test]
+    fn finds_what_optifine_reads_from_a_shader() {
+        assert_eq!(draw_buffers(FRAGMENT_SHADER), Some(vec![0, 2]));
+        assert_eq!(draw_buffers("/* RENDERTARGETS: 1,12 */"), Some(vec![1, 12]));
+        assert_eq!(draw_buffers("void main() {}"), None);
+        assert_eq!(frag_data_outputs(FRAGMENT_SHADER), vec![0, 2]);
+
+        let samplers: Vec<_> = sampler_uniforms(FRAGMENT_SHADER)
+            .into_iter()
+            .map(|(line, name)| format!("{}:{}", line, name))
+            .collect();
+        assert_eq!(samplers, vec!["3:colortex0", "4:gaux1", "4:depthtex0", "5:shadow"]);
+
+        assert_eq!(
+            int_constants(FRAGMENT_SHADER),
+            vec![(7, "colortex0Format".to_owned(), "RGBA16F".to_owned())]
+        );
+        assert_eq!(includes(FRAGMENT_SHADER), vec![(2, "/lib/common.glsl".to_owned())]);
+    }
+
+    #[test]
+    fn finds_vertex_attributes() {
+        let fields = vertex_fields(
+            "attribute vec4 mc_Entity;\n\
+             void main() { gl_Position = ftransform(); color = gl_Color; uv = gl_MultiTexCoord0.xy; }",
+        );
+        let fields: Vec<_> = fields.iter().map(|field| field.field.clone()).collect();
+        assert_eq!(
+            fields,
+            vec![
+                VertexField::Position,
+                VertexField::Color,
+                VertexField::UV0,
+                VertexField::McEntityId
+            ]
+        );
+    }
+}
@@ -0,0 +1,294 @@
+//! The programs that Optifine runs each frame, and the names it gives to textures
+
+/// A program that Optifine uses to draw geometry
+pub(crate) struct GbuffersProgram {
+    pub name: &'static str,
+
+    /// The program that Optifine uses instead if a shaderpack doesn't have this one
+    pub fallback: Option<&'static str>,
+
+    /// A geometry filter that selects the geometry that this program draws
+    pub filter: &'static str,
+
+    /// Whether this program draws translucent geometry. Translucent geometry is drawn after the deferred programs
+    pub translucent: bool,
+}
+
+/// The gbuffers programs that Nova can import, in the order that Optifine draws them
+pub(crate) const GBUFFERS_PROGRAMS: &[GbuffersProgram] = &[
+    GbuffersProgram {
+        name: "gbuffers_basic",
+        fallback: None,
+        filter: "geometry_type::selection_box",
+        translucent: false,
+    },
+    GbuffersProgram {
+        name: "gbuffers_textured",
+        fallback: Some("gbuffers_basic"),
+        filter: "geometry_type::particle",
+        translucent: false,
+    },
+    GbuffersProgram {
+        name: "gbuffers_textured_lit",
+        fallback: Some("gbuffers_textured"),
+        filter: "geometry_type::lit_particle",
+        translucent: false,
+    },
+    GbuffersProgram {
+        name: "gbuffers_skybasic",
+        fallback: Some("gbuffers_basic"),
+        filter: "geometry_type::sky_decoration AND NOT (name::sun OR name::moon)",
+        translucent: false,
+    },
+    GbuffersProgram {
+        name: "gbuffers_skytextured",
+        fallback: Some("gbuffers_textured"),
+        filter: "geometry_type::sky_decoration AND (name::sun OR name::moon)",
+        translucent: false,
+    },
+    GbuffersProgram {
+        name: "gbuffers_clouds",
+        fallback: Some("gbuffers_textured"),
+        filter: "geometry_type::cloud",
+        translucent: false,
+    },
+    GbuffersProgram {
+        name: "gbuffers_terrain",
+        fallback: Some("gbuffers_textured_lit"),
+        filter: "(geometry_type::block OR geometry_type::falling_block) AND NOT transparent",
+        translucent: false,
+    },
+    GbuffersProgram {
+        name: "gbuffers_damagedblock",
+        fallback: Some("gbuffers_terrain"),
+        filter: "name_part::destroy_stage",
+        translucent: false,
+    },
+    GbuffersProgram {
+        name: "gbuffers_beaconbeam",
+        fallback: Some("gbuffers_textured"),
+        filter: "name::beacon_beam",
+        translucent: false,
+    },
+    GbuffersProgram {
+        name: "gbuffers_entities",
+        fallback: Some("gbuffers_textured_lit"),
+        filter: "geometry_type::entity",
+        translucent: false,
+    },
+    GbuffersProgram {
+        name: "gbuffers_armor_glint",
+        fallback: Some("gbuffers_textured"),
+        filter: "geometry_type::glint",
+        translucent: false,
+    },
+    GbuffersProgram {
+        name: "gbuffers_spidereyes",
+        fallback: Some("gbuffers_textured"),
+        filter: "geometry_type::eyes",
+        translucent: false,
+    },
+    GbuffersProgram {
+        name: "gbuffers_hand",
+        fallback: Some("gbuffers_textured_lit"),
+        filter: "geometry_type::hand AND NOT transparent",
+        translucent: false,
+    },
+    GbuffersProgram {
+        name: "gbuffers_water",
+        fallback: Some("gbuffers_terrain"),
+        filter: "(geometry_type::block OR geometry_type::falling_block) AND transparent",
+        translucent: true,
+    },
+    GbuffersProgram {
+        name: "gbuffers_hand_water",
+        fallback: Some("gbuffers_hand"),
+        filter: "geometry_type::hand AND transparent",
+        translucent: true,
+    },
+    GbuffersProgram {
+        name: "gbuffers_weather",
+        fallback: Some("gbuffers_textured_lit"),
+        filter: "geometry_type::weather",
+        translucent: true,
+    },
+];
+
+/// The program that draws the shadow map
+pub(crate) const SHADOW_PROGRAM: &str = "shadow";
+
+/// The geometry that the shadow program draws
+pub(crate) const SHADOW_FILTER: &str = "geometry_type::block OR geometry_type::falling_block OR geometry_type::entity";
+
+/// The program that draws to the screen
+pub(crate) const FINAL_PROGRAM: &str = "final";
+
+/// The geometry that fullscreen programs like `composite` draw
+pub(crate) const FULLSCREEN_FILTER: &str = "geometry_type::fullscreen_quad";
+
+/// Optifine runs `deferred`, `deferred1` ... `deferred15`, and the same for `composite`
+const FULLSCREEN_PROGRAM_COUNT: usize = 16;
+
+/// The names of a numbered series of fullscreen programs, such as `composite`, `composite1` ... `composite15`
+///
+/// # Parameters
+///
+/// * `prefix` - The name of the first program in the series
+pub(crate) fn numbered_programs(prefix: &str) -> Vec<String> {
+    (0..FULLSCREEN_PROGRAM_COUNT)
+        .map(|index| {
+            if index == 0 {
+                prefix.to_owned()
+            } else {
+                format!("{}{}", prefix, index)
+            }
+        })
+        .collect()
+}
+
+/// Whether Nova knows when to run the program with this name
+///
+/// # Parameters
+///
+/// * `name` - The name of the program, such as `gbuffers_terrain`
+pub(crate) fn is_known_program(name: &str) -> bool {
+    name == SHADOW_PROGRAM
+        || name == FINAL_PROGRAM
+        || GBUFFERS_PROGRAMS.iter().any(|program| program.name == name)
+        || numbered_programs("deferred").iter().any(|program| program == name)
+        || numbered_programs("composite").iter().any(|program| program == name)
+}
+
+/// The old names that Optifine still accepts for `colortex0` through `colortex7`
+const BUFFER_ALIASES: &[(&str, &str)] = &[
+    ("gcolor", "colortex0"),
+    ("gdepth", "colortex1"),
+    ("gnormal", "colortex2"),
+    ("composite", "colortex3"),
+    ("gaux1", "colortex4"),
+    ("gaux2", "colortex5"),
+    ("gaux3", "colortex6"),
+    ("gaux4", "colortex7"),
+    ("shadowcolor", "shadowcolor0"),
+    ("shadow", "shadowtex0"),
+];
+
+/// Depth buffers that Optifine copies partway through the frame, the depth buffer that Nova reads in their place, and
+/// what the copy leaves out. Nova doesn't copy depth buffers, so these see everything that's drawn to the depth buffer
+/// they're read from
+const DEPTH_COPIES: &[(&str, &str, &str)] = &[
+    ("depthtex1", "depthtex0", "translucent geometry"),
+    ("depthtex2", "depthtex0", "translucent geometry and the hand"),
+    ("shadowtex1", "shadowtex0", "translucent geometry"),
+];
+
+/// The textures that Nova provides, and the Optifine samplers that they replace
+const BUILTIN_SAMPLERS: &[(&str, &str)] = &[
+    ("texture", "ColorVirtualTexture"),
+    ("tex", "ColorVirtualTexture"),
+    ("gtexture", "ColorVirtualTexture"),
+    ("normals", "NormalVirtualTexture"),
+    ("specular", "DataVirtualTexture"),
+    ("lightmap", "Lightmap"),
+];
+
+/// Finds the name that Nova uses for one of Optifine's render targets
+///
+/// Render targets keep their Optifine names, except that old aliases like `gaux1` are replaced by the current name,
+/// and copies of a depth buffer like `depthtex1` are replaced by the depth buffer. See `depth_copy`
+///
+/// # Parameters
+///
+/// * `name` - The Optifine name of the render target
+pub(crate) fn render_target_name(name: &str) -> Option<String> {
+    if let Some((_, target)) = BUFFER_ALIASES.iter().find(|(alias, _)| *alias == name) {
+        return Some((*target).to_owned());
+    }
+    if let Some((target, _)) = depth_copy(name) {
+        return Some(target.to_owned());
+    }
+
+    let numbered = [("colortex", 16), ("shadowcolor", 2), ("shadowtex", 1), ("depthtex", 1)];
+    numbered
+        .iter()
+        .find(|(prefix, count)| {
+            name.starts_with(prefix) && name[prefix.len()..].parse::<usize>().is_ok_and(|index| index < *count)
+        })
+        .map(|_| name.to_owned())
+}
+
+/// Finds the depth buffer that Nova reads in place of one of Optifine's copies of a depth buffer, and what Optifine's
+/// copy leaves out that Nova's doesn't, as (depth buffer, what's left out)
+///
+/// # Parameters
+///
+/// * `name` - The Optifine name of the copy, such as `depthtex1`
+pub(crate) fn depth_copy(name: &str) -> Option<(&'static str, &'static str)> {
+    DEPTH_COPIES
+        .iter()
+        .find(|(copy, _, _)| *copy == name)
+        .map(|(_, target, left_out)| (*target, *left_out))
+}
+
+/// Finds the resource that Nova binds to a sampler uniform in an Optifine shader
+///
+/// # Parameters
+///
+/// * `sampler` - The name of the sampler uniform
+pub(crate) fn sampler_resource(sampler: &str) -> Option<String> {
+    BUILTIN_SAMPLERS
+        .iter()
+        .find(|(name, _)| *name == sampler)
+        .map(|(_, resource)| (*resource).to_owned())
+        .or_else(|| render_target_name(sampler))
+}
+
+/// Whether a resource is one of the render targets that an imported shaderpack declares, rather than a texture that
+/// Nova provides
+///
+/// # Parameters
+///
+/// * `resource` - The Nova name of the resource
+pub(crate) fn is_render_target(resource: &str) -> bool {
+    render_target_name(resource).as_deref() == Some(resource)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shaderpack::optifine::programs::*;
+    use crate::shaderpack::*;
+
+    #[test]
+    fn every_fallback_is_an_earlier_program() {
+        for (index, program) in GBUFFERS_PROGRAMS.iter().enumerate() {
+            if let Some(fallback) = program.fallback {
+                let fallback_index = GBUFFERS_PROGRAMS.iter().position(|other| other.name == fallback);
+                assert!(fallback_index.is_some_and(|fallback_index| fallback_index < index));
+            }
+            assert!(GeometryFilter::parse(program.filter).is_ok());
+        }
+    }
+
+    #[test]
+    fn maps_optifine_names_to_nova_resources() {
+        assert_eq!(sampler_resource("gaux2").unwrap(), "colortex5");
+        assert_eq!(sampler_resource("colortex12").unwrap(), "colortex12");
+        assert_eq!(sampler_resource("shadow").unwrap(), "shadowtex0");
+        assert_eq!(sampler_resource("lightmap").unwrap(), "Lightmap");
+        assert_eq!(sampler_resource("depthtex1").unwrap(), "depthtex0");
+        assert_eq!(sampler_resource("depthtex2").unwrap(), "depthtex0");
+        assert_eq!(sampler_resource("shadowtex1").unwrap(), "shadowtex0");
+        assert_eq!(
+            depth_copy("depthtex2"),
+            Some(("depthtex0", "translucent geometry and the hand"))
+        );
+        assert_eq!(sampler_resource("colortex16"), None);
+        assert_eq!(sampler_resource("depthtex3"), None);
+        assert_eq!(sampler_resource("shadowtex2"), None);
+        assert_eq!(sampler_resource("noisetex"), None);
+        assert!(is_render_target("depthtex0"));
+        assert!(!is_render_target("gcolor"));
+        assert!(!is_render_target("depthtex1"));
+        assert!(!is_render_target("Lightmap"));
+    }
+}
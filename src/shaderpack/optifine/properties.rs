@@ -0,0 +1,128 @@
+//! Reading Optifine's `shaders.properties` file
+
+/// One `key=value` entry in a `shaders.properties` file
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ShaderProperty {
+    pub key: String,
+    pub value: String,
+
+    /// The line that the entry starts on, starting at 1
+    pub line: usize,
+}
+
+/// The contents of a `shaders.properties` file
+///
+/// The file is in Java's properties format: one `key=value` entry per line, with lines that end in `\` continuing on
+/// the next line. Lines that start with `#` or `!` are comments, except that Optifine treats preprocessor directives
+/// like `#ifdef` as conditions. Nova doesn't evaluate those conditions, so it reads every entry and keeps track of the
+/// directives so that they can be reported
+#[derive(Debug, Clone, Default)]
+pub struct ShaderProperties {
+    /// Every entry in the file, in the order that they're written
+    pub properties: Vec<ShaderProperty>,
+
+    /// The preprocessor directives in the file, as (line, directive)
+    pub directives: Vec<(usize, String)>,
+}
+
+const DIRECTIVES: &[&str] = &[
+    "#if", "#ifdef", "#ifndef", "#elif", "#else", "#endif", "#define", "#undef",
+];
+
+impl ShaderProperties {
+    /// Parses the text of a `shaders.properties` file
+    ///
+    /// # Parameters
+    ///
+    /// * `text` - The text of the file
+    pub fn parse(text: &str) -> ShaderProperties {
+        let mut properties = ShaderProperties::default();
+        let mut lines = text.lines().enumerate();
+        while let Some((index, line)) = lines.next() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if line.starts_with('#') || line.starts_with('!') {
+                let word = line.split_whitespace().next().unwrap_or("");
+                if DIRECTIVES.contains(&word) {
+                    properties.directives.push((index + 1, line.to_owned()));
+                }
+                continue;
+            }
+
+            let mut entry = line.to_owned();
+            while entry.ends_with('\\') {
+                entry.pop();
+                match lines.next() {
+                    Some((_, next_line)) => entry.push_str(next_line.trim_start()),
+                    None => break,
+                }
+            }
+
+            let (key, value) = match entry.find(['=', ':']) {
+                Some(separator) => (&entry[..separator], &entry[separator + 1..]),
+                None => (entry.as_str(), ""),
+            };
+            properties.properties.push(ShaderProperty {
+                key: key.trim().to_owned(),
+                value: value.trim().to_owned(),
+                line: index + 1,
+            });
+        }
+
+        properties
+    }
+
+    /// Finds an entry by its key. If the key is in the file more than once, the last entry wins, like in Java
+    ///
+    /// # Parameters
+    ///
+    /// * `key` - The key to find
+    pub fn get(&self, key: &str) -> Option<&ShaderProperty> {
+        self.properties.iter().rev().find(|property| property.key == key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shaderpack::*;
+
+    #[test]
+    fn parses_entries_continuations_and_directives() {
+        let properties = ShaderProperties::parse(
+            "# A comment\n\
+             blend.gbuffers_water = SRC_ALPHA ONE_MINUS_SRC_ALPHA ONE ONE\n\
+             sliders=SUN_ANGLE \\\n    SHADOW_DISTANCE\n\
+             #ifdef BLOOM\n\
+             program.composite1.enabled=true\n\
+             #endif\n\
+             program.composite1.enabled=false\n",
+        );
+
+        let keys: Vec<_> = properties
+            .properties
+            .iter()
+            .map(|property| property.key.as_str())
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                "blend.gbuffers_water",
+                "sliders",
+                "program.composite1.enabled",
+                "program.composite1.enabled"
+            ]
+        );
+        assert_eq!(properties.properties[1].value, "SUN_ANGLE SHADOW_DISTANCE");
+        assert_eq!(properties.properties[1].line, 3);
+
+        let enabled = properties.get("program.composite1.enabled").unwrap();
+        assert_eq!((enabled.value.as_str(), enabled.line), ("false", 8));
+        assert_eq!(
+            properties.directives,
+            vec![(5, "#ifdef BLOOM".to_owned()), (7, "#endif".to_owned())]
+        );
+    }
+}
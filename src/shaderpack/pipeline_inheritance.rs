@@ -0,0 +1,317 @@
+//! Resolving the inheritance between a shaderpack's pipelines
+
+use crate::shaderpack::*;
+use std::collections::HashMap;
+
+enum Resolution {
+    Pending,
+    Resolved(Box<PipelineCreationInfo>),
+    /// The pipeline can't be resolved. The error has already been reported
+    Failed,
+}
+
+impl ShaderpackData {
+    /// Makes every pipeline inherit from its parent, its parent's parent, and so on
+    ///
+    /// See `PipelineCreationInfo::merge_with_parent` for what's inherited. Pipelines keep their `parent`, so that
+    /// tools can still show where a pipeline's state came from
+    ///
+    /// The pipelines are only changed if every one of them could be resolved. Pipeline names must be unique, which
+    /// `load_nova_shaderpack` checks before resolving inheritance. If two pipelines have the same name, children
+    /// inherit from the first one
+    ///
+    /// # Errors
+    ///
+    /// Returns every pipeline whose parent doesn't exist, and every cycle of pipelines that inherit from each other.
+    /// Each problem is reported once, even if many pipelines inherit from a pipeline with the problem
+    ///
+    /// If inheritance can be resolved, returns every pipeline that still doesn't have a pass or a vertex shader
+    ///
+    /// # Parameters
+    ///
+    /// * `locations` - Where each pipeline is declared, in the same order as `pipelines`. Errors about a pipeline point
+    ///   at its declaration
+    pub fn resolve_pipeline_inheritance(&mut self, locations: &[JsonLocation]) -> Result<(), Vec<ShaderpackLoadError>> {
+        let indices: HashMap<&str, usize> = self
+            .pipelines
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(index, pipeline)| (pipeline.name.as_str(), index))
+            .collect();
+
+        let mut resolutions: Vec<Resolution> = self.pipelines.iter().map(|_| Resolution::Pending).collect();
+        let mut errors = Vec::new();
+
+        for start in 0..self.pipelines.len() {
+            if !matches!(resolutions[start], Resolution::Pending) {
+                continue;
+            }
+
+            // Walk up the chain of parents until reaching a pipeline without a parent or one that's already been
+            // looked at
+            let mut chain = vec![start];
+            let mut failed = false;
+            let mut ancestor = None;
+            while let Some(parent_name) = &self.pipelines[*chain.last().unwrap()].parent {
+                let parent = match indices.get(parent_name.as_str()) {
+                    Some(parent) => *parent,
+                    None => {
+                        errors.push(ShaderpackLoadError::UnknownParentPipeline {
+                            location: locations[*chain.last().unwrap()].clone(),
+                            pipeline: self.pipelines[*chain.last().unwrap()].name.clone(),
+                            parent: parent_name.clone(),
+                        });
+                        failed = true;
+                        break;
+                    }
+                };
+
+                if let Some(position) = chain.iter().position(|index| *index == parent) {
+                    errors.push(ShaderpackLoadError::PipelineInheritanceCycle {
+                        location: locations[parent].clone(),
+                        pipelines: chain[position..]
+                            .iter()
+                            .map(|index| self.pipelines[*index].name.clone())
+                            .collect(),
+                    });
+                    failed = true;
+                    break;
+                }
+
+                match &resolutions[parent] {
+                    Resolution::Pending => chain.push(parent),
+                    Resolution::Resolved(resolved) => {
+                        ancestor = Some(resolved.as_ref().clone());
+                        break;
+                    }
+                    Resolution::Failed => {
+                        failed = true;
+                        break;
+                    }
+                }
+            }
+
+            // Then walk back down, with each pipeline inheriting from the one above it
+            for index in chain.into_iter().rev() {
+                resolutions[index] = if failed {
+                    Resolution::Failed
+                } else {
+                    let resolved = match &ancestor {
+                        Some(parent) => self.pipelines[index].merge_with_parent(parent),
+                        None => self.pipelines[index].clone(),
+                    };
+                    ancestor = Some(resolved.clone());
+                    Resolution::Resolved(Box::new(resolved))
+                };
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let pipelines: Vec<PipelineCreationInfo> = resolutions
+            .into_iter()
+            .filter_map(|resolution| match resolution {
+                Resolution::Resolved(pipeline) => Some(*pipeline),
+                _ => None,
+            })
+            .collect();
+
+        // Pipelines may leave these out so that they can inherit them, but something has to set them
+        for (pipeline, location) in pipelines.iter().zip(locations) {
+            if pipeline.pass.is_empty() {
+                errors.push(missing_field(pipeline, location, "pass"));
+            }
+            if pipeline.vertex_shader.filename.as_os_str().is_empty() {
+                errors.push(missing_field(pipeline, location, "vertexShader"));
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        self.pipelines = pipelines;
+        Ok(())
+    }
+}
+
+fn missing_field(pipeline: &PipelineCreationInfo, location: &JsonLocation, field: &str) -> ShaderpackLoadError {
+    ShaderpackLoadError::MissingPipelineField {
+        location: location.clone(),
+        pipeline: pipeline.name.clone(),
+        field: field.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shaderpack::*;
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    fn pipeline(json: &str) -> PipelineCreationInfo {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn shaderpack(pipelines: Vec<PipelineCreationInfo>) -> ShaderpackData {
+        ShaderpackData {
+            pipelines,
+            ..ShaderpackData::default()
+        }
+    }
+
+    /// Resolves inheritance as if each pipeline was declared in its own file, named after the pipeline
+    fn resolve(data: &mut ShaderpackData) -> Result<(), Vec<ShaderpackLoadError>> {
+        let locations: Vec<_> = data
+            .pipelines
+            .iter()
+            .map(|pipeline| JsonLocation {
+                file: PathBuf::from(format!("materials/{}.pipeline", pipeline.name)),
+                json_path: ".".to_owned(),
+                line: 1,
+                column: 1,
+            })
+            .collect();
+        data.resolve_pipeline_inheritance(&locations)
+    }
+
+    #[test]
+    fn inherits_through_chains() {
+        let mut data = shaderpack(vec![
+            pipeline(
+                r#"{ "name": "gbuffers_textured_lit", "parent": "gbuffers_textured",
+                     "defines": ["LIT", "TEXTURED"], "depthFunc": "LessEqual" }"#,
+            ),
+            pipeline(
+                r#"{ "name": "gbuffers_basic", "pass": "Forward", "vertexShader": "basic.vert",
+                     "fragmentShader": "basic.frag", "defines": ["BASIC"], "states": ["DisableCulling"],
+                     "vertexFields": [{ "name": "position", "field": "Position" }],
+                     "frontFace": { "passOp": "Replace" }, "destinationBlendFactor": "OneMinusSrcAlpha" }"#,
+            ),
+            pipeline(
+                r#"{ "name": "gbuffers_textured", "parent": "gbuffers_basic", "fragmentShader": "textured.frag",
+                     "defines": ["TEXTURED"], "states": ["Blending"] }"#,
+            ),
+        ]);
+
+        resolve(&mut data).unwrap();
+
+        let lit = &data.pipelines[0];
+        assert_eq!(lit.name, "gbuffers_textured_lit");
+        assert_eq!(lit.parent.as_ref().unwrap(), "gbuffers_textured");
+        assert_eq!(lit.pass, "Forward");
+        assert_eq!(lit.defines, vec!["BASIC", "TEXTURED", "LIT"]);
+        assert_eq!(
+            lit.states,
+            vec![RasterizerState::DisableCulling, RasterizerState::Blending]
+        );
+        assert_eq!(lit.vertex_fields.len(), 1);
+        assert_eq!(lit.front_face.as_ref().unwrap().pass_op, StencilOp::Replace);
+        assert_eq!(lit.dst_blend_factor, Some(BlendFactor::OneMinusSrcAlpha));
+        assert_eq!(lit.depth_func, Some(CompareOp::LessEqual));
+        assert_eq!(lit.vertex_shader.filename, Path::new("basic.vert"));
+        assert_eq!(
+            lit.fragment_shader.as_ref().unwrap().filename,
+            Path::new("textured.frag")
+        );
+
+        assert_eq!(data.pipelines[1].defines, vec!["BASIC"]);
+        assert_eq!(data.pipelines[2].defines, vec!["BASIC", "TEXTURED"]);
+    }
+
+    #[test]
+    fn children_can_set_default_values() {
+        let mut data = shaderpack(vec![
+            pipeline(
+                r#"{ "name": "water", "pass": "Forward", "vertexShader": "water.vert", "depthBias": 2.0,
+                     "stencilRef": 1, "msaaSupport": "Both", "sourceBlendFactor": "SrcAlpha" }"#,
+            ),
+            pipeline(
+                r#"{ "name": "water_opaque", "parent": "water", "depthBias": 0.0, "stencilRef": 0,
+                     "msaaSupport": "None", "sourceBlendFactor": "One" }"#,
+            ),
+            pipeline(r#"{ "name": "water_lit", "parent": "water" }"#),
+        ]);
+
+        resolve(&mut data).unwrap();
+
+        let opaque = &data.pipelines[1];
+        assert_eq!(opaque.depth_bias, Some(0.0));
+        assert_eq!(opaque.stencil_ref, Some(0));
+        assert_eq!(opaque.msaa_support, Some(MSAASupport::None));
+        assert_eq!(opaque.src_blend_factor, Some(BlendFactor::One));
+
+        let lit = &data.pipelines[2];
+        assert_eq!(lit.depth_bias, Some(2.0));
+        assert_eq!(lit.stencil_ref, Some(1));
+        assert_eq!(lit.msaa_support, Some(MSAASupport::Both));
+        assert_eq!(lit.src_blend_factor, Some(BlendFactor::SrcAlpha));
+        assert_eq!(lit.dst_blend_factor, None);
+    }
+
+    #[test]
+    fn children_replace_defines_of_the_same_symbol() {
+        let mut data = shaderpack(vec![
+            pipeline(
+                r#"{ "name": "terrain", "pass": "Forward", "vertexShader": "terrain.vert",
+                     "defines": ["QUALITY=1", "SHADOWS", "WAVES=2"] }"#,
+            ),
+            pipeline(r#"{ "name": "terrain_high", "parent": "terrain", "defines": ["QUALITY=2", "BLOOM"] }"#),
+            pipeline(r#"{ "name": "terrain_flat", "parent": "terrain_high", "defines": ["!SHADOWS", "!WAVES"] }"#),
+        ]);
+
+        resolve(&mut data).unwrap();
+
+        assert_eq!(
+            data.pipelines[1].defines,
+            vec!["QUALITY=2", "SHADOWS", "WAVES=2", "BLOOM"]
+        );
+        assert_eq!(
+            data.pipelines[2].defines,
+            vec!["QUALITY=2", "!SHADOWS", "!WAVES", "BLOOM"]
+        );
+    }
+
+    #[test]
+    fn reports_cycles_and_unknown_parents() {
+        let mut data = shaderpack(vec![
+            pipeline(r#"{ "name": "a", "parent": "b" }"#),
+            pipeline(r#"{ "name": "b", "parent": "c" }"#),
+            pipeline(r#"{ "name": "c", "parent": "a" }"#),
+            pipeline(r#"{ "name": "d", "parent": "a" }"#),
+            pipeline(r#"{ "name": "e", "parent": "missing" }"#),
+            pipeline(r#"{ "name": "f", "parent": "e" }"#),
+            pipeline(r#"{ "name": "g", "parent": "g" }"#),
+        ]);
+
+        let errors = resolve(&mut data).unwrap_err();
+        assert_eq!(errors.len(), 3);
+        match &errors[0] {
+            ShaderpackLoadError::PipelineInheritanceCycle { location, pipelines } => {
+                assert_eq!(location.file, Path::new("materials/a.pipeline"));
+                assert_eq!(pipelines, &["a", "b", "c"]);
+            }
+            other => panic!("Expected PipelineInheritanceCycle, got {:?}", other),
+        }
+        match &errors[1] {
+            ShaderpackLoadError::UnknownParentPipeline {
+                location,
+                pipeline,
+                parent,
+            } => {
+                assert_eq!(location.file, Path::new("materials/e.pipeline"));
+                assert_eq!(pipeline, "e");
+                assert_eq!(parent, "missing");
+            }
+            other => panic!("Expected UnknownParentPipeline, got {:?}", other),
+        }
+        match &errors[2] {
+            ShaderpackLoadError::PipelineInheritanceCycle { pipelines, .. } => assert_eq!(pipelines, &["g"]),
+            other => panic!("Expected PipelineInheritanceCycle, got {:?}", other),
+        }
+
+        assert!(data.pipelines[1].pass.is_empty());
+    }
+}
@@ -1,78 +1,250 @@
 //! Structs that represent shaderpack data
+//!
+//! Most of these structs can be deserialized from the JSON files in a Nova shaderpack. Fields that a shaderpack may
+//! leave out are documented with the value they default to
 
-use std::{collections::HashMap, path::PathBuf};
+use cgmath::Vector2;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ShaderpackData {
-    pipelines: Vec<PipelineCreationInfo>,
+    pub pipelines: Vec<PipelineCreationInfo>,
     /// All the renderpasses that this shaderpack needs, in submission order
-    passes: Vec<RenderPassCreationInfo>,
-    materials: Vec<MaterialData>,
-    resources: ShaderpackResourceData,
+    pub passes: Vec<RenderPassCreationInfo>,
+    pub materials: Vec<MaterialData>,
+    pub resources: ShaderpackResourceData,
 }
 
-#[derive(Debug, Clone)]
+/// A pipeline, loaded from a `.pipeline` file in the shaderpack's `materials` folder
+///
+/// Optional fields that are documented with a default are `None` when neither the pipeline nor its parents set them,
+/// so that a pipeline can tell a value it sets apart from one it inherits
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct PipelineCreationInfo {
     /// The name of this pipeline
-    name: String,
+    pub name: String,
     /// The pipeline that this pipeline inherits from
-    parent: Option<String>,
+    #[serde(default)]
+    pub parent: Option<String>,
     /// The name of the pass that this pipeline belongs to
-    pass: String,
+    ///
+    /// May only be left out if this pipeline inherits it from its parent
+    #[serde(default)]
+    pub pass: String,
     /// All of the symbols in the shader that are defined by this state
-    defines: Vec<String>,
+    ///
+    /// `NAME` defines a symbol, and `NAME=VALUE` gives it a value. `!NAME` makes sure that a symbol isn't defined,
+    /// even if the shader defines it itself
+    #[serde(default)]
+    pub defines: Vec<String>,
     /// Defines the rasterizer state that's active for this pipeline
-    states: Vec<RasterizerState>,
+    #[serde(default)]
+    pub states: Vec<RasterizerState>,
     /// Sets up the vertex fields that Nova will bind to this pipeline
-    vertex_fields: Vec<VertexFieldData>,
+    #[serde(default)]
+    pub vertex_fields: Vec<VertexFieldData>,
     /// The stencil buffer operations to perform on the front faces
-    front_face: Option<StencilOpState>,
+    #[serde(default)]
+    pub front_face: Option<StencilOpState>,
     /// The stencil buffer operations to perform on the back faces
-    back_face: Option<StencilOpState>,
+    #[serde(default)]
+    pub back_face: Option<StencilOpState>,
     /// The material to use if this one's shaders can't be found
-    fallback: Option<String>,
+    #[serde(default)]
+    pub fallback: Option<String>,
     /// A bias to apply to the depth
-    depth_bias: f32,
+    ///
+    /// Defaults to 0
+    #[serde(default)]
+    pub depth_bias: Option<f32>,
     /// The depth bias, scaled by slope I guess?
-    slope_scaled_depth_bias: f32,
+    ///
+    /// Defaults to 0
+    #[serde(default)]
+    pub slope_scaled_depth_bias: Option<f32>,
     /// The reference value to use for the stencil test
-    stencil_ref: u32,
+    ///
+    /// Defaults to 0
+    #[serde(default)]
+    pub stencil_ref: Option<u32>,
     /// The mask to use when reading from the stencil buffer
-    stencil_read_mask: u32,
+    ///
+    /// Defaults to 0
+    #[serde(default)]
+    pub stencil_read_mask: Option<u32>,
     /// The mask to use when writing to the stencil buffer
-    stencil_write_mask: u32,
+    ///
+    /// Defaults to 0
+    #[serde(default)]
+    pub stencil_write_mask: Option<u32>,
     /// How to handle MSAA for this state
-    msaa_support: MSAASupport,
+    ///
+    /// Defaults to `MSAASupport::None`
+    #[serde(default)]
+    pub msaa_support: Option<MSAASupport>,
     /// Decides how the vertices are rendered
-    primitive_mode: PrimitiveTopology,
+    ///
+    /// Defaults to `PrimitiveTopology::Triangles`
+    #[serde(default)]
+    pub primitive_mode: Option<PrimitiveTopology>,
     /// Where to get the blending factor for the soource
-    src_blend_factor: BlendFactor,
+    ///
+    /// Defaults to `BlendFactor::One`
+    #[serde(default, rename = "sourceBlendFactor")]
+    pub src_blend_factor: Option<BlendFactor>,
     /// Where to get the blending factor for the destination
-    dst_blend_factor: BlendFactor,
+    ///
+    /// Defaults to `BlendFactor::Zero`
+    #[serde(default, rename = "destinationBlendFactor")]
+    pub dst_blend_factor: Option<BlendFactor>,
     /// How to get the source alpha in a blend
-    alpha_src: BlendFactor,
+    ///
+    /// Defaults to `BlendFactor::One`
+    #[serde(default)]
+    pub alpha_src: Option<BlendFactor>,
     /// How to get the destination alpha in a blend
-    alpha_dst: BlendFactor,
+    ///
+    /// Defaults to `BlendFactor::Zero`
+    #[serde(default)]
+    pub alpha_dst: Option<BlendFactor>,
     /// The function to use for the depth test
-    depth_func: CompareOp,
+    ///
+    /// Defaults to `CompareOp::Less`
+    #[serde(default)]
+    pub depth_func: Option<CompareOp>,
     /// The render queue that this pass belongs to
     /// This may or may not be removed depending on what is actually needed by Nova
-    render_queue: RenderQueue,
+    ///
+    /// Defaults to `RenderQueue::Opaque`
+    #[serde(default)]
+    pub render_queue: Option<RenderQueue>,
     /// Vertex shader to use
-    vertex_shader: ShaderSource,
+    ///
+    /// May only be left out if this pipeline inherits it from its parent
+    #[serde(default)]
+    pub vertex_shader: ShaderSource,
     /// Geometry shader to use
-    geometry_shader: Option<ShaderSource>,
+    #[serde(default)]
+    pub geometry_shader: Option<ShaderSource>,
     /// Tessellation Control shader to use
-    tessellation_control_shader: Option<ShaderSource>,
+    #[serde(default)]
+    pub tessellation_control_shader: Option<ShaderSource>,
     /// Tessellation Evaluation shader to use
-    tessellation_evaluation_shader: Option<ShaderSource>,
+    #[serde(default)]
+    pub tessellation_evaluation_shader: Option<ShaderSource>,
     /// Fragment shader to use
-    fragment_shader: Option<ShaderSource>,
+    #[serde(default)]
+    pub fragment_shader: Option<ShaderSource>,
 }
 
 impl PipelineCreationInfo {
-    pub fn merge_with_parent(&self, other: &PipelineCreationInfo) -> Self {
-        unimplemented!()
+    /// Makes a copy of this pipeline that inherits everything it doesn't set from its parent
+    ///
+    /// Inheritance follows these rules:
+    /// - `defines` are combined by the symbol they define. The parent's come first, and if both pipelines define the
+    ///   same symbol, this pipeline's define replaces the parent's. This lets a child give a symbol a new value, or
+    ///   undefine it with `!NAME`
+    /// - `states` are combined. The parent's come first, and duplicates are removed
+    /// - `vertex_fields` are inherited if this pipeline has none
+    /// - `pass`, `vertex_shader` and everything that's optional, such as the other shaders, the stencil operations and
+    ///   the blend factors, are inherited if this pipeline doesn't set them. A value that this pipeline sets is kept,
+    ///   even if it's the same as the default
+    ///
+    /// `name` and `parent` are never inherited
+    ///
+    /// # Parameters
+    ///
+    /// * `parent` - The pipeline to inherit from. It should have already inherited from its own parent
+    pub fn merge_with_parent(&self, parent: &PipelineCreationInfo) -> Self {
+        fn combine<T: Clone + PartialEq>(parent: &[T], child: &[T]) -> Vec<T> {
+            let mut combined: Vec<T> = Vec::new();
+            for value in parent.iter().chain(child) {
+                if !combined.contains(value) {
+                    combined.push(value.clone());
+                }
+            }
+            combined
+        }
+
+        fn combine_defines(parent: &[String], child: &[String]) -> Vec<String> {
+            let mut combined: Vec<String> = Vec::new();
+            for define in parent.iter().chain(child) {
+                let symbol = define_symbol(define);
+                match combined.iter_mut().find(|existing| define_symbol(existing) == symbol) {
+                    Some(existing) => *existing = define.clone(),
+                    None => combined.push(define.clone()),
+                }
+            }
+            combined
+        }
+
+        fn inherit<T: Clone>(parent: &Option<T>, child: &Option<T>) -> Option<T> {
+            child.clone().or_else(|| parent.clone())
+        }
+
+        PipelineCreationInfo {
+            name: self.name.clone(),
+            parent: self.parent.clone(),
+            pass: if self.pass.is_empty() {
+                parent.pass.clone()
+            } else {
+                self.pass.clone()
+            },
+            defines: combine_defines(&parent.defines, &self.defines),
+            states: combine(&parent.states, &self.states),
+            vertex_fields: if self.vertex_fields.is_empty() {
+                parent.vertex_fields.clone()
+            } else {
+                self.vertex_fields.clone()
+            },
+            front_face: inherit(&parent.front_face, &self.front_face),
+            back_face: inherit(&parent.back_face, &self.back_face),
+            fallback: inherit(&parent.fallback, &self.fallback),
+            depth_bias: inherit(&parent.depth_bias, &self.depth_bias),
+            slope_scaled_depth_bias: inherit(&parent.slope_scaled_depth_bias, &self.slope_scaled_depth_bias),
+            stencil_ref: inherit(&parent.stencil_ref, &self.stencil_ref),
+            stencil_read_mask: inherit(&parent.stencil_read_mask, &self.stencil_read_mask),
+            stencil_write_mask: inherit(&parent.stencil_write_mask, &self.stencil_write_mask),
+            msaa_support: inherit(&parent.msaa_support, &self.msaa_support),
+            primitive_mode: inherit(&parent.primitive_mode, &self.primitive_mode),
+            src_blend_factor: inherit(&parent.src_blend_factor, &self.src_blend_factor),
+            dst_blend_factor: inherit(&parent.dst_blend_factor, &self.dst_blend_factor),
+            alpha_src: inherit(&parent.alpha_src, &self.alpha_src),
+            alpha_dst: inherit(&parent.alpha_dst, &self.alpha_dst),
+            depth_func: inherit(&parent.depth_func, &self.depth_func),
+            render_queue: inherit(&parent.render_queue, &self.render_queue),
+            vertex_shader: if self.vertex_shader.filename.as_os_str().is_empty() {
+                parent.vertex_shader.clone()
+            } else {
+                self.vertex_shader.clone()
+            },
+            geometry_shader: inherit(&parent.geometry_shader, &self.geometry_shader),
+            tessellation_control_shader: inherit(
+                &parent.tessellation_control_shader,
+                &self.tessellation_control_shader,
+            ),
+            tessellation_evaluation_shader: inherit(
+                &parent.tessellation_evaluation_shader,
+                &self.tessellation_evaluation_shader,
+            ),
+            fragment_shader: inherit(&parent.fragment_shader, &self.fragment_shader),
+        }
+    }
+}
+
+/// Finds the symbol that one of a pipeline's defines is about, like `QUALITY` for `QUALITY`, `QUALITY=2` or `!QUALITY`
+///
+/// # Parameters
+///
+/// * `define` - The define, written like in `PipelineCreationInfo::defines`
+pub fn define_symbol(define: &str) -> &str {
+    let define = define.trim_start_matches('!');
+    match define.find('=') {
+        Some(separator) => &define[..separator],
+        None => define,
     }
 }
 
@@ -93,83 +265,136 @@ impl PipelineCreationInfo {
 /// change per frame, a UBO for per-model data like the model matrix, and the virtual texture atlases. The default
 /// resources.json file sets up sixteen framebuffer color attachments for ping-pong buffers, a depth attachment,
 /// some shadow maps, etc
-#[derive(Debug, Clone)]
+///
+/// Passes are loaded from the shaderpack's `passes.json` file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct RenderPassCreationInfo {
     /// The name of this render pass
-    name: String,
+    pub name: String,
     /// The materials that MUST execute before this one
-    dependencies: Vec<String>,
+    #[serde(default)]
+    pub dependencies: Vec<String>,
     /// The textures that this pass will read from
-    texture_inputs: Vec<String>,
+    #[serde(default)]
+    pub texture_inputs: Vec<String>,
     /// The textures that this pass will write to
-    texture_outputs: Vec<TextureAttachmentInfo>,
+    #[serde(default)]
+    pub texture_outputs: Vec<TextureAttachmentInfo>,
     /// The depth texture this pass will write to
-    depth_texture: Option<TextureAttachmentInfo>,
+    #[serde(default)]
+    pub depth_texture: Option<TextureAttachmentInfo>,
     /// All the buffers that this renderpass reads from
-    input_buffers: Vec<String>,
+    #[serde(default)]
+    pub input_buffers: Vec<String>,
     /// All the buffers that this renderpass writes to
-    output_buffers: Vec<String>,
+    #[serde(default)]
+    pub output_buffers: Vec<String>,
 }
 
-#[derive(Debug, Clone)]
+/// A material, loaded from a `.mat` file in the shaderpack's `materials` folder
+#[derive(Debug, Clone, Deserialize)]
 pub struct MaterialData {
-    name: String,
-    passes: Vec<MaterialPass>,
-    geometry_filter: String,
+    pub name: String,
+    pub passes: Vec<MaterialPass>,
+    /// Selects the objects that this material is used for
+    #[serde(default, rename = "filter")]
+    pub geometry_filter: String,
 }
 
-#[derive(Debug, Clone)]
+/// All the resources that a shaderpack declares in its `resources.json` file
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct ShaderpackResourceData {
-    textures: Vec<TextureCreateInfo>,
-    samplers: Vec<SamplerCreateInfo>,
+    #[serde(default)]
+    pub textures: Vec<TextureCreateInfo>,
+    #[serde(default)]
+    pub samplers: Vec<SamplerCreateInfo>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct VertexFieldData {
-    semantic_name: String,
-    field: VertexField,
+    #[serde(rename = "name")]
+    pub semantic_name: String,
+    pub field: VertexField,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct StencilOpState {
-    fail_op: StencilOp,
-    pass_op: StencilOp,
-    depth_fail_op: StencilOp,
-    compare_op: StencilOp,
-    compare_mask: u32,
-    write_mask: u32,
+    /// Defaults to `StencilOp::Keep`
+    #[serde(default)]
+    pub fail_op: StencilOp,
+    /// Defaults to `StencilOp::Keep`
+    #[serde(default)]
+    pub pass_op: StencilOp,
+    /// Defaults to `StencilOp::Keep`
+    #[serde(default)]
+    pub depth_fail_op: StencilOp,
+    /// Defaults to `CompareOp::Always`
+    #[serde(default = "CompareOp::always")]
+    pub compare_op: CompareOp,
+    /// Defaults to 0
+    #[serde(default)]
+    pub compare_mask: u32,
+    /// Defaults to 0
+    #[serde(default)]
+    pub write_mask: u32,
 }
 
-#[derive(Debug, Clone)]
+/// A shader that a pipeline uses
+///
+/// Shaderpacks only give the path to the shader's file. The SPIR-V is filled in when the shader is compiled
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(from = "PathBuf")]
 pub struct ShaderSource {
-    filename: PathBuf,
-    source: Vec<u32>,
+    pub filename: PathBuf,
+    pub source: Vec<u32>,
+}
+
+impl From<PathBuf> for ShaderSource {
+    fn from(filename: PathBuf) -> ShaderSource {
+        ShaderSource {
+            filename,
+            source: Vec::new(),
+        }
+    }
 }
 
 ///  A description of a texture that a render pass outputs to
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct TextureAttachmentInfo {
     ///  The name of the texture
-    name: String,
+    pub name: String,
     /// Pixel format of the texture
-    pixel_format: PixelFormat,
+    ///
+    /// Defaults to `PixelFormat::RGBA8`
+    #[serde(default)]
+    pub pixel_format: PixelFormat,
     ///  Whether to clear the texture
     ///
     /// If the texture is a depth buffer, it gets cleared to 1
     /// If the texture is a stencil buffer, it gets cleared to 0xFFFFFFFF
     /// If the texture is a color buffer, it gets cleared to (0, 0, 0, 0)
-    clear: bool,
+    ///
+    /// Defaults to false
+    #[serde(default)]
+    pub clear: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct MaterialPass {
-    name: String,
-    material_name: String,
-    pipeline: String,
-    bindings: HashMap<String, String>,
+    pub name: String,
+    /// The name of the material that this pass belongs to. Filled in when the material is loaded
+    #[serde(skip)]
+    pub material_name: String,
+    pub pipeline: String,
+    /// Maps the names of shader variables to the names of the resources to bind to them
+    #[serde(default)]
+    pub bindings: HashMap<String, String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct TextureCreateInfo {
     ///  The name of the texture
     ///
@@ -203,38 +428,77 @@ pub struct TextureCreateInfo {
     ///
     /// If you use one of the virtual textures, then all fields except the binding are ignored
     /// If you use `Backbuffer`, then all fields are ignored since the backbuffer is always bound to output location 0
-    name: String,
-    format: TextureFormat,
+    pub name: String,
+    pub format: TextureFormat,
 }
 
 ///  Defines a sampler to use for a texture
 ///
 /// At the time of writing I'm not sure how this is corellated with a texture, but all well
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct SamplerCreateInfo {
-    name: String,
+    pub name: String,
     ///  What kind of texture filter to use
     ///
     /// texel_aa does something that I don't want to figure out right now. Bilinear is your regular bilinear filter,
     /// and point is the point filter. Aniso isn't an option and I kinda hope it stays that way
-    filter: TextureFilter,
+    pub filter: TextureFilter,
     ///  How the texture should wrap at the edges
-    wrap_mode: WrapMode,
+    pub wrap_mode: WrapMode,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct TextureFormat {
     ///  The format of the texture
-    pixel_format: PixelFormat,
+    ///
+    /// Defaults to `PixelFormat::RGBA8`
+    #[serde(default)]
+    pub pixel_format: PixelFormat,
     ///  How to interpret the dimensions of this texture
-    dimension_type: TextureDimensionType,
+    ///
+    /// Defaults to `TextureDimensionType::ScreenRelative`
+    #[serde(default)]
+    pub dimension_type: TextureDimensionType,
     ///  The width, in pixels, of the texture
-    width: f32,
+    ///
+    /// If the texture is screen-relative, this is a multiple of the screen's width instead
+    pub width: f32,
     ///  The height, in pixels, of the texture
-    height: f32,
+    ///
+    /// If the texture is screen-relative, this is a multiple of the screen's height instead
+    pub height: f32,
+}
+
+impl TextureFormat {
+    /// Works out how big a texture with this format is, in pixels
+    ///
+    /// # Parameters
+    ///
+    /// * `screen_size` - The size of the screen, in pixels
+    pub fn size_in_pixels(&self, screen_size: Vector2<u32>) -> Vector2<u32> {
+        match self.dimension_type {
+            TextureDimensionType::ScreenRelative => Vector2::new(
+                (self.width * screen_size.x as f32).round() as u32,
+                (self.height * screen_size.y as f32).round() as u32,
+            ),
+            TextureDimensionType::Absolute => Vector2::new(self.width.round() as u32, self.height.round() as u32),
+        }
+    }
+
+    /// Works out how much memory a texture with this format needs, in bytes
+    ///
+    /// # Parameters
+    ///
+    /// * `screen_size` - The size of the screen, in pixels
+    pub fn size_in_bytes(&self, screen_size: Vector2<u32>) -> u64 {
+        let size = self.size_in_pixels(screen_size);
+        u64::from(size.x) * u64::from(size.y) * u64::from(self.pixel_format.bytes_per_pixel())
+    }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
 pub enum RasterizerState {
     /// Enable blending for this material state
     Blending,
@@ -258,20 +522,22 @@ pub enum RasterizerState {
     DisableAlphaWrite,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Default, Deserialize)]
 pub enum MSAASupport {
     MSAA,
     Both,
+    #[default]
     None,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Default, Deserialize)]
 pub enum PrimitiveTopology {
+    #[default]
     Triangles,
     Lines,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
 pub enum BlendFactor {
     One,
     Zero,
@@ -285,9 +551,10 @@ pub enum BlendFactor {
     OneMinusDstAlpha,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Default, Deserialize)]
 pub enum CompareOp {
     Never,
+    #[default]
     Less,
     LessEqual,
     Greater,
@@ -297,14 +564,21 @@ pub enum CompareOp {
     Always,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+impl CompareOp {
+    fn always() -> CompareOp {
+        CompareOp::Always
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Default, Deserialize)]
 pub enum RenderQueue {
     Transparent,
+    #[default]
     Opaque,
     Cutout,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
 pub enum VertexField {
     ///  The vertex position
     ///
@@ -353,8 +627,21 @@ pub enum VertexField {
     McEntityId,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+impl VertexField {
+    /// How many bytes this field takes up in each vertex
+    pub fn size_in_bytes(&self) -> u32 {
+        match self {
+            VertexField::Position | VertexField::Normal | VertexField::Tangent | VertexField::McEntityId => 12,
+            VertexField::UV0 | VertexField::MidTexCoord => 8,
+            VertexField::Color | VertexField::VirtualTextureId => 4,
+            VertexField::UV1 => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Default, Deserialize)]
 pub enum StencilOp {
+    #[default]
     Keep,
     Zero,
     Replace,
@@ -365,8 +652,9 @@ pub enum StencilOp {
     Invert,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Default, Deserialize)]
 pub enum PixelFormat {
+    #[default]
     RGBA8,
     RGBA16F,
     RGBA32F,
@@ -374,26 +662,50 @@ pub enum PixelFormat {
     DepthStencil,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+impl PixelFormat {
+    /// How many bytes each pixel of this format takes up
+    pub fn bytes_per_pixel(&self) -> u32 {
+        match self {
+            PixelFormat::RGBA8 => 4,
+            PixelFormat::RGBA16F => 8,
+            PixelFormat::RGBA32F => 16,
+            // 32-bit depth, or 24-bit depth with 8 bits of stencil
+            PixelFormat::Depth | PixelFormat::DepthStencil => 4,
+        }
+    }
+
+    /// Whether textures with this format are depth textures
+    pub fn is_depth(&self) -> bool {
+        match self {
+            PixelFormat::RGBA8 | PixelFormat::RGBA16F | PixelFormat::RGBA32F => false,
+            PixelFormat::Depth | PixelFormat::DepthStencil => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
 pub enum TextureFilter {
     TexelAA,
     Bilinear,
     Point,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
 pub enum WrapMode {
     Repeat,
     Clamp,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Default, Deserialize)]
 pub enum TextureDimensionType {
+    /// The texture's width and height are multiples of the screen's size, so the texture is resized with the screen
+    #[default]
     ScreenRelative,
+    /// The texture's width and height are in pixels
     Absolute,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
 pub enum TextureLocation {
     ///  The texture is written to by a shader
     Dynamic,
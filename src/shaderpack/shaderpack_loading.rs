@@ -0,0 +1,831 @@
+//! Loading shaderpacks from resource packs
+
+use crate::loading::*;
+use crate::shaderpack::*;
+use failure::Fail;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::value::RawValue;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::fmt;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Where in a shaderpack's JSON files a problem is
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct JsonLocation {
+    /// The file with the problem, relative to the root of the shaderpack
+    pub file: PathBuf,
+
+    /// The path to the value with the problem, such as `[2].textureOutputs[0].pixelFormat`. `.` is the whole file
+    pub json_path: String,
+
+    /// The line that the problem is on, starting at 1
+    pub line: usize,
+
+    /// The column that the problem is in, starting at 1
+    pub column: usize,
+}
+
+impl fmt::Display for JsonLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{} (at {})",
+            self.file.display(),
+            self.line,
+            self.column,
+            self.json_path
+        )
+    }
+}
+
+/// Errors that can happen while loading a shaderpack
+#[derive(Fail, Debug)]
+pub enum ShaderpackLoadError {
+    /// A file that the shaderpack needs couldn't be read
+    #[fail(display = "Could not read shaderpack file {:?}", file)]
+    CouldNotRead {
+        file: PathBuf,
+        #[cause]
+        error: ResourcePackError,
+    },
+
+    /// A file isn't valid JSON
+    #[fail(display = "{}: Invalid JSON: {}", location, message)]
+    InvalidJson { location: JsonLocation, message: String },
+
+    /// A file is valid JSON, but a value in it isn't what Nova expects. This includes missing fields and unknown
+    /// enum values
+    #[fail(display = "{}: {}", location, message)]
+    InvalidValue { location: JsonLocation, message: String },
+
+    /// A pipeline inherits from a pipeline that doesn't exist
+    #[fail(
+        display = "{}: Pipeline {} inherits from {}, which doesn't exist",
+        location, pipeline, parent
+    )]
+    UnknownParentPipeline {
+        /// Where the pipeline is declared
+        location: JsonLocation,
+        pipeline: String,
+        parent: String,
+    },
+
+    /// A pipeline doesn't set a field that every pipeline needs, and doesn't inherit it either
+    #[fail(
+        display = "{}: Pipeline {} has no {}, and doesn't inherit one",
+        location, pipeline, field
+    )]
+    MissingPipelineField {
+        /// Where the pipeline is declared
+        location: JsonLocation,
+        pipeline: String,
+        /// The name of the missing field in the pipeline's JSON
+        field: String,
+    },
+
+    /// Some pipelines inherit from each other in a loop
+    #[fail(
+        display = "{}: Pipelines {:?} inherit from each other in a loop",
+        location, pipelines
+    )]
+    PipelineInheritanceCycle {
+        /// Where the first pipeline in the loop is declared
+        location: JsonLocation,
+        /// The pipelines in the loop. Each one inherits from the next, and the last one inherits from the first
+        pipelines: Vec<String>,
+    },
+
+    /// Two passes, or two pipelines, have the same name
+    #[fail(
+        display = "{}: There's already a {} named {}, declared at {}",
+        location, kind, name, first_location
+    )]
+    DuplicateName {
+        /// What has the same name, like `pass` or `pipeline`
+        kind: &'static str,
+        name: String,
+        /// Where the later declaration is
+        location: JsonLocation,
+        /// Where the first declaration with the name is
+        first_location: Box<JsonLocation>,
+    },
+
+    /// `resources.json` declares a texture with the same name as one of the textures that Nova provides
+    #[fail(
+        display = "Texture {} is built into Nova, and can't be declared by the shaderpack",
+        texture
+    )]
+    RedefinedBuiltinTexture { texture: String },
+
+    /// A pass writes to a built-in texture that can only be read
+    #[fail(
+        display = "Pass {} writes to {}, but {} is built into Nova and can only be read",
+        pass, texture, texture
+    )]
+    WritesInputOnlyTexture { pass: String, texture: String },
+
+    /// A pass reads a built-in texture that can only be written to
+    #[fail(
+        display = "Pass {} reads {}, but {} is built into Nova and can only be written to",
+        pass, texture, texture
+    )]
+    ReadsOutputOnlyTexture { pass: String, texture: String },
+
+    /// A material binds a built-in texture that can only be written to
+    #[fail(
+        display = "Material {} pass {} binds {}, but {} is built into Nova and can only be written to",
+        material, pass, texture, texture
+    )]
+    BindsOutputOnlyTexture {
+        material: String,
+        pass: String,
+        texture: String,
+    },
+}
+
+impl ShaderpackLoadError {
+    /// Where in the shaderpack's JSON this error is, if it's about the contents of a file
+    pub fn location(&self) -> Option<&JsonLocation> {
+        match self {
+            ShaderpackLoadError::CouldNotRead { .. }
+            | ShaderpackLoadError::RedefinedBuiltinTexture { .. }
+            | ShaderpackLoadError::WritesInputOnlyTexture { .. }
+            | ShaderpackLoadError::ReadsOutputOnlyTexture { .. }
+            | ShaderpackLoadError::BindsOutputOnlyTexture { .. } => None,
+            ShaderpackLoadError::InvalidJson { location, .. }
+            | ShaderpackLoadError::InvalidValue { location, .. }
+            | ShaderpackLoadError::DuplicateName { location, .. }
+            | ShaderpackLoadError::UnknownParentPipeline { location, .. }
+            | ShaderpackLoadError::MissingPipelineField { location, .. }
+            | ShaderpackLoadError::PipelineInheritanceCycle { location, .. } => Some(location),
+        }
+    }
+}
+
+/// Loads a Nova shaderpack, stopping at the first problem
+///
+/// A Nova shaderpack has these files:
+/// - `passes.json`, which lists the shaderpack's render passes in submission order
+/// - `resources.json`, which declares the textures and samplers that the passes use. It's optional, since Nova provides
+///   some resources itself
+/// - A `.pipeline` file in the `materials` folder for each pipeline
+/// - A `.mat` file in the `materials` folder for each material
+///
+/// Pipelines and materials are sorted by the name of the file they're loaded from, so that loading a shaderpack
+/// always gives the same result. Pipelines have already inherited from their parents. No two passes, and no two
+/// pipelines, may have the same name
+///
+/// The shaderpack may not declare any of the textures that Nova provides, and may only use them in the ways listed in
+/// `BUILTIN_TEXTURES`
+///
+/// # Parameters
+///
+/// * `pack` - The pack to load the shaderpack from
+pub fn load_nova_shaderpack(pack: &dyn ResourcePack) -> Result<ShaderpackData, ShaderpackLoadError> {
+    load_nova_shaderpack_with_all_errors(pack).map_err(|errors| errors.into_iter().next().unwrap())
+}
+
+/// Loads a Nova shaderpack, reporting every problem with it instead of just the first one
+///
+/// Shader authors can fix everything that's wrong with their shaderpack in one go this way. A broken pass, texture
+/// or sampler doesn't stop the rest of its file from being checked, and a broken file doesn't stop the rest of the
+/// shaderpack from being checked. Inheritance is resolved between the pipelines that could be loaded, even if other
+/// parts of the shaderpack are broken
+///
+/// Errors are in the order that the files were loaded, followed by the problems with pipeline inheritance. See
+/// `load_nova_shaderpack` for the files that are loaded
+///
+/// # Parameters
+///
+/// * `pack` - The pack to load the shaderpack from
+pub fn load_nova_shaderpack_with_all_errors(
+    pack: &dyn ResourcePack,
+) -> Result<ShaderpackData, Vec<ShaderpackLoadError>> {
+    let mut loader = ShaderpackLoader {
+        pack,
+        errors: Vec::new(),
+    };
+
+    let resources = loader.load_resources();
+    let passes = loader.load_passes();
+    let (pipelines, materials) = loader.load_materials();
+
+    loader.check_unique_names("pass", &passes, |pass| &pass.name);
+    loader.check_unique_names("pipeline", &pipelines, |pipeline| &pipeline.name);
+
+    let (pipelines, pipeline_locations): (Vec<_>, Vec<_>) = pipelines.into_iter().unzip();
+    let mut data = ShaderpackData {
+        pipelines,
+        passes: passes.into_iter().map(|(pass, _)| pass).collect(),
+        materials,
+        resources,
+    };
+    loader.check_builtin_textures(&data);
+
+    if let Err(errors) = data.resolve_pipeline_inheritance(&pipeline_locations) {
+        loader.errors.extend(errors);
+    }
+
+    if loader.errors.is_empty() {
+        Ok(data)
+    } else {
+        Err(loader.errors)
+    }
+}
+
+/// `resources.json`, with each texture and sampler left unparsed so that they can be checked one at a time
+#[derive(Deserialize)]
+struct RawResources<'a> {
+    #[serde(borrow, default)]
+    textures: Vec<&'a RawValue>,
+    #[serde(borrow, default)]
+    samplers: Vec<&'a RawValue>,
+}
+
+struct ShaderpackLoader<'a> {
+    pack: &'a dyn ResourcePack,
+    errors: Vec<ShaderpackLoadError>,
+}
+
+impl<'a> ShaderpackLoader<'a> {
+    fn load_resources(&mut self) -> ShaderpackResourceData {
+        let file = Path::new("resources.json");
+        if !self.pack.exists(file) {
+            return ShaderpackResourceData::default();
+        }
+
+        let contents = match self.read(file) {
+            Some(contents) => contents,
+            None => return ShaderpackResourceData::default(),
+        };
+        let raw: RawResources<'_> = match self.parse(file, &contents, &contents, "") {
+            Some(raw) => raw,
+            None => return ShaderpackResourceData::default(),
+        };
+
+        let textures = self.parse_each(file, &contents, &raw.textures, "textures");
+        let samplers = self.parse_each(file, &contents, &raw.samplers, "samplers");
+        ShaderpackResourceData {
+            textures: textures.into_iter().map(|(texture, _)| texture).collect(),
+            samplers: samplers.into_iter().map(|(sampler, _)| sampler).collect(),
+        }
+    }
+
+    fn load_passes(&mut self) -> Vec<(RenderPassCreationInfo, JsonLocation)> {
+        let file = Path::new("passes.json");
+        let contents = match self.read(file) {
+            Some(contents) => contents,
+            None => return Vec::new(),
+        };
+        let raw: Vec<&RawValue> = match self.parse(file, &contents, &contents, "") {
+            Some(raw) => raw,
+            None => return Vec::new(),
+        };
+
+        self.parse_each(file, &contents, &raw, "")
+    }
+
+    fn load_materials(&mut self) -> (Vec<(PipelineCreationInfo, JsonLocation)>, Vec<MaterialData>) {
+        let mut pipelines = Vec::new();
+        let mut materials = Vec::new();
+
+        let directory = Path::new("materials");
+        if !self.pack.exists(directory) {
+            return (pipelines, materials);
+        }
+
+        let files = match self.pack.list_directory(directory) {
+            Ok(files) => files,
+            Err(error) => {
+                self.errors.push(ShaderpackLoadError::CouldNotRead {
+                    file: directory.to_path_buf(),
+                    error,
+                });
+                return (pipelines, materials);
+            }
+        };
+
+        for file in files {
+            match file.extension().and_then(|extension| extension.to_str()) {
+                Some("pipeline") => pipelines.extend(self.load_file::<PipelineCreationInfo>(&file)),
+                Some("mat") => {
+                    if let Some((mut material, _)) = self.load_file::<MaterialData>(&file) {
+                        for pass in &mut material.passes {
+                            pass.material_name = material.name.clone();
+                        }
+                        materials.push(material);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        (pipelines, materials)
+    }
+
+    /// Reports every value whose name was already used by an earlier value
+    fn check_unique_names<T>(
+        &mut self,
+        kind: &'static str,
+        values: &[(T, JsonLocation)],
+        name: impl Fn(&T) -> &String,
+    ) {
+        let mut first_locations: HashMap<&String, &JsonLocation> = HashMap::new();
+        for (value, location) in values {
+            match first_locations.entry(name(value)) {
+                Entry::Occupied(first) => self.errors.push(ShaderpackLoadError::DuplicateName {
+                    kind,
+                    name: name(value).clone(),
+                    location: location.clone(),
+                    first_location: Box::new((*first.get()).clone()),
+                }),
+                Entry::Vacant(entry) => {
+                    entry.insert(location);
+                }
+            }
+        }
+    }
+
+    /// Checks that the shaderpack doesn't declare any of the textures that Nova provides, and only uses them in the
+    /// ways that Nova allows
+    fn check_builtin_textures(&mut self, data: &ShaderpackData) {
+        for texture in &data.resources.textures {
+            if builtin_texture(&texture.name).is_some() {
+                self.errors.push(ShaderpackLoadError::RedefinedBuiltinTexture {
+                    texture: texture.name.clone(),
+                });
+            }
+        }
+
+        for pass in &data.passes {
+            for texture in &pass.texture_inputs {
+                if builtin_texture(texture).is_some_and(|builtin| !builtin.can_be_read()) {
+                    self.errors.push(ShaderpackLoadError::ReadsOutputOnlyTexture {
+                        pass: pass.name.clone(),
+                        texture: texture.clone(),
+                    });
+                }
+            }
+
+            for output in pass.texture_outputs.iter().chain(&pass.depth_texture) {
+                if builtin_texture(&output.name).is_some_and(|builtin| !builtin.can_be_written()) {
+                    self.errors.push(ShaderpackLoadError::WritesInputOnlyTexture {
+                        pass: pass.name.clone(),
+                        texture: output.name.clone(),
+                    });
+                }
+            }
+        }
+
+        for material_pass in data.materials.iter().flat_map(|material| &material.passes) {
+            let mut resources: Vec<_> = material_pass.bindings.values().collect();
+            resources.sort();
+            resources.dedup();
+            for resource in resources {
+                if builtin_texture(resource).is_some_and(|builtin| !builtin.can_be_read()) {
+                    self.errors.push(ShaderpackLoadError::BindsOutputOnlyTexture {
+                        material: material_pass.material_name.clone(),
+                        pass: material_pass.name.clone(),
+                        texture: resource.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Loads a file that holds a single value, along with where the value is
+    fn load_file<T: DeserializeOwned>(&mut self, file: &Path) -> Option<(T, JsonLocation)> {
+        let contents = self.read(file)?;
+        let value = self.parse(file, &contents, &contents, "")?;
+        Some((value, locate(file, &contents, &contents, ".")))
+    }
+
+    fn read(&mut self, file: &Path) -> Option<String> {
+        match self.pack.read_string(file) {
+            Ok(contents) => Some(contents),
+            Err(error) => {
+                self.errors.push(ShaderpackLoadError::CouldNotRead {
+                    file: file.to_path_buf(),
+                    error,
+                });
+                None
+            }
+        }
+    }
+
+    /// Parses each of the values in a list separately, so that one broken value doesn't hide problems with the others
+    ///
+    /// Returns each value that could be parsed, along with where it is
+    fn parse_each<T: DeserializeOwned>(
+        &mut self,
+        file: &Path,
+        contents: &str,
+        values: &[&RawValue],
+        json_path: &str,
+    ) -> Vec<(T, JsonLocation)> {
+        values
+            .iter()
+            .enumerate()
+            .filter_map(|(index, value)| {
+                let json_path = format!("{}[{}]", json_path, index);
+                let parsed = self.parse(file, contents, value.get(), &json_path)?;
+                Some((parsed, locate(file, contents, value.get(), &json_path)))
+            })
+            .collect()
+    }
+
+    /// Parses some JSON, recording an error if it's invalid
+    ///
+    /// # Parameters
+    ///
+    /// * `file` - The file that the JSON is from
+    /// * `contents` - The full contents of `file`
+    /// * `json` - The JSON to parse. Must be a slice of `contents`, so that errors can be located in the file
+    /// * `json_path` - The path to `json` in the file
+    fn parse<'de, T: Deserialize<'de>>(
+        &mut self,
+        file: &Path,
+        contents: &str,
+        json: &'de str,
+        json_path: &str,
+    ) -> Option<T> {
+        let mut deserializer = serde_json::Deserializer::from_str(json);
+        let error = match serde_path_to_error::deserialize(&mut deserializer) {
+            Ok(value) => return Some(value),
+            Err(error) => error,
+        };
+
+        let offset = json.as_ptr() as usize - contents.as_ptr() as usize;
+        let (line, column) = offset_to_line_column(contents, offset);
+        let inner = error.inner();
+        let (line, column) = if inner.line() <= 1 {
+            (line, column + inner.column().saturating_sub(1))
+        } else {
+            (line + inner.line() - 1, inner.column())
+        };
+
+        let location = JsonLocation {
+            file: file.to_path_buf(),
+            json_path: join_json_path(json_path, &error.path().to_string()),
+            line,
+            column,
+        };
+        let message = strip_position(&inner.to_string());
+
+        self.errors.push(if inner.is_syntax() || inner.is_eof() {
+            ShaderpackLoadError::InvalidJson { location, message }
+        } else {
+            ShaderpackLoadError::InvalidValue { location, message }
+        });
+
+        None
+    }
+}
+
+/// Finds where some JSON starts in a file
+///
+/// # Parameters
+///
+/// * `file` - The file that the JSON is from
+/// * `contents` - The full contents of `file`
+/// * `json` - The JSON to find. Must be a slice of `contents`
+/// * `json_path` - The path to `json` in the file
+fn locate(file: &Path, contents: &str, json: &str, json_path: &str) -> JsonLocation {
+    let offset = json.as_ptr() as usize - contents.as_ptr() as usize;
+    let (line, column) = offset_to_line_column(contents, offset);
+    JsonLocation {
+        file: file.to_path_buf(),
+        json_path: json_path.to_owned(),
+        line,
+        column,
+    }
+}
+
+fn offset_to_line_column(contents: &str, offset: usize) -> (usize, usize) {
+    let before = &contents[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (line, offset - line_start + 1)
+}
+
+/// Appends a path from `serde_path_to_error` to the path of the value that was being parsed
+fn join_json_path(prefix: &str, path: &str) -> String {
+    match (prefix, path) {
+        ("", path) => path.to_owned(),
+        (prefix, ".") => prefix.to_owned(),
+        (prefix, path) if path.starts_with('[') => format!("{}{}", prefix, path),
+        (prefix, path) => format!("{}.{}", prefix, path),
+    }
+}
+
+/// serde_json puts the position of an error at the end of its message. Errors are located relative to the whole file
+/// instead, so that position is misleading
+fn strip_position(message: &str) -> String {
+    match message.rfind(" at line ") {
+        Some(index) => message[..index].to_owned(),
+        None => message.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::loading::*;
+    use crate::shaderpack::*;
+    use std::path::Path;
+
+    const PASSES: &str = r#"[
+        {
+            "name": "Forward",
+            "textureOutputs": [{ "name": "Backbuffer", "clear": true }],
+            "depthTexture": { "name": "DepthBuffer", "pixelFormat": "Depth" }
+        }
+    ]"#;
+
+    #[test]
+    fn loads_shaderpack() {
+        let (_dir, pack) = make_pack(&[
+            ("passes.json", PASSES),
+            (
+                "resources.json",
+                r#"{
+                    "textures": [
+                        { "name": "DepthBuffer", "format": { "pixelFormat": "Depth", "width": 1, "height": 1 } }
+                    ],
+                    "samplers": [{ "name": "Point", "filter": "Point", "wrapMode": "Clamp" }]
+                }"#,
+            ),
+            (
+                "materials/gbuffers_terrain.pipeline",
+                r#"{
+                    "name": "gbuffers_terrain",
+                    "pass": "Forward",
+                    "states": ["Blending"],
+                    "vertexFields": [{ "name": "position", "field": "Position" }],
+                    "frontFace": { "failOp": "Replace" },
+                    "destinationBlendFactor": "OneMinusSrcAlpha",
+                    "vertexShader": "shaders/gbuffers_terrain.vert",
+                    "fragmentShader": "shaders/gbuffers_terrain.frag"
+                }"#,
+            ),
+            (
+                "materials/terrain.mat",
+                r#"{
+                    "name": "terrain",
+                    "passes": [
+                        {
+                            "name": "main",
+                            "pipeline": "gbuffers_terrain",
+                            "bindings": { "colortex": "ColorVirtualTexture" }
+                        }
+                    ],
+                    "filter": "geometry_type::block"
+                }"#,
+            ),
+            ("materials/readme.txt", "Not a material"),
+        ]);
+
+        let data = load_nova_shaderpack(&pack).unwrap();
+
+        assert_eq!(data.passes.len(), 1);
+        let pass = &data.passes[0];
+        assert_eq!(pass.name, "Forward");
+        assert!(pass.dependencies.is_empty());
+        assert_eq!(pass.texture_outputs[0].pixel_format, PixelFormat::RGBA8);
+        assert!(pass.texture_outputs[0].clear);
+        assert_eq!(pass.depth_texture.as_ref().unwrap().pixel_format, PixelFormat::Depth);
+        assert!(!pass.depth_texture.as_ref().unwrap().clear);
+
+        assert_eq!(data.pipelines.len(), 1);
+        let pipeline = &data.pipelines[0];
+        assert_eq!(pipeline.parent, None);
+        assert_eq!(pipeline.states, vec![RasterizerState::Blending]);
+        assert_eq!(pipeline.vertex_fields[0].semantic_name, "position");
+        assert_eq!(pipeline.msaa_support, None);
+        assert_eq!(pipeline.primitive_mode, None);
+        assert_eq!(pipeline.src_blend_factor, None);
+        assert_eq!(pipeline.dst_blend_factor, Some(BlendFactor::OneMinusSrcAlpha));
+        assert_eq!(pipeline.alpha_src, None);
+        assert_eq!(pipeline.alpha_dst, None);
+        assert_eq!(pipeline.depth_func, None);
+        assert_eq!(pipeline.render_queue, None);
+        assert_eq!(pipeline.depth_bias, None);
+        let front_face = pipeline.front_face.as_ref().unwrap();
+        assert_eq!(front_face.fail_op, StencilOp::Replace);
+        assert_eq!(front_face.pass_op, StencilOp::Keep);
+        assert_eq!(front_face.compare_op, CompareOp::Always);
+        assert_eq!(
+            pipeline.vertex_shader.filename,
+            Path::new("shaders/gbuffers_terrain.vert")
+        );
+        assert!(pipeline.vertex_shader.source.is_empty());
+        assert!(pipeline.geometry_shader.is_none());
+
+        assert_eq!(data.materials.len(), 1);
+        let material = &data.materials[0];
+        assert_eq!(material.geometry_filter, "geometry_type::block");
+        assert_eq!(material.passes[0].material_name, "terrain");
+        assert_eq!(material.passes[0].bindings["colortex"], "ColorVirtualTexture");
+
+        assert_eq!(
+            data.resources.textures[0].format.dimension_type,
+            TextureDimensionType::ScreenRelative
+        );
+        assert_eq!(data.resources.samplers[0].wrap_mode, WrapMode::Clamp);
+    }
+
+    #[test]
+    fn resources_are_optional() {
+        let (_dir, pack) = make_pack(&[("passes.json", PASSES)]);
+
+        let data = load_nova_shaderpack(&pack).unwrap();
+        assert_eq!(data.passes.len(), 1);
+        assert!(data.pipelines.is_empty());
+        assert!(data.resources.textures.is_empty());
+    }
+
+    #[test]
+    fn reports_which_file_is_broken() {
+        let (_dir, pack) = make_pack(&[
+            ("passes.json", PASSES),
+            ("materials/broken.pipeline", r#"{ "name": "broken" }"#),
+        ]);
+
+        match load_nova_shaderpack(&pack) {
+            Err(ShaderpackLoadError::MissingPipelineField {
+                location,
+                pipeline,
+                field,
+            }) => {
+                assert_eq!(location.file, Path::new("materials/broken.pipeline"));
+                assert_eq!(pipeline, "broken");
+                assert_eq!(field, "pass");
+            }
+            other => panic!("Expected MissingPipelineField, got {:?}", other),
+        }
+
+        let (_dir, pack) = make_pack(&[
+            ("passes.json", PASSES),
+            (
+                "materials/broken.pipeline",
+                r#"{ "name": "broken", "states": ["Sparkles"] }"#,
+            ),
+        ]);
+        match load_nova_shaderpack(&pack) {
+            Err(ShaderpackLoadError::InvalidValue { location, .. }) => {
+                assert_eq!(location.file, Path::new("materials/broken.pipeline"))
+            }
+            other => panic!("Expected InvalidValue, got {:?}", other),
+        }
+
+        let (_dir, pack) = make_pack(&[]);
+        match load_nova_shaderpack(&pack) {
+            Err(ShaderpackLoadError::CouldNotRead { file, .. }) => assert_eq!(file, Path::new("passes.json")),
+            other => panic!("Expected CouldNotRead, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reports_duplicate_names() {
+        let (_dir, pack) = make_pack(&[
+            (
+                "passes.json",
+                r#"[
+    { "name": "Forward" },
+    { "name": "Bloom" },
+    { "name": "Forward", "dependencies": ["Bloom"] }
+]"#,
+            ),
+            (
+                "materials/gbuffers.pipeline",
+                r#"{ "name": "gbuffers", "pass": "Forward", "vertexShader": "gbuffers.vert" }"#,
+            ),
+            (
+                "materials/gbuffers_copy.pipeline",
+                r#"{ "name": "gbuffers", "pass": "Bloom", "vertexShader": "bloom.vert" }"#,
+            ),
+        ]);
+
+        let errors: Vec<_> = load_nova_shaderpack_with_all_errors(&pack)
+            .unwrap_err()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "passes.json:4:5 (at [2]): There's already a pass named Forward, declared at passes.json:2:5 (at [0])",
+                "materials/gbuffers_copy.pipeline:1:1 (at .): There's already a pipeline named gbuffers, declared at \
+                 materials/gbuffers.pipeline:1:1 (at .)",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_misused_builtin_textures() {
+        let (_dir, pack) = make_pack(&[
+            (
+                "passes.json",
+                r#"[
+    { "name": "Forward", "textureInputs": ["Lightmap"], "textureOutputs": [{ "name": "Lightmap" }] },
+    { "name": "Final", "textureInputs": ["Backbuffer"], "textureOutputs": [{ "name": "Backbuffer" }] }
+]"#,
+            ),
+            (
+                "resources.json",
+                r#"{ "textures": [{ "name": "Backbuffer", "format": { "width": 1.0, "height": 1.0 } }] }"#,
+            ),
+            (
+                "materials/terrain.mat",
+                r#"{ "name": "terrain", "filter": "geometry_type::block",
+                     "passes": [{ "name": "main", "pipeline": "terrain",
+                                  "bindings": { "albedo": "ColorVirtualTexture", "previous": "Backbuffer" } }] }"#,
+            ),
+        ]);
+
+        let errors: Vec<_> = load_nova_shaderpack_with_all_errors(&pack)
+            .unwrap_err()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "Texture Backbuffer is built into Nova, and can't be declared by the shaderpack",
+                "Pass Forward writes to Lightmap, but Lightmap is built into Nova and can only be read",
+                "Pass Final reads Backbuffer, but Backbuffer is built into Nova and can only be written to",
+                "Material terrain pass main binds Backbuffer, but Backbuffer is built into Nova and can only be \
+                 written to",
+            ]
+        );
+    }
+
+    #[test]
+    fn collects_every_error_with_its_location() {
+        let (_dir, pack) = make_pack(&[
+            (
+                "passes.json",
+                r#"[
+    { "name": "Shadow" },
+    { "name": "Forward", "textureOutputs": [{ "name": "Backbuffer", "pixelFormat": "RGB9" }] },
+    { "dependencies": [] }
+]"#,
+            ),
+            (
+                "resources.json",
+                r#"{
+    "samplers": [
+        { "name": "Point", "filter": "Point", "wrapMode": "Clamp" },
+        { "name": "Linear", "filter": "Bilinear", "wrapMode": 7 }
+    ]
+}"#,
+            ),
+            ("materials/broken.mat", "{ \"name\": \"broken\",\n  \"passes\": [ }"),
+            (
+                "materials/orphan.pipeline",
+                r#"{ "name": "orphan", "parent": "missing", "pass": "Forward" }"#,
+            ),
+        ]);
+
+        let errors = load_nova_shaderpack_with_all_errors(&pack).unwrap_err();
+        let locations: Vec<_> = errors
+            .iter()
+            .map(|error| {
+                let location = error.location().unwrap();
+                (
+                    location.file.to_str().unwrap(),
+                    location.json_path.as_str(),
+                    location.line,
+                )
+            })
+            .collect();
+        assert_eq!(
+            locations,
+            vec![
+                ("resources.json", "samplers[1].wrapMode", 4),
+                ("passes.json", "[1].textureOutputs[0].pixelFormat", 3),
+                ("passes.json", "[2]", 4),
+                ("materials/broken.mat", "passes[0]", 2),
+                ("materials/orphan.pipeline", ".", 1),
+            ]
+        );
+
+        match &errors[1] {
+            ShaderpackLoadError::InvalidValue { location, message } => {
+                assert_eq!(location.column, 89);
+                assert!(message.contains("RGB9"), "{}", message);
+            }
+            other => panic!("Expected InvalidValue, got {:?}", other),
+        }
+        match &errors[3] {
+            ShaderpackLoadError::InvalidJson { .. } => {}
+            other => panic!("Expected InvalidJson, got {:?}", other),
+        }
+        assert_eq!(
+            errors[4].to_string(),
+            "materials/orphan.pipeline:1:1 (at .): Pipeline orphan inherits from missing, which doesn't exist"
+        );
+    }
+}
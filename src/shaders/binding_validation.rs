@@ -0,0 +1,305 @@
+//! Checking that materials bind resources that their shaders can use
+
+use crate::rhi::*;
+use crate::shaderpack::*;
+use failure::Fail;
+use log::warn;
+use std::collections::HashMap;
+
+/// Errors in the bindings of a material pass
+#[derive(Fail, Debug)]
+pub enum BindingError {
+    #[fail(
+        display = "Material {} pass {} uses pipeline {}, whose shaders haven't been reflected",
+        material, pass, pipeline
+    )]
+    UnknownPipeline {
+        material: String,
+        pass: String,
+        pipeline: String,
+    },
+
+    #[fail(
+        display = "Material {} pass {} binds {}, but pipeline {} has no shader variable with that name",
+        material, pass, variable, pipeline
+    )]
+    UnknownVariable {
+        material: String,
+        pass: String,
+        pipeline: String,
+        variable: String,
+    },
+
+    #[fail(
+        display = "Material {} pass {} binds {} to {}, which isn't a declared or built-in resource",
+        material, pass, variable, resource
+    )]
+    UnknownResource {
+        material: String,
+        pass: String,
+        variable: String,
+        resource: String,
+    },
+
+    #[fail(
+        display = "Material {} pass {} binds {} to {}, but {} is a {:?} and a {:?} can't be bound to a {:?}",
+        material, pass, variable, resource, resource, resource_kind, resource_kind, descriptor_type
+    )]
+    IncompatibleResource {
+        material: String,
+        pass: String,
+        variable: String,
+        resource: String,
+        resource_kind: ResourceKind,
+        descriptor_type: DescriptorType,
+    },
+}
+
+/// The kinds of resources that a shaderpack can bind to its shaders
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ResourceKind {
+    Texture,
+    Sampler,
+    Buffer,
+}
+
+impl ResourceKind {
+    /// Whether a resource of this kind can be bound to a shader variable with the provided descriptor type
+    ///
+    /// A combined image sampler can be bound to a texture or to a sampler, since Nova provides the other half
+    pub fn can_bind_to(self, descriptor_type: &DescriptorType) -> bool {
+        matches!(
+            (self, descriptor_type),
+            (ResourceKind::Texture, DescriptorType::SampledImage)
+                | (ResourceKind::Texture, DescriptorType::CombinedImageSampler)
+                | (ResourceKind::Sampler, DescriptorType::Sampler)
+                | (ResourceKind::Sampler, DescriptorType::CombinedImageSampler)
+                | (ResourceKind::Buffer, DescriptorType::UniformBuffer)
+                | (ResourceKind::Buffer, DescriptorType::StorageBuffer)
+        )
+    }
+}
+
+/// A shader variable that a material pass doesn't bind anything to
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct UnboundVariable {
+    pub material: String,
+    pub pass: String,
+    pub variable: String,
+}
+
+/// Checks the bindings of every material pass in a shaderpack against the shaders of the pass's pipeline
+///
+/// Each binding must name one of the variables in the pipeline's shaders, and a resource that's declared in the
+/// shaderpack or built into Nova. The resource must be something that can be bound to the variable: a texture for a
+/// texture variable, a sampler for a sampler variable, and a buffer for a buffer variable. Buffers are declared by
+/// the passes that read or write them
+///
+/// # Errors
+///
+/// Returns every problem with every material pass's bindings
+///
+/// # Returns
+///
+/// The shader variables that material passes don't bind anything to. A warning is logged for each of them
+///
+/// # Parameters
+///
+/// * `data` - The shaderpack to check the materials of
+/// * `pipeline_bindings` - The bindings of each pipeline's shaders, by the name of the pipeline, as returned by
+///   `reflect_pipeline`
+pub fn validate_material_bindings(
+    data: &ShaderpackData,
+    pipeline_bindings: &HashMap<String, HashMap<String, ResourceBindingDescription>>,
+) -> Result<Vec<UnboundVariable>, Vec<BindingError>> {
+    let resources = declared_resources(data);
+
+    let mut errors = Vec::new();
+    let mut unbound = Vec::new();
+    for material_pass in data.materials.iter().flat_map(|material| &material.passes) {
+        let material = &material_pass.material_name;
+        let pass = &material_pass.name;
+        let bindings = match pipeline_bindings.get(&material_pass.pipeline) {
+            Some(bindings) => bindings,
+            None => {
+                errors.push(BindingError::UnknownPipeline {
+                    material: material.clone(),
+                    pass: pass.clone(),
+                    pipeline: material_pass.pipeline.clone(),
+                });
+                continue;
+            }
+        };
+
+        let mut bound: Vec<_> = material_pass.bindings.iter().collect();
+        bound.sort();
+        for (variable, resource) in bound {
+            let description = match bindings.get(variable) {
+                Some(description) => description,
+                None => {
+                    errors.push(BindingError::UnknownVariable {
+                        material: material.clone(),
+                        pass: pass.clone(),
+                        pipeline: material_pass.pipeline.clone(),
+                        variable: variable.clone(),
+                    });
+                    continue;
+                }
+            };
+
+            match resources.get(resource.as_str()) {
+                None => errors.push(BindingError::UnknownResource {
+                    material: material.clone(),
+                    pass: pass.clone(),
+                    variable: variable.clone(),
+                    resource: resource.clone(),
+                }),
+                Some(kind) if !kind.can_bind_to(&description.descriptor_type) => {
+                    errors.push(BindingError::IncompatibleResource {
+                        material: material.clone(),
+                        pass: pass.clone(),
+                        variable: variable.clone(),
+                        resource: resource.clone(),
+                        resource_kind: *kind,
+                        descriptor_type: description.descriptor_type.clone(),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+
+        let mut variables: Vec<_> = bindings
+            .keys()
+            .filter(|variable| !material_pass.bindings.contains_key(*variable))
+            .collect();
+        variables.sort();
+        for variable in variables {
+            warn!(
+                "Material {} pass {} doesn't bind anything to {}",
+                material, pass, variable
+            );
+            unbound.push(UnboundVariable {
+                material: material.clone(),
+                pass: pass.clone(),
+                variable: variable.clone(),
+            });
+        }
+    }
+
+    if errors.is_empty() { Ok(unbound) } else { Err(errors) }
+}
+
+/// Finds every resource that a shaderpack can bind, and what kind of resource it is
+fn declared_resources(data: &ShaderpackData) -> HashMap<&str, ResourceKind> {
+    let mut resources = HashMap::new();
+    for texture in BUILTIN_TEXTURES.iter().filter(|texture| texture.can_be_read()) {
+        resources.insert(texture.name, ResourceKind::Texture);
+    }
+    for texture in &data.resources.textures {
+        resources.insert(texture.name.as_str(), ResourceKind::Texture);
+    }
+    for sampler in &data.resources.samplers {
+        resources.insert(sampler.name.as_str(), ResourceKind::Sampler);
+    }
+    for pass in &data.passes {
+        for buffer in pass.input_buffers.iter().chain(&pass.output_buffers) {
+            resources.insert(buffer.as_str(), ResourceKind::Buffer);
+        }
+    }
+
+    resources
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rhi::*;
+    use crate::shaderpack::*;
+    use crate::shaders::*;
+    use std::collections::HashMap;
+
+    fn shaderpack(bindings: &str) -> ShaderpackData {
+        let mut material: MaterialData = serde_json::from_str(&format!(
+            r#"{{ "name": "terrain", "filter": "geometry_type::block",
+                  "passes": [{{ "name": "forward", "pipeline": "terrain", "bindings": {} }}] }}"#,
+            bindings
+        ))
+        .unwrap();
+        material.passes[0].material_name = material.name.clone();
+
+        ShaderpackData {
+            materials: vec![material],
+            passes: vec![serde_json::from_str(r#"{ "name": "forward", "inputBuffers": ["Lights"] }"#).unwrap()],
+            resources: serde_json::from_str(
+                r#"{ "textures": [{ "name": "Albedo",
+                                    "format": { "pixelFormat": "RGBA8", "width": 1.0, "height": 1.0 } }],
+                     "samplers": [{ "name": "Point", "filter": "Point", "wrapMode": "Clamp" }] }"#,
+            )
+            .unwrap(),
+            ..ShaderpackData::default()
+        }
+    }
+
+    fn terrain_bindings() -> HashMap<String, HashMap<String, ResourceBindingDescription>> {
+        let binding = |binding, descriptor_type| ResourceBindingDescription {
+            set: 0,
+            binding,
+            count: 1,
+            descriptor_type,
+            stages: ShaderStageFlags::FRAGMENT,
+        };
+
+        let mut bindings = HashMap::new();
+        bindings.insert("albedo".to_owned(), binding(0, DescriptorType::SampledImage));
+        bindings.insert("block_texture".to_owned(), binding(1, DescriptorType::SampledImage));
+        bindings.insert("point".to_owned(), binding(2, DescriptorType::Sampler));
+        bindings.insert("lights".to_owned(), binding(3, DescriptorType::UniformBuffer));
+
+        let mut pipelines = HashMap::new();
+        pipelines.insert("terrain".to_owned(), bindings);
+        pipelines
+    }
+
+    #[test]
+    fn accepts_compatible_bindings_and_reports_unbound_variables() {
+        let data = shaderpack(r#"{ "albedo": "Albedo", "block_texture": "ColorVirtualTexture", "point": "Point" }"#);
+
+        let unbound = validate_material_bindings(&data, &terrain_bindings()).unwrap();
+        assert_eq!(
+            unbound,
+            vec![UnboundVariable {
+                material: "terrain".to_owned(),
+                pass: "forward".to_owned(),
+                variable: "lights".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_bad_bindings() {
+        let data =
+            shaderpack(r#"{ "albedo": "Point", "block_texture": "Missing", "lights": "Lights", "normals": "Albedo" }"#);
+
+        let errors: Vec<_> = validate_material_bindings(&data, &terrain_bindings())
+            .unwrap_err()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "Material terrain pass forward binds albedo to Point, but Point is a Sampler and a Sampler can't be \
+                 bound to a SampledImage",
+                "Material terrain pass forward binds block_texture to Missing, which isn't a declared or built-in \
+                 resource",
+                "Material terrain pass forward binds normals, but pipeline terrain has no shader variable with that \
+                 name",
+            ]
+        );
+
+        let errors = validate_material_bindings(&data, &HashMap::new()).unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "Material terrain pass forward uses pipeline terrain, whose shaders haven't been reflected"
+        );
+    }
+}
@@ -0,0 +1,343 @@
+//! Compiling GLSL to SPIR-V
+
+use crate::rhi::*;
+use crate::shaderpack::*;
+use crate::shaders::*;
+use failure::Fail;
+use naga::back::spv;
+use naga::front::glsl;
+use naga::valid::Capabilities;
+use naga::valid::ValidationFlags;
+use naga::valid::Validator;
+use sha2::Digest;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::PathBuf;
+
+/// A stage of a graphics pipeline
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum ShaderStage {
+    Vertex,
+    TessellationControl,
+    TessellationEvaluation,
+    Geometry,
+    Fragment,
+}
+
+impl ShaderStage {
+    /// The flag for this stage, for saying which stages can use a resource
+    pub fn flag(self) -> ShaderStageFlags {
+        match self {
+            ShaderStage::Vertex => ShaderStageFlags::VERTEX,
+            ShaderStage::TessellationControl => ShaderStageFlags::TESSELLATION_CONTROL,
+            ShaderStage::TessellationEvaluation => ShaderStageFlags::TESSELLATION_EVALUATION,
+            ShaderStage::Geometry => ShaderStageFlags::GEOMETRY,
+            ShaderStage::Fragment => ShaderStageFlags::FRAGMENT,
+        }
+    }
+
+    /// The stage as the compiler knows it, if the compiler supports the stage
+    fn naga_stage(self) -> Option<naga::ShaderStage> {
+        match self {
+            ShaderStage::Vertex => Some(naga::ShaderStage::Vertex),
+            ShaderStage::Fragment => Some(naga::ShaderStage::Fragment),
+            ShaderStage::TessellationControl | ShaderStage::TessellationEvaluation | ShaderStage::Geometry => None,
+        }
+    }
+}
+
+/// Errors that can happen while compiling a shader
+#[derive(Fail, Debug)]
+pub enum ShaderCompileError {
+    #[fail(display = "{}", error)]
+    Preprocess {
+        #[cause]
+        error: PreprocessError,
+    },
+
+    /// The compiler found a problem at a specific line of one of the shader's files
+    #[fail(display = "{:?}:{}: {}", file, line, message)]
+    CompileError {
+        file: PathBuf,
+        line: usize,
+        message: String,
+    },
+
+    /// The compiler found a problem with the shader, but couldn't say where
+    #[fail(display = "{:?}: {}", file, message)]
+    InvalidShader { file: PathBuf, message: String },
+
+    #[fail(display = "{:?}: Nova can't compile {:?} shaders yet", file, stage)]
+    UnsupportedStage { file: PathBuf, stage: ShaderStage },
+}
+
+/// Compiles GLSL shaders to SPIR-V
+///
+/// Shaders are compiled with naga, which only reads Vulkan-style GLSL: version 440 or later, with textures and samplers
+/// declared separately. It can't compile tessellation or geometry shaders yet
+///
+/// Compiled shaders are cached by a hash of their stage and preprocessed source, so a shader that's used by many
+/// pipelines with the same defines, or that didn't change when its shaderpack was reloaded, is only compiled once
+#[derive(Debug, Default)]
+pub struct ShaderCompiler {
+    /// The SPIR-V of every shader that's been compiled, by the hash of the shader
+    cache: HashMap<[u8; 32], Vec<u32>>,
+}
+
+impl ShaderCompiler {
+    /// Makes a compiler with an empty cache
+    pub fn new() -> ShaderCompiler {
+        ShaderCompiler::default()
+    }
+
+    /// Compiles a shader to SPIR-V, or takes the SPIR-V from the cache if the shader's been compiled before
+    ///
+    /// # Errors
+    ///
+    /// Returns every error that the compiler found. Errors point at the file and line that the problem came from,
+    /// rather than at the preprocessed shader
+    ///
+    /// # Parameters
+    ///
+    /// * `shader` - The preprocessed shader to compile
+    /// * `stage` - The pipeline stage that the shader is for
+    pub fn compile(
+        &mut self,
+        shader: &PreprocessedShader,
+        stage: ShaderStage,
+    ) -> Result<Vec<u32>, Vec<ShaderCompileError>> {
+        let hash: [u8; 32] = Sha256::new()
+            .chain_update(format!("{:?}", stage))
+            .chain_update([0])
+            .chain_update(&shader.source)
+            .finalize()
+            .into();
+        if let Some(words) = self.cache.get(&hash) {
+            return Ok(words.clone());
+        }
+
+        let words = compile_uncached(shader, stage)?;
+        self.cache.insert(hash, words.clone());
+        Ok(words)
+    }
+
+    /// Compiles every shader of a pipeline, and puts their SPIR-V in the pipeline's `ShaderSource`s
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors from every shader that failed to compile. The shaders that did compile still get their
+    /// SPIR-V, and the shaders that didn't have their SPIR-V cleared, so that none is left over from an earlier compile
+    ///
+    /// # Parameters
+    ///
+    /// * `preprocessor` - The preprocessor to read the pipeline's shaders with
+    /// * `pipeline` - The pipeline to compile the shaders of
+    pub fn compile_pipeline(
+        &mut self,
+        preprocessor: &Preprocessor<'_>,
+        pipeline: &mut PipelineCreationInfo,
+    ) -> Result<(), Vec<ShaderCompileError>> {
+        let defines = &pipeline.defines;
+        let shaders = vec![
+            (ShaderStage::Vertex, Some(&mut pipeline.vertex_shader)),
+            (
+                ShaderStage::TessellationControl,
+                pipeline.tessellation_control_shader.as_mut(),
+            ),
+            (
+                ShaderStage::TessellationEvaluation,
+                pipeline.tessellation_evaluation_shader.as_mut(),
+            ),
+            (ShaderStage::Geometry, pipeline.geometry_shader.as_mut()),
+            (ShaderStage::Fragment, pipeline.fragment_shader.as_mut()),
+        ];
+
+        let mut errors = Vec::new();
+        for (stage, shader) in shaders {
+            let shader = match shader {
+                Some(shader) => shader,
+                None => continue,
+            };
+
+            let preprocessed = match preprocessor.preprocess(&shader.filename, defines) {
+                Ok(preprocessed) => preprocessed,
+                Err(error) => {
+                    errors.push(ShaderCompileError::Preprocess { error });
+                    shader.source.clear();
+                    continue;
+                }
+            };
+            match self.compile(&preprocessed, stage) {
+                Ok(words) => shader.source = words,
+                Err(stage_errors) => {
+                    errors.extend(stage_errors);
+                    shader.source.clear();
+                }
+            }
+        }
+
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    /// The number of compiled shaders in the cache
+    pub fn cached_shaders(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Forgets every compiled shader
+    pub fn clear_cache(&mut self) {
+        self.cache.clear();
+    }
+}
+
+fn compile_uncached(shader: &PreprocessedShader, stage: ShaderStage) -> Result<Vec<u32>, Vec<ShaderCompileError>> {
+    let invalid_shader = |message: String| ShaderCompileError::InvalidShader {
+        file: shader.file.clone(),
+        message,
+    };
+    let error_at = |span: naga::Span, message: String| {
+        let location = if span.is_defined() {
+            let line = span.location(&shader.source).line_number as usize;
+            shader.source_map.location(line)
+        } else {
+            None
+        };
+        match location {
+            Some(location) => ShaderCompileError::CompileError {
+                file: location.file.clone(),
+                line: location.line,
+                message,
+            },
+            None => invalid_shader(message),
+        }
+    };
+
+    let naga_stage = match stage.naga_stage() {
+        Some(naga_stage) => naga_stage,
+        None => {
+            return Err(vec![ShaderCompileError::UnsupportedStage {
+                file: shader.file.clone(),
+                stage,
+            }]);
+        }
+    };
+
+    let module = glsl::Frontend::default()
+        .parse(&glsl::Options::from(naga_stage), &shader.source)
+        .map_err(|errors| {
+            errors
+                .into_iter()
+                .map(|error| error_at(error.meta, error.kind.to_string()))
+                .collect::<Vec<_>>()
+        })?;
+
+    let info = Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .map_err(|error| {
+            let span = error.spans().next().map(|(span, _)| *span).unwrap_or_default();
+            vec![error_at(span, error_chain(error.as_inner()))]
+        })?;
+
+    // Names are kept so that the shader's resources can be bound by name
+    let options = spv::Options {
+        flags: spv::WriterFlags::DEBUG | spv::WriterFlags::LABEL_VARYINGS,
+        ..spv::Options::default()
+    };
+    spv::write_vec(&module, &info, &options, None).map_err(|error| vec![invalid_shader(error.to_string())])
+}
+
+/// Describes an error and everything that caused it, since the compiler's errors are often nested several deep
+fn error_chain(error: &dyn Error) -> String {
+    let mut message = error.to_string();
+    let mut cause = error.source();
+    while let Some(error) = cause {
+        message.push_str(": ");
+        message.push_str(&error.to_string());
+        cause = error.source();
+    }
+
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::loading::*;
+    use crate::shaderpack::*;
+    use crate::shaders::*;
+
+    const VERTEX_SHADER: &str = "#version 450\n\
+        layout(location = 0) in vec3 position;\n\
+        void main() { gl_Position = vec4(position, 1.0); }\n";
+
+    const FRAGMENT_SHADER: &str = "#version 450\n\
+        #include \"lib/color.glsl\"\n\
+        layout(location = 0) out vec4 color;\n\
+        void main() { color = COLOR; }\n";
+
+    /// The first word of every SPIR-V module
+    const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+    #[test]
+    fn compiles_and_caches_pipelines() {
+        let (_dir, pack) = make_pack(&[
+            ("shaders/main.vert", VERTEX_SHADER),
+            ("shaders/main.frag", FRAGMENT_SHADER),
+            ("shaders/lib/color.glsl", "#define COLOR vec4(1.0)"),
+        ]);
+        let preprocessor = Preprocessor::new(&pack);
+        let mut pipeline: PipelineCreationInfo = serde_json::from_str(
+            r#"{ "name": "main", "pass": "forward", "vertexShader": "shaders/main.vert",
+                 "fragmentShader": "shaders/main.frag" }"#,
+        )
+        .unwrap();
+
+        let mut compiler = ShaderCompiler::new();
+        compiler.compile_pipeline(&preprocessor, &mut pipeline).unwrap();
+        assert_eq!(pipeline.vertex_shader.source[0], SPIRV_MAGIC);
+        assert_eq!(pipeline.fragment_shader.as_ref().unwrap().source[0], SPIRV_MAGIC);
+        assert_eq!(compiler.cached_shaders(), 2);
+
+        // Compiling the same shaders again uses the cache, while a different define makes a new shader
+        compiler.compile_pipeline(&preprocessor, &mut pipeline).unwrap();
+        assert_eq!(compiler.cached_shaders(), 2);
+        pipeline.defines.push("SHADOWS".to_owned());
+        compiler.compile_pipeline(&preprocessor, &mut pipeline).unwrap();
+        assert_eq!(compiler.cached_shaders(), 4);
+    }
+
+    #[test]
+    fn reports_errors_in_the_original_files() {
+        let (_dir, pack) = make_pack(&[
+            ("shaders/main.vert", VERTEX_SHADER),
+            ("shaders/main.frag", FRAGMENT_SHADER),
+            // The error is in a macro, which the compiler reports where the macro is defined
+            ("shaders/lib/color.glsl", "\n#define COLOR vec4(unknown)"),
+            ("shaders/main.geom", "#version 450\nvoid main() {}"),
+        ]);
+        let preprocessor = Preprocessor::new(&pack);
+        let mut pipeline: PipelineCreationInfo = serde_json::from_str(
+            r#"{ "name": "main", "pass": "forward", "vertexShader": "shaders/main.vert",
+                 "geometryShader": "shaders/main.geom", "fragmentShader": "shaders/main.frag" }"#,
+        )
+        .unwrap();
+        // As if the fragment shader compiled before it was broken
+        pipeline.fragment_shader.as_mut().unwrap().source = vec![SPIRV_MAGIC];
+
+        let errors: Vec<_> = ShaderCompiler::new()
+            .compile_pipeline(&preprocessor, &mut pipeline)
+            .unwrap_err()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                r#""shaders/main.geom": Nova can't compile Geometry shaders yet"#,
+                r#""shaders/lib/color.glsl":2: Unknown variable: unknown"#,
+            ]
+        );
+        assert_eq!(pipeline.vertex_shader.source[0], SPIRV_MAGIC);
+        assert!(pipeline.fragment_shader.as_ref().unwrap().source.is_empty());
+    }
+}
@@ -0,0 +1,20 @@
+//! Turning the shaders in a shaderpack into something that the GPU can run
+//!
+//! Shaderpacks are written in GLSL, spread across many files. Before Nova can hand a pipeline's shaders to the GPU, it
+//! stitches each shader's files together, adds the pipeline's defines, and compiles the result to SPIR-V
+
+mod binding_validation;
+mod compiler;
+mod preprocessor;
+mod reflection;
+#[cfg(test)]
+mod test_pipelines;
+mod vertex_layout;
+
+pub use binding_validation::*;
+pub use compiler::*;
+pub use preprocessor::*;
+pub use reflection::*;
+#[cfg(test)]
+pub use test_pipelines::*;
+pub use vertex_layout::*;
@@ -0,0 +1,461 @@
+//! Resolving `#include`s and adding a pipeline's defines to its shaders
+
+use crate::loading::*;
+use crate::shaderpack::*;
+use failure::Fail;
+use std::collections::HashSet;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Errors that can happen while preprocessing a shader
+#[derive(Fail, Debug)]
+pub enum PreprocessError {
+    #[fail(display = "Could not read shader {:?}", file)]
+    CouldNotRead {
+        file: PathBuf,
+        #[cause]
+        error: ResourcePackError,
+    },
+
+    /// An `#include` directive doesn't have a path in double quotes, or its path leaves the pack
+    #[fail(display = "{:?}:{}: Invalid include `{}`", file, line, include)]
+    InvalidInclude {
+        file: PathBuf,
+        line: usize,
+        include: String,
+    },
+
+    #[fail(display = "{:?}:{}: Could not include {}", file, line, include)]
+    MissingInclude {
+        file: PathBuf,
+        line: usize,
+        include: String,
+        #[cause]
+        error: ResourcePackError,
+    },
+
+    /// Some files include each other in a loop. Each file includes the next, and the last one includes the first
+    #[fail(display = "Shaders {:?} include each other in a loop", files)]
+    IncludeCycle { files: Vec<PathBuf> },
+}
+
+/// A line in one of a shader's original files
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SourceLocation {
+    /// The pack-relative path of the file
+    pub file: PathBuf,
+
+    /// The line in the file, starting at 1
+    pub line: usize,
+}
+
+/// Where each line of a preprocessed shader came from, so that compiler errors can point at the files that the
+/// shader's author wrote
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    /// The original location of each line of the preprocessed shader
+    locations: Vec<SourceLocation>,
+}
+
+impl SourceMap {
+    /// Finds where a line of the preprocessed shader came from
+    ///
+    /// The defines that the preprocessor adds come from the pipeline rather than from a file, so they're mapped to the
+    /// `#version` line that they're added after
+    ///
+    /// # Parameters
+    ///
+    /// * `line` - The line of the preprocessed shader, starting at 1
+    pub fn location(&self, line: usize) -> Option<&SourceLocation> {
+        line.checked_sub(1).and_then(|index| self.locations.get(index))
+    }
+
+    /// The number of lines in the preprocessed shader
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    /// Whether the preprocessed shader is empty
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+}
+
+/// A shader with all of its includes pasted in and its pipeline's defines added
+#[derive(Debug, Clone)]
+pub struct PreprocessedShader {
+    /// The pack-relative path of the shader
+    pub file: PathBuf,
+
+    /// The GLSL of the shader
+    pub source: String,
+
+    /// Where each line of `source` came from
+    pub source_map: SourceMap,
+}
+
+/// Stitches a shader's files together, and adds the defines of the pipeline that uses it
+///
+/// `#include "file"` directives are replaced with the contents of the file. A path that starts with `/` is relative to
+/// the include root, which is the root of the pack unless it's changed with `with_include_root`. Other paths are
+/// relative to the file that includes them. A file that has `#pragma once` is only included the first time
+///
+/// Conditional compilation is left to the compiler, so every `#include` is followed, even in a block that the
+/// compiler will skip
+///
+/// Defines are written as `NAME` or `NAME=VALUE`, like `PipelineCreationInfo::defines`. They're added right after the
+/// `#version` line. If the shader defines a symbol that the pipeline also defines, the shader's `#define` is removed so
+/// that the pipeline's value wins. A define written as `!NAME` removes the shader's `#define NAME`, so that the symbol
+/// isn't defined at all. If the pipeline has more than one define for the same symbol, only the last one is added
+pub struct Preprocessor<'a> {
+    pack: &'a dyn ResourcePack,
+
+    /// The folder that include paths starting with `/` are relative to
+    include_root: PathBuf,
+}
+
+impl<'a> Preprocessor<'a> {
+    /// Makes a preprocessor that reads shaders from the provided pack
+    ///
+    /// # Parameters
+    ///
+    /// * `pack` - The pack to read shaders and their includes from
+    pub fn new(pack: &'a dyn ResourcePack) -> Preprocessor<'a> {
+        Preprocessor {
+            pack,
+            include_root: PathBuf::new(),
+        }
+    }
+
+    /// Sets the folder that include paths starting with `/` are relative to
+    ///
+    /// Optifine shaderpacks treat their `shaders` folder as the root for includes, for example
+    ///
+    /// # Parameters
+    ///
+    /// * `include_root` - The pack-relative path of the folder
+    pub fn with_include_root<P: Into<PathBuf>>(mut self, include_root: P) -> Preprocessor<'a> {
+        self.include_root = include_root.into();
+        self
+    }
+
+    /// Preprocesses a shader
+    ///
+    /// # Parameters
+    ///
+    /// * `file` - The pack-relative path of the shader
+    /// * `defines` - The defines of the pipeline that uses the shader
+    pub fn preprocess(&self, file: &Path, defines: &[String]) -> Result<PreprocessedShader, PreprocessError> {
+        let file = normalize_pack_path(file).map_err(|error| PreprocessError::CouldNotRead {
+            file: file.to_path_buf(),
+            error,
+        })?;
+        let text = self
+            .pack
+            .read_string(&file)
+            .map_err(|error| PreprocessError::CouldNotRead {
+                file: file.clone(),
+                error,
+            })?;
+
+        let mut output = Output {
+            lines: Vec::new(),
+            source_map: SourceMap::default(),
+            replaced_defines: defines
+                .iter()
+                .map(|define| define_name(define.trim_start_matches('!')).to_owned())
+                .collect(),
+            included_once: HashSet::new(),
+        };
+        self.add_file(&file, &text, &mut vec![file.clone()], &mut output)?;
+
+        let injected: Vec<_> = defines
+            .iter()
+            .enumerate()
+            .filter(|(index, define)| {
+                let symbol = define_symbol(define);
+                !defines[index + 1..].iter().any(|later| define_symbol(later) == symbol)
+            })
+            .map(|(_, define)| define_directive(define))
+            .collect();
+        let version_line = output.lines.iter().position(|line| directive(line) == Some("version"));
+        let (position, location) = match version_line {
+            Some(index) => (index + 1, output.source_map.locations[index].clone()),
+            None => (
+                0,
+                SourceLocation {
+                    file: file.clone(),
+                    line: 1,
+                },
+            ),
+        };
+        output
+            .source_map
+            .locations
+            .splice(position..position, injected.iter().map(|_| location.clone()));
+        output.lines.splice(position..position, injected);
+
+        let mut source = output.lines.join("\n");
+        source.push('\n');
+        Ok(PreprocessedShader {
+            file,
+            source,
+            source_map: output.source_map,
+        })
+    }
+
+    /// Adds the lines of a file to the output, following its includes
+    ///
+    /// `including` holds the files that are being included, starting with the shader itself and ending with `file`
+    fn add_file(
+        &self,
+        file: &Path,
+        text: &str,
+        including: &mut Vec<PathBuf>,
+        output: &mut Output,
+    ) -> Result<(), PreprocessError> {
+        // Whether the previous line is part of a replaced `#define` and ends with a `\`, so this line is part of it too
+        let mut in_replaced_define = false;
+        for (index, line) in text.lines().enumerate() {
+            let location = SourceLocation {
+                file: file.to_path_buf(),
+                line: index + 1,
+            };
+
+            if in_replaced_define {
+                in_replaced_define = line.trim_end().ends_with('\\');
+                output.push(String::new(), location);
+                continue;
+            }
+
+            match directive(line) {
+                Some("include") => {
+                    let include = line.trim().trim_start_matches('#').trim_start()["include".len()..].trim();
+                    self.add_include(include, location, including, output)?;
+                }
+                Some("pragma") if line.split_whitespace().any(|word| word == "once") => {
+                    output.included_once.insert(file.to_path_buf());
+                    output.push(String::new(), location);
+                }
+                Some("define") if output.replaces_define(line) => {
+                    in_replaced_define = line.trim_end().ends_with('\\');
+                    output.push(String::new(), location);
+                }
+                _ => output.push(line.to_owned(), location),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_include(
+        &self,
+        include: &str,
+        location: SourceLocation,
+        including: &mut Vec<PathBuf>,
+        output: &mut Output,
+    ) -> Result<(), PreprocessError> {
+        let invalid_include = || PreprocessError::InvalidInclude {
+            file: location.file.clone(),
+            line: location.line,
+            include: include.to_owned(),
+        };
+
+        let path = include
+            .strip_prefix('"')
+            .and_then(|path| path.strip_suffix('"'))
+            .ok_or_else(invalid_include)?;
+        let path = match path.strip_prefix('/') {
+            Some(path) => self.include_root.join(path),
+            None => location.file.parent().unwrap_or_else(|| Path::new("")).join(path),
+        };
+        let path = normalize_pack_path(&path).map_err(|_| invalid_include())?;
+
+        // The include directive itself becomes a blank line, so that every line of the file keeps its own location
+        output.push(String::new(), location.clone());
+        if output.included_once.contains(&path) {
+            return Ok(());
+        }
+        if let Some(start) = including.iter().position(|file| *file == path) {
+            return Err(PreprocessError::IncludeCycle {
+                files: including[start..].to_vec(),
+            });
+        }
+
+        let text = self
+            .pack
+            .read_string(&path)
+            .map_err(|error| PreprocessError::MissingInclude {
+                file: location.file.clone(),
+                line: location.line,
+                include: include.to_owned(),
+                error,
+            })?;
+        including.push(path.clone());
+        self.add_file(&path, &text, including, output)?;
+        including.pop();
+
+        Ok(())
+    }
+}
+
+/// The preprocessed shader, as it's being built
+struct Output {
+    lines: Vec<String>,
+    source_map: SourceMap,
+
+    /// The symbols that the pipeline defines or undefines, whose `#define`s are removed from the shader
+    replaced_defines: HashSet<String>,
+
+    /// The files that have `#pragma once`, and so shouldn't be included again
+    included_once: HashSet<PathBuf>,
+}
+
+impl Output {
+    fn push(&mut self, line: String, location: SourceLocation) {
+        self.lines.push(line);
+        self.source_map.locations.push(location);
+    }
+
+    /// Whether a `#define` line defines a symbol that the pipeline replaces
+    fn replaces_define(&self, line: &str) -> bool {
+        line.trim()
+            .trim_start_matches('#')
+            .trim_start()
+            .strip_prefix("define")
+            .map(|definition| define_name(definition.trim_start()))
+            .is_some_and(|name| self.replaced_defines.contains(name))
+    }
+}
+
+/// Finds the name of the preprocessor directive on a line, like `include` for `#include "file"`
+fn directive(line: &str) -> Option<&str> {
+    let line = line.trim_start().strip_prefix('#')?.trim_start();
+    let end = line
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .unwrap_or(line.len());
+    Some(&line[..end])
+}
+
+/// Finds the name of the symbol that a definition defines, like `SHADOWS` for `SHADOWS 1`, `SHADOWS=1` or
+/// `MIX(a, b) a + b`
+fn define_name(definition: &str) -> &str {
+    let end = definition
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .unwrap_or(definition.len());
+    &definition[..end]
+}
+
+/// Turns one of a pipeline's defines into a directive
+fn define_directive(define: &str) -> String {
+    if let Some(name) = define.strip_prefix('!') {
+        return format!("#undef {}", name);
+    }
+
+    match define.find('=') {
+        Some(separator) => format!("#define {} {}", &define[..separator], &define[separator + 1..]),
+        None => format!("#define {}", define),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::loading::*;
+    use crate::shaders::*;
+    use std::path::Path;
+
+    #[test]
+    fn resolves_includes_and_injects_defines() {
+        let (_dir, pack) = make_pack(&[
+            (
+                "shaders/main.frag",
+                "#version 450\n\
+                 #define SHADOWS\n\
+                 #define QUALITY \\\n\
+                     1\n\
+                 #include \"/lib/common.glsl\"\n\
+                 #include \"util.glsl\"\n\
+                 void main() {}",
+            ),
+            (
+                "shaders/lib/common.glsl",
+                "#pragma once\n#include \"../util.glsl\"\nfloat common;",
+            ),
+            ("shaders/util.glsl", "float util;"),
+        ]);
+        let preprocessor = Preprocessor::new(&pack).with_include_root("shaders");
+        let defines = vec!["QUALITY=2".to_owned(), "!SHADOWS".to_owned(), "BLOOM".to_owned()];
+        let shader = preprocessor
+            .preprocess(Path::new("shaders/main.frag"), &defines)
+            .unwrap();
+
+        assert_eq!(
+            shader.source,
+            "#version 450\n\
+             #define QUALITY 2\n\
+             #undef SHADOWS\n\
+             #define BLOOM\n\
+             \n\
+             \n\
+             \n\
+             \n\
+             \n\
+             \n\
+             float util;\n\
+             float common;\n\
+             \n\
+             float util;\n\
+             void main() {}\n"
+        );
+
+        let location = |line| {
+            let location = shader.source_map.location(line).unwrap();
+            format!("{}:{}", location.file.display(), location.line)
+        };
+        assert_eq!(location(1), "shaders/main.frag:1");
+        assert_eq!(location(3), "shaders/main.frag:1");
+        assert_eq!(location(7), "shaders/main.frag:4");
+        assert_eq!(location(11), "shaders/util.glsl:1");
+        assert_eq!(location(12), "shaders/lib/common.glsl:3");
+        assert_eq!(location(15), "shaders/main.frag:7");
+        assert_eq!(shader.source_map.len(), 15);
+        assert!(shader.source_map.location(16).is_none());
+    }
+
+    #[test]
+    fn injects_the_last_define_of_each_symbol() {
+        let (_dir, pack) = make_pack(&[("main.frag", "#version 450\nvoid main() {}")]);
+        let defines = vec!["X=1".to_owned(), "Y".to_owned(), "X=2".to_owned(), "!Y".to_owned()];
+        let shader = Preprocessor::new(&pack)
+            .preprocess(Path::new("main.frag"), &defines)
+            .unwrap();
+
+        assert_eq!(shader.source, "#version 450\n#define X 2\n#undef Y\nvoid main() {}\n");
+        assert_eq!(shader.source_map.len(), 4);
+    }
+
+    #[test]
+    fn reports_broken_includes() {
+        let (_dir, pack) = make_pack(&[
+            ("a.glsl", "#include \"b.glsl\""),
+            ("b.glsl", "\n#include \"a.glsl\""),
+            ("missing.glsl", "#include \"nowhere.glsl\""),
+            ("escapes.glsl", "#include \"../outside.glsl\""),
+        ]);
+        let preprocessor = Preprocessor::new(&pack);
+        let error = |file: &str| preprocessor.preprocess(Path::new(file), &[]).unwrap_err().to_string();
+
+        assert_eq!(
+            error("a.glsl"),
+            r#"Shaders ["a.glsl", "b.glsl"] include each other in a loop"#
+        );
+        assert_eq!(
+            error("missing.glsl"),
+            r#""missing.glsl":1: Could not include "nowhere.glsl""#
+        );
+        assert_eq!(
+            error("escapes.glsl"),
+            r#""escapes.glsl":1: Invalid include `"../outside.glsl"`"#
+        );
+    }
+}
@@ -0,0 +1,517 @@
+//! Finding out which resources a compiled shader uses
+
+use crate::rhi::*;
+use crate::shaderpack::*;
+use crate::shaders::*;
+use failure::Fail;
+use rspirv::dr::Instruction;
+use rspirv::dr::Operand;
+use rspirv::spirv::Decoration;
+use rspirv::spirv::Dim;
+use rspirv::spirv::Op;
+use rspirv::spirv::StorageClass;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Errors that can happen while reflecting a shader
+#[derive(Fail, Debug)]
+pub enum ReflectionError {
+    #[fail(display = "{:?} hasn't been compiled", file)]
+    NotCompiled { file: PathBuf },
+
+    #[fail(display = "{:?} isn't valid SPIR-V: {}", file, message)]
+    InvalidSpirv { file: PathBuf, message: String },
+
+    /// Resources are bound by name, so a resource without one can't be bound
+    #[fail(display = "{:?}: The resource at set {} binding {} has no name", file, set, binding)]
+    UnnamedResource { file: PathBuf, set: u32, binding: u32 },
+
+    #[fail(display = "{:?}: Nova can't bind {}: {}", file, name, message)]
+    UnsupportedResource {
+        file: PathBuf,
+        name: String,
+        message: String,
+    },
+
+    /// Two of a pipeline's shaders declare a resource with the same name, but with different bindings or types
+    #[fail(
+        display = "{} is declared differently in {:?} and {:?}",
+        name, first_file, second_file
+    )]
+    ConflictingDeclarations {
+        name: String,
+        first_file: PathBuf,
+        second_file: PathBuf,
+    },
+
+    /// Two resources with different names are bound to the same binding
+    #[fail(
+        display = "{} and {} are both bound to set {} binding {}",
+        first_name, second_name, set, binding
+    )]
+    SharedBinding {
+        set: u32,
+        binding: u32,
+        first_name: String,
+        second_name: String,
+    },
+}
+
+/// The resources that one compiled shader uses
+#[derive(Debug, Clone)]
+pub struct ShaderInterface {
+    /// The pack-relative path of the shader
+    pub file: PathBuf,
+
+    /// The shader's resources, by the name of their variable. Each resource's `stages` is just this shader's stage
+    pub bindings: HashMap<String, ResourceBindingDescription>,
+
+    /// The shader's inputs that have a location, in order of their location. Built-in inputs like `gl_VertexIndex`
+    /// aren't included
+    pub inputs: Vec<ShaderInput>,
+}
+
+/// The type of each component of a shader input
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ScalarType {
+    Float,
+    Int,
+    Uint,
+}
+
+/// One of a shader's inputs, like `layout(location = 0) in vec3 position;`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ShaderInput {
+    pub name: String,
+    pub location: u32,
+    pub scalar_type: ScalarType,
+
+    /// The number of components, like 3 for a `vec3` or 1 for a `float`
+    pub components: u32,
+}
+
+/// Finds the resources that a compiled shader uses
+///
+/// Uniform blocks are named after their instance name if they have one, and after the block's name if not
+///
+/// # Parameters
+///
+/// * `shader` - The shader, with its SPIR-V filled in
+/// * `stage` - The pipeline stage that the shader is for
+pub fn reflect_shader(shader: &ShaderSource, stage: ShaderStage) -> Result<ShaderInterface, ReflectionError> {
+    let file = shader.filename.clone();
+    if shader.source.is_empty() {
+        return Err(ReflectionError::NotCompiled { file });
+    }
+    let module = rspirv::dr::load_words(&shader.source).map_err(|error| ReflectionError::InvalidSpirv {
+        file: file.clone(),
+        message: error.to_string(),
+    })?;
+
+    let module = SpirvModule::new(&module);
+    let mut bindings = HashMap::new();
+    let mut inputs = Vec::new();
+    for variable in module.variables() {
+        let storage_class = match variable.operands.first() {
+            Some(Operand::StorageClass(storage_class)) => *storage_class,
+            _ => continue,
+        };
+        if storage_class == StorageClass::Input {
+            inputs.extend(module.input(variable));
+            continue;
+        }
+        if ![
+            StorageClass::UniformConstant,
+            StorageClass::Uniform,
+            StorageClass::StorageBuffer,
+        ]
+        .contains(&storage_class)
+        {
+            continue;
+        }
+
+        let id = variable.result_id.unwrap_or_default();
+        let (set, binding) = match (
+            module.decoration(id, Decoration::DescriptorSet),
+            module.decoration(id, Decoration::Binding),
+        ) {
+            (Some(set), Some(binding)) => (set, binding),
+            _ => continue,
+        };
+
+        let pointee = variable
+            .result_type
+            .and_then(|pointer| module.instruction(pointer))
+            .and_then(|pointer| id_operand(pointer, 1));
+        let (type_id, count) = module.array_element(pointee.unwrap_or_default());
+        let name = module
+            .names
+            .get(&id)
+            .or_else(|| module.names.get(&type_id))
+            .filter(|name| !name.is_empty())
+            .cloned()
+            .ok_or_else(|| ReflectionError::UnnamedResource {
+                file: file.clone(),
+                set,
+                binding,
+            })?;
+
+        let descriptor_type =
+            module
+                .descriptor_type(type_id, storage_class)
+                .map_err(|message| ReflectionError::UnsupportedResource {
+                    file: file.clone(),
+                    name: name.clone(),
+                    message,
+                })?;
+        bindings.insert(
+            name,
+            ResourceBindingDescription {
+                set,
+                binding,
+                count,
+                descriptor_type,
+                stages: stage.flag(),
+            },
+        );
+    }
+
+    inputs.sort_by_key(|input| input.location);
+    Ok(ShaderInterface { file, bindings, inputs })
+}
+
+/// Finds the resources that every shader of a pipeline uses, in the form that `Device::create_pipeline_interface`
+/// takes
+///
+/// A resource that's used by several shaders has all of their stages. The shaders must agree on its set, binding,
+/// array size and type
+///
+/// # Errors
+///
+/// Returns every problem with every shader, including resources that the shaders declare differently
+///
+/// # Parameters
+///
+/// * `pipeline` - The pipeline, with all of its shaders compiled
+pub fn reflect_pipeline(
+    pipeline: &PipelineCreationInfo,
+) -> Result<HashMap<String, ResourceBindingDescription>, Vec<ReflectionError>> {
+    let shaders = vec![
+        (ShaderStage::Vertex, Some(&pipeline.vertex_shader)),
+        (
+            ShaderStage::TessellationControl,
+            pipeline.tessellation_control_shader.as_ref(),
+        ),
+        (
+            ShaderStage::TessellationEvaluation,
+            pipeline.tessellation_evaluation_shader.as_ref(),
+        ),
+        (ShaderStage::Geometry, pipeline.geometry_shader.as_ref()),
+        (ShaderStage::Fragment, pipeline.fragment_shader.as_ref()),
+    ];
+
+    let mut errors = Vec::new();
+    let mut bindings: HashMap<String, ResourceBindingDescription> = HashMap::new();
+    // The file that first declared each resource, for reporting conflicts
+    let mut declared_in: HashMap<String, PathBuf> = HashMap::new();
+    for (stage, shader) in shaders {
+        let interface = match shader.map(|shader| reflect_shader(shader, stage)) {
+            Some(Ok(interface)) => interface,
+            Some(Err(error)) => {
+                errors.push(error);
+                continue;
+            }
+            None => continue,
+        };
+
+        let mut names: Vec<_> = interface.bindings.keys().collect();
+        names.sort();
+        for name in names {
+            let description = &interface.bindings[name];
+            match bindings.get_mut(name) {
+                Some(existing) => {
+                    let matches = existing.set == description.set
+                        && existing.binding == description.binding
+                        && existing.count == description.count
+                        && existing.descriptor_type == description.descriptor_type;
+                    if matches {
+                        existing.stages |= description.stages;
+                    } else {
+                        errors.push(ReflectionError::ConflictingDeclarations {
+                            name: name.clone(),
+                            first_file: declared_in[name].clone(),
+                            second_file: interface.file.clone(),
+                        });
+                    }
+                }
+                None => {
+                    let shared = bindings.iter().find(|(_, existing)| {
+                        existing.set == description.set && existing.binding == description.binding
+                    });
+                    if let Some((first_name, _)) = shared {
+                        errors.push(ReflectionError::SharedBinding {
+                            set: description.set,
+                            binding: description.binding,
+                            first_name: first_name.clone(),
+                            second_name: name.clone(),
+                        });
+                        continue;
+                    }
+
+                    bindings.insert(name.clone(), description.clone());
+                    declared_in.insert(name.clone(), interface.file.clone());
+                }
+            }
+        }
+    }
+
+    if errors.is_empty() { Ok(bindings) } else { Err(errors) }
+}
+
+/// A SPIR-V module, with its instructions indexed for looking things up by id
+struct SpirvModule<'a> {
+    module: &'a rspirv::dr::Module,
+
+    /// The types, constants and global variables, by their result id
+    instructions: HashMap<u32, &'a Instruction>,
+
+    /// The names from `OpName`, by the id that they name
+    names: HashMap<u32, String>,
+}
+
+impl<'a> SpirvModule<'a> {
+    fn new(module: &'a rspirv::dr::Module) -> SpirvModule<'a> {
+        let instructions = module
+            .types_global_values
+            .iter()
+            .filter_map(|instruction| instruction.result_id.map(|id| (id, instruction)))
+            .collect();
+        let names = module
+            .debug_names
+            .iter()
+            .filter(|instruction| instruction.class.opcode == Op::Name)
+            .filter_map(
+                |instruction| match (instruction.operands.first(), instruction.operands.get(1)) {
+                    (Some(Operand::IdRef(id)), Some(Operand::LiteralString(name))) => Some((*id, name.clone())),
+                    _ => None,
+                },
+            )
+            .collect();
+
+        SpirvModule {
+            module,
+            instructions,
+            names,
+        }
+    }
+
+    fn instruction(&self, id: u32) -> Option<&'a Instruction> {
+        self.instructions.get(&id).copied()
+    }
+
+    fn variables(&self) -> impl Iterator<Item = &'a Instruction> {
+        self.module
+            .types_global_values
+            .iter()
+            .filter(|instruction| instruction.class.opcode == Op::Variable)
+    }
+
+    /// Finds the literal value of a decoration on an id. Decorations without a value, like `Block`, have a value of 0
+    fn decoration(&self, id: u32, decoration: Decoration) -> Option<u32> {
+        self.module.annotations.iter().find_map(|instruction| {
+            if instruction.class.opcode != Op::Decorate {
+                return None;
+            }
+            match instruction.operands.as_slice() {
+                [Operand::IdRef(target), Operand::Decoration(found), rest @ ..]
+                    if *target == id && *found == decoration =>
+                {
+                    match rest.first() {
+                        Some(Operand::LiteralInt32(value)) => Some(*value),
+                        _ => Some(0),
+                    }
+                }
+                _ => None,
+            }
+        })
+    }
+
+    /// Unwraps an array type into the type of its elements and its length. Types that aren't arrays have a length of 1
+    fn array_element(&self, type_id: u32) -> (u32, u32) {
+        let instruction = match self.instruction(type_id) {
+            Some(instruction) => instruction,
+            None => return (type_id, 1),
+        };
+        let element = id_operand(instruction, 0).unwrap_or_default();
+        match instruction.class.opcode {
+            Op::TypeArray => {
+                let length = id_operand(instruction, 1)
+                    .and_then(|length| self.instruction(length))
+                    .and_then(|constant| match constant.operands.first() {
+                        Some(Operand::LiteralInt32(length)) => Some(*length),
+                        _ => None,
+                    })
+                    .unwrap_or(1);
+                (element, length)
+            }
+            Op::TypeRuntimeArray => (element, 0),
+            _ => (type_id, 1),
+        }
+    }
+
+    /// Describes an input variable, if it has a location and a type that a vertex attribute can have
+    fn input(&self, variable: &Instruction) -> Option<ShaderInput> {
+        let id = variable.result_id?;
+        let location = self.decoration(id, Decoration::Location)?;
+        let type_id = id_operand(self.instruction(variable.result_type?)?, 1)?;
+
+        let mut type_instruction = self.instruction(type_id)?;
+        let mut components = 1;
+        if type_instruction.class.opcode == Op::TypeVector {
+            components = match type_instruction.operands.get(1) {
+                Some(Operand::LiteralInt32(components)) => *components,
+                _ => return None,
+            };
+            type_instruction = self.instruction(id_operand(type_instruction, 0)?)?;
+        }
+        let scalar_type = match (type_instruction.class.opcode, type_instruction.operands.get(1)) {
+            (Op::TypeFloat, _) => ScalarType::Float,
+            (Op::TypeInt, Some(Operand::LiteralInt32(0))) => ScalarType::Uint,
+            (Op::TypeInt, _) => ScalarType::Int,
+            _ => return None,
+        };
+
+        Some(ShaderInput {
+            name: self.names.get(&id).cloned().unwrap_or_default(),
+            location,
+            scalar_type,
+            components,
+        })
+    }
+
+    fn descriptor_type(&self, type_id: u32, storage_class: StorageClass) -> Result<DescriptorType, String> {
+        let instruction = self
+            .instruction(type_id)
+            .ok_or_else(|| "Its type isn't declared".to_owned())?;
+        match (instruction.class.opcode, storage_class) {
+            (Op::TypeSampledImage, _) => Ok(DescriptorType::CombinedImageSampler),
+            (Op::TypeSampler, _) => Ok(DescriptorType::Sampler),
+            (Op::TypeImage, _) => match (instruction.operands.get(1), instruction.operands.get(5)) {
+                (Some(Operand::Dim(Dim::DimSubpassData)), _) => Err("Subpass inputs aren't supported".to_owned()),
+                (_, Some(Operand::LiteralInt32(1))) => Ok(DescriptorType::SampledImage),
+                (_, Some(Operand::LiteralInt32(2))) => Err("Storage images aren't supported".to_owned()),
+                _ => Err("Images that aren't known to be sampled or storage images aren't supported".to_owned()),
+            },
+            (Op::TypeStruct, StorageClass::StorageBuffer) => Ok(DescriptorType::StorageBuffer),
+            (Op::TypeStruct, StorageClass::Uniform) => {
+                if self.decoration(type_id, Decoration::BufferBlock).is_some() {
+                    Ok(DescriptorType::StorageBuffer)
+                } else {
+                    Ok(DescriptorType::UniformBuffer)
+                }
+            }
+            (opcode, _) => Err(format!("Resources of type {:?} aren't supported", opcode)),
+        }
+    }
+}
+
+fn id_operand(instruction: &Instruction, index: usize) -> Option<u32> {
+    match instruction.operands.get(index) {
+        Some(Operand::IdRef(id)) => Some(*id),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rhi::*;
+    use crate::shaders::*;
+
+    const PIPELINE: &str =
+        r#"{ "name": "main", "pass": "forward", "vertexShader": "main.vert", "fragmentShader": "main.frag" }"#;
+
+    const VERTEX_SHADER: &str = "#version 450\n\
+        layout(set = 0, binding = 0) uniform Camera { mat4 view_projection; } camera;\n\
+        layout(location = 0) in vec3 position;\n\
+        layout(location = 0) out vec2 uv;\n\
+        void main() { gl_Position = camera.view_projection * vec4(position, 1.0); uv = position.xy; }\n";
+
+    #[test]
+    fn merges_bindings_across_stages() {
+        let pipeline = compiled_pipeline(
+            PIPELINE,
+            &[
+                ("main.vert", VERTEX_SHADER),
+                (
+                    "main.frag",
+                    "#version 450\n\
+             layout(set = 0, binding = 0) uniform Camera { mat4 view_projection; } camera;\n\
+             layout(set = 1, binding = 0) uniform texture2D colortex;\n\
+             layout(set = 1, binding = 1) uniform sampler point;\n\
+             layout(set = 2, binding = 0) buffer Lights { vec4 positions[]; };\n\
+             layout(location = 0) in vec2 uv;\n\
+             layout(location = 0) out vec4 color;\n\
+             void main() {\n\
+                 color = texture(sampler2D(colortex, point), uv) * camera.view_projection[0] + positions[0];\n\
+             }\n",
+                ),
+            ],
+        );
+
+        let bindings = reflect_pipeline(&pipeline).unwrap();
+        assert_eq!(bindings.len(), 4);
+        assert_eq!(
+            bindings["camera"],
+            ResourceBindingDescription {
+                set: 0,
+                binding: 0,
+                count: 1,
+                descriptor_type: DescriptorType::UniformBuffer,
+                stages: ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT,
+            }
+        );
+        assert_eq!(
+            (bindings["colortex"].count, &bindings["colortex"].descriptor_type),
+            (1, &DescriptorType::SampledImage)
+        );
+        assert_eq!(bindings["point"].descriptor_type, DescriptorType::Sampler);
+        assert_eq!(
+            (bindings["Lights"].set, &bindings["Lights"].descriptor_type),
+            (2, &DescriptorType::StorageBuffer)
+        );
+        assert_eq!(bindings["Lights"].stages, ShaderStageFlags::FRAGMENT);
+    }
+
+    #[test]
+    fn reports_conflicting_declarations() {
+        let pipeline = compiled_pipeline(
+            PIPELINE,
+            &[
+                ("main.vert", VERTEX_SHADER),
+                (
+                    "main.frag",
+                    "#version 450\n\
+             layout(set = 0, binding = 1) uniform Camera { mat4 view_projection; } camera;\n\
+             layout(set = 0, binding = 0) uniform texture2D colortex0;\n\
+             layout(set = 0, binding = 2) uniform sampler point;\n\
+             layout(location = 0) in vec2 uv;\n\
+             layout(location = 0) out vec4 color;\n\
+             void main() { color = texture(sampler2D(colortex0, point), uv) * camera.view_projection[0]; }\n",
+                ),
+            ],
+        );
+
+        let errors: Vec<_> = reflect_pipeline(&pipeline)
+            .unwrap_err()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                r#"camera is declared differently in "main.vert" and "main.frag""#,
+                "camera and colortex0 are both bound to set 0 binding 0",
+            ]
+        );
+    }
+}
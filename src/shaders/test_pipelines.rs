@@ -0,0 +1,20 @@
+//! Compiled pipelines for tests
+
+use crate::loading::*;
+use crate::shaderpack::*;
+use crate::shaders::*;
+
+/// Compiles a pipeline whose shaders are in a temporary resource pack
+///
+/// # Parameters
+///
+/// * `pipeline` - The pipeline's JSON. The paths of its shaders are relative to the root of the pack
+/// * `files` - The path of each file in the pack, and its contents
+pub fn compiled_pipeline(pipeline: &str, files: &[(&str, &str)]) -> PipelineCreationInfo {
+    let (_dir, pack) = make_pack(files);
+    let mut pipeline: PipelineCreationInfo = serde_json::from_str(pipeline).unwrap();
+    ShaderCompiler::new()
+        .compile_pipeline(&Preprocessor::new(&pack), &mut pipeline)
+        .unwrap();
+    pipeline
+}
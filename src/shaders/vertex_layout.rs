@@ -0,0 +1,312 @@
+//! Working out how a pipeline's vertex fields are laid out in each vertex
+
+use crate::shaderpack::*;
+use crate::shaders::*;
+use failure::Fail;
+
+/// Every attribute starts at a multiple of this many bytes, and the stride is padded to a multiple of it too
+///
+/// Some GPUs can't read vertex attributes that aren't aligned to four bytes, and the rest read them more slowly
+pub const VERTEX_ATTRIBUTE_ALIGNMENT: u32 = 4;
+
+/// Errors that can happen while checking a vertex layout against a vertex shader
+#[derive(Fail, Debug)]
+pub enum VertexLayoutError {
+    #[fail(display = "Could not reflect the vertex shader of pipeline {}", pipeline)]
+    Reflection {
+        pipeline: String,
+        #[cause]
+        error: ReflectionError,
+    },
+
+    #[fail(
+        display = "Pipeline {}'s vertex shader reads {}, but the pipeline has no vertex field with that name",
+        pipeline, input
+    )]
+    MissingField { pipeline: String, input: String },
+
+    #[fail(
+        display = "Pipeline {}'s vertex shader reads {} from location {}, but the vertex field is at location {}",
+        pipeline, input, location, expected
+    )]
+    WrongLocation {
+        pipeline: String,
+        input: String,
+        location: u32,
+        expected: u32,
+    },
+
+    #[fail(
+        display = "Pipeline {}'s vertex shader reads {} as {:?}, but the vertex field is {:?}",
+        pipeline, input, scalar_type, format
+    )]
+    IncompatibleType {
+        pipeline: String,
+        input: String,
+        scalar_type: ScalarType,
+        format: VertexFormat,
+    },
+}
+
+/// The format that a vertex field is stored in
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum VertexFormat {
+    /// Three 32-bit floats
+    R32G32B32Sfloat,
+    /// Two 32-bit floats
+    R32G32Sfloat,
+    /// Four 8-bit values that the shader reads as floats between 0 and 1
+    R8G8B8A8Unorm,
+    /// Two 8-bit values that the shader reads as floats between 0 and 1
+    R8G8Unorm,
+    /// One 32-bit unsigned integer
+    R32Uint,
+}
+
+impl VertexFormat {
+    /// The format that a vertex field is stored in
+    pub fn of(field: &VertexField) -> VertexFormat {
+        match field {
+            VertexField::Position | VertexField::Normal | VertexField::Tangent | VertexField::McEntityId => {
+                VertexFormat::R32G32B32Sfloat
+            }
+            VertexField::UV0 | VertexField::MidTexCoord => VertexFormat::R32G32Sfloat,
+            VertexField::Color => VertexFormat::R8G8B8A8Unorm,
+            VertexField::UV1 => VertexFormat::R8G8Unorm,
+            VertexField::VirtualTextureId => VertexFormat::R32Uint,
+        }
+    }
+
+    /// The type that a shader reads each component of this format as
+    pub fn scalar_type(self) -> ScalarType {
+        match self {
+            VertexFormat::R32G32B32Sfloat
+            | VertexFormat::R32G32Sfloat
+            | VertexFormat::R8G8B8A8Unorm
+            | VertexFormat::R8G8Unorm => ScalarType::Float,
+            VertexFormat::R32Uint => ScalarType::Uint,
+        }
+    }
+}
+
+/// Where one vertex field is in each vertex
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct VertexAttribute {
+    /// The name of the vertex shader input that reads this field
+    pub semantic_name: String,
+    pub field: VertexField,
+
+    /// The location of the vertex shader input that reads this field
+    pub location: u32,
+    pub format: VertexFormat,
+
+    /// How many bytes from the start of the vertex this field starts
+    pub offset: u32,
+
+    /// How many bytes of padding come after this field, to align the next field or the end of the vertex
+    pub padding: u32,
+}
+
+/// How a pipeline's vertex fields are laid out in each vertex
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct VertexLayout {
+    /// The fields, in the order that they're stored in each vertex
+    pub attributes: Vec<VertexAttribute>,
+
+    /// The size of each vertex, in bytes
+    pub stride: u32,
+}
+
+impl VertexLayout {
+    /// Lays out vertex fields one after another, in the order that they're listed
+    ///
+    /// Each field goes at the location with the same index as the field, and starts on a multiple of
+    /// `VERTEX_ATTRIBUTE_ALIGNMENT` bytes
+    ///
+    /// # Parameters
+    ///
+    /// * `fields` - The vertex fields of a pipeline
+    pub fn new(fields: &[VertexFieldData]) -> VertexLayout {
+        let mut attributes: Vec<VertexAttribute> = Vec::new();
+        let mut offset = 0;
+        for (location, field) in fields.iter().enumerate() {
+            let size = field.field.size_in_bytes();
+            let padding = align(size) - size;
+            attributes.push(VertexAttribute {
+                semantic_name: field.semantic_name.clone(),
+                field: field.field.clone(),
+                location: location as u32,
+                format: VertexFormat::of(&field.field),
+                offset,
+                padding,
+            });
+            offset += size + padding;
+        }
+
+        VertexLayout {
+            attributes,
+            stride: offset,
+        }
+    }
+
+    /// Lays out a pipeline's vertex fields, and checks the layout against the pipeline's vertex shader
+    ///
+    /// # Errors
+    ///
+    /// Returns every problem with the layout, or the error from reflecting the vertex shader
+    ///
+    /// # Parameters
+    ///
+    /// * `pipeline` - The pipeline, with its vertex shader compiled
+    pub fn for_pipeline(pipeline: &PipelineCreationInfo) -> Result<VertexLayout, Vec<VertexLayoutError>> {
+        let interface = reflect_shader(&pipeline.vertex_shader, ShaderStage::Vertex).map_err(|error| {
+            vec![VertexLayoutError::Reflection {
+                pipeline: pipeline.name.clone(),
+                error,
+            }]
+        })?;
+
+        let layout = VertexLayout::new(&pipeline.vertex_fields);
+        layout.check_inputs(&pipeline.name, &interface.inputs)?;
+        Ok(layout)
+    }
+
+    /// Checks that every input of a vertex shader reads a vertex field with the same name, at the same location, that
+    /// has the type the shader expects
+    ///
+    /// The number of components doesn't need to match. Vulkan drops the components that the shader doesn't read, and
+    /// fills in missing components with 0, or with 1 for the fourth component
+    ///
+    /// # Parameters
+    ///
+    /// * `pipeline` - The name of the pipeline, for errors
+    /// * `inputs` - The vertex shader's inputs
+    pub fn check_inputs(&self, pipeline: &str, inputs: &[ShaderInput]) -> Result<(), Vec<VertexLayoutError>> {
+        let mut errors = Vec::new();
+        for input in inputs {
+            let attribute = self
+                .attributes
+                .iter()
+                .find(|attribute| attribute.semantic_name == input.name);
+            match attribute {
+                None => errors.push(VertexLayoutError::MissingField {
+                    pipeline: pipeline.to_owned(),
+                    input: input.name.clone(),
+                }),
+                Some(attribute) if attribute.location != input.location => {
+                    errors.push(VertexLayoutError::WrongLocation {
+                        pipeline: pipeline.to_owned(),
+                        input: input.name.clone(),
+                        location: input.location,
+                        expected: attribute.location,
+                    })
+                }
+                Some(attribute) if attribute.format.scalar_type() != input.scalar_type => {
+                    errors.push(VertexLayoutError::IncompatibleType {
+                        pipeline: pipeline.to_owned(),
+                        input: input.name.clone(),
+                        scalar_type: input.scalar_type,
+                        format: attribute.format,
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+}
+
+/// Rounds a size up to the next multiple of `VERTEX_ATTRIBUTE_ALIGNMENT`
+fn align(size: u32) -> u32 {
+    size.div_ceil(VERTEX_ATTRIBUTE_ALIGNMENT) * VERTEX_ATTRIBUTE_ALIGNMENT
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shaderpack::*;
+    use crate::shaders::*;
+
+    fn terrain_pipeline(vertex_fields: &str, vertex_shader: &str) -> PipelineCreationInfo {
+        let pipeline = format!(
+            r#"{{ "name": "terrain", "pass": "forward", "vertexShader": "main.vert", "vertexFields": {} }}"#,
+            vertex_fields
+        );
+        compiled_pipeline(&pipeline, &[("main.vert", vertex_shader)])
+    }
+
+    const VERTEX_FIELDS: &str = r#"[
+        { "name": "position", "field": "Position" },
+        { "name": "lightmap_uv", "field": "UV1" },
+        { "name": "color", "field": "Color" },
+        { "name": "texture_id", "field": "VirtualTextureId" }
+    ]"#;
+
+    #[test]
+    fn pads_fields_to_four_bytes() {
+        let fields: Vec<VertexFieldData> = serde_json::from_str(VERTEX_FIELDS).unwrap();
+        let layout = VertexLayout::new(&fields);
+
+        let attributes: Vec<_> = layout
+            .attributes
+            .iter()
+            .map(|attribute| {
+                (
+                    attribute.location,
+                    attribute.format,
+                    attribute.offset,
+                    attribute.padding,
+                )
+            })
+            .collect();
+        assert_eq!(
+            attributes,
+            vec![
+                (0, VertexFormat::R32G32B32Sfloat, 0, 0),
+                (1, VertexFormat::R8G8Unorm, 12, 2),
+                (2, VertexFormat::R8G8B8A8Unorm, 16, 0),
+                (3, VertexFormat::R32Uint, 20, 0),
+            ]
+        );
+        assert_eq!(layout.stride, 24);
+    }
+
+    #[test]
+    fn checks_layouts_against_vertex_shaders() {
+        let pipeline = terrain_pipeline(
+            VERTEX_FIELDS,
+            "#version 450\n\
+             layout(location = 0) in vec3 position;\n\
+             layout(location = 1) in vec2 lightmap_uv;\n\
+             layout(location = 3) flat in uint texture_id;\n\
+             layout(location = 0) out vec2 uv;\n\
+             void main() { gl_Position = vec4(position, float(texture_id)); uv = lightmap_uv; }\n",
+        );
+        let layout = VertexLayout::for_pipeline(&pipeline).unwrap();
+        assert_eq!(layout.attributes.len(), 4);
+
+        let pipeline = terrain_pipeline(
+            VERTEX_FIELDS,
+            "#version 450\n\
+             layout(location = 0) in vec3 position;\n\
+             layout(location = 2) in vec2 lightmap_uv;\n\
+             layout(location = 3) in vec4 texture_id;\n\
+             layout(location = 4) in vec3 normal;\n\
+             void main() { gl_Position = vec4(position + normal, lightmap_uv.x) + texture_id; }\n",
+        );
+        let errors: Vec<_> = VertexLayout::for_pipeline(&pipeline)
+            .unwrap_err()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "Pipeline terrain's vertex shader reads lightmap_uv from location 2, but the vertex field is at \
+                 location 1",
+                "Pipeline terrain's vertex shader reads texture_id as Float, but the vertex field is R32Uint",
+                "Pipeline terrain's vertex shader reads normal, but the pipeline has no vertex field with that name",
+            ]
+        );
+    }
+}